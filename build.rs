@@ -0,0 +1,20 @@
+//build script, makes the c header for the c api when the capi feature is on
+//the header is written to OUT_DIR, and a test checks the copy in include/ matches it
+fn main() {
+    #[cfg(feature = "capi")]
+    {
+        let crate_dir: String = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out_dir: String = std::env::var("OUT_DIR").unwrap();
+        let config: cbindgen::Config =
+            cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+                .expect("could not read cbindgen.toml");
+        cbindgen::Builder::new()
+            .with_src(format!("{}/src/capi.rs", crate_dir))
+            .with_config(config)
+            .generate()
+            .expect("could not make the c header")
+            .write_to_file(format!("{}/chip8.h", out_dir));
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+    }
+}
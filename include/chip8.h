@@ -0,0 +1,97 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* made by cbindgen from src/capi.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+typedef struct Chip8 Chip8;
+
+/**
+ * Size of the display returned by `chip8_framebuffer`, one byte per pixel in rows.
+ */
+#define CHIP8_DISPLAY_WIDTH 64
+
+#define CHIP8_DISPLAY_HEIGHT 32
+
+/**
+ * Version of the C API, raised when a function changes.
+ */
+#define CHIP8_API_VERSION 1
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Make a machine with the font loaded and nothing else in memory.
+ */
+Chip8 *chip8_new(void);
+
+/**
+ * Free a machine from `chip8_new`.
+ *
+ * # Safety
+ * `chip8` must be null or a pointer from `chip8_new` that has not been freed.
+ */
+void chip8_free(Chip8 *chip8);
+
+/**
+ * Reset the machine and load a ROM at 0x200. Returns 0, or -1 if it does not fit in memory.
+ *
+ * # Safety
+ * `chip8` must be null or a live machine, and `rom` must point to `length` readable bytes.
+ */
+int32_t chip8_load_rom(Chip8 *chip8, const uint8_t *rom, size_t length);
+
+/**
+ * Seed the random number generator so runs repeat exactly.
+ *
+ * # Safety
+ * `chip8` must be null or a live machine.
+ */
+void chip8_seed(Chip8 *chip8, uint64_t seed);
+
+/**
+ * Run instructions until the end of the frame, call 60 times a second for normal speed.
+ * Returns 0, or -1 if `chip8` is null. Any ROM can be run, instructions that do not exist are
+ * skipped.
+ *
+ * # Safety
+ * `chip8` must be null or a live machine.
+ */
+int32_t chip8_run_frame(Chip8 *chip8);
+
+/**
+ * Press or release a key from 0 to 15, other keys are ignored.
+ *
+ * # Safety
+ * `chip8` must be null or a live machine.
+ */
+void chip8_set_key(Chip8 *chip8, uint8_t key, bool pressed);
+
+/**
+ * Get the display, `CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT` bytes in rows, 1 for a lit pixel.
+ * The pointer stays valid until the machine is freed.
+ *
+ * # Safety
+ * `chip8` must be null or a live machine.
+ */
+const uint8_t *chip8_framebuffer(const Chip8 *chip8);
+
+/**
+ * Check if the beeper should be sounding.
+ *
+ * # Safety
+ * `chip8` must be null or a live machine.
+ */
+bool chip8_sound_active(const Chip8 *chip8);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
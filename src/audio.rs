@@ -0,0 +1,456 @@
+//use declarations
+use crate::chip8::FRAMES_PER_SECOND;
+use serde::{Deserialize, Serialize};
+
+//beeper waveforms
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    Square,
+    //square wave high for pulse_width of each cycle
+    Pulse,
+    Triangle,
+    Sine,
+    //random level held for each cycle
+    Noise,
+}
+
+impl Waveform {
+    //waveforms in the order the settings screen cycles through them
+    pub const ALL: [Waveform; 5] = [
+        Waveform::Square,
+        Waveform::Pulse,
+        Waveform::Triangle,
+        Waveform::Sine,
+        Waveform::Noise,
+    ];
+
+    //name used in the config file and on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Waveform::Square => "square",
+            Waveform::Pulse => "pulse",
+            Waveform::Triangle => "triangle",
+            Waveform::Sine => "sine",
+            Waveform::Noise => "noise",
+        }
+    }
+
+    //parse a waveform name
+    pub fn parse(text: &str) -> Result<Waveform, String> {
+        match text {
+            "square" => Ok(Waveform::Square),
+            "pulse" => Ok(Waveform::Pulse),
+            "triangle" => Ok(Waveform::Triangle),
+            "sine" => Ok(Waveform::Sine),
+            "noise" => Ok(Waveform::Noise),
+            _ => Err(format!(
+                "unknown waveform \"{}\", expected square, pulse, triangle, sine or noise",
+                text
+            )),
+        }
+    }
+}
+
+//beeper settings struct
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeeperSettings {
+    //tone frequency in hz
+    pub frequency: f32,
+    pub waveform: Waveform,
+    //part of each cycle the pulse wave is high, from 0 to 1
+    pub pulse_width: f32,
+    //volume from 0 to 1
+    pub volume: f32,
+    //time in milliseconds to fade in when the sound starts and out when it stops
+    pub attack: f32,
+    pub release: f32,
+    pub muted: bool,
+}
+
+impl Default for BeeperSettings {
+    fn default() -> BeeperSettings {
+        BeeperSettings {
+            frequency: 440.0,
+            waveform: Waveform::Square,
+            pulse_width: 0.25,
+            volume: 0.25,
+            attack: 2.0,
+            release: 5.0,
+            muted: false,
+        }
+    }
+}
+
+//audio settings as stored in the config file, unset values are taken from the level above
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct AudioConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waveform: Option<Waveform>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pulse_width: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attack: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub muted: Option<bool>,
+}
+
+impl AudioConfig {
+    //overwrite the settings that are set in other
+    pub fn merge(&mut self, other: &AudioConfig) {
+        self.frequency = other.frequency.or(self.frequency);
+        self.waveform = other.waveform.or(self.waveform);
+        self.pulse_width = other.pulse_width.or(self.pulse_width);
+        self.volume = other.volume.or(self.volume);
+        self.attack = other.attack.or(self.attack);
+        self.release = other.release.or(self.release);
+        self.muted = other.muted.or(self.muted);
+    }
+
+    //overwrite the settings that are set here
+    pub fn apply(&self, settings: &mut BeeperSettings) {
+        if let Some(frequency) = self.frequency {
+            settings.frequency = frequency;
+        }
+        if let Some(waveform) = self.waveform {
+            settings.waveform = waveform;
+        }
+        if let Some(pulse_width) = self.pulse_width {
+            settings.pulse_width = pulse_width;
+        }
+        if let Some(volume) = self.volume {
+            settings.volume = volume;
+        }
+        if let Some(attack) = self.attack {
+            settings.attack = attack;
+        }
+        if let Some(release) = self.release {
+            settings.release = release;
+        }
+        if let Some(muted) = self.muted {
+            settings.muted = muted;
+        }
+    }
+}
+
+//beeper struct and methods
+//the beeper plays all the time and the sound timer opens and closes a gate,
+//fading the tone in and out so it does not click when it starts and stops
+pub struct Beeper {
+    settings: BeeperSettings,
+    sample_rate: u32,
+    phase: f32,
+    //whether the sound timer is running, and the current fade level from 0 to 1
+    gate: bool,
+    envelope: f32,
+    //state of the noise generator and the level it is holding
+    noise_seed: u32,
+    noise_level: f32,
+}
+
+impl Beeper {
+    pub fn new(settings: BeeperSettings, sample_rate: u32) -> Beeper {
+        Beeper {
+            settings,
+            sample_rate,
+            phase: 0.0,
+            gate: false,
+            envelope: 0.0,
+            noise_seed: 0x1234_5678,
+            noise_level: 0.0,
+        }
+    }
+
+    pub fn settings(&self) -> BeeperSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: BeeperSettings) {
+        self.settings = settings;
+    }
+
+    //start or stop the tone
+    pub fn set_gate(&mut self, gate: bool) {
+        self.gate = gate;
+    }
+
+    //get the next sample from -1 to 1
+    pub fn next_sample(&mut self) -> f32 {
+        //move the envelope toward the gate, taking attack or release milliseconds to get there
+        let (target, time): (f32, f32) = if self.gate {
+            (1.0, self.settings.attack)
+        } else {
+            (0.0, self.settings.release)
+        };
+        let step: f32 = if time > 0.0 {
+            1000.0 / (time * self.sample_rate as f32)
+        } else {
+            1.0
+        };
+        if self.envelope < target {
+            self.envelope = (self.envelope + step).min(target);
+        } else if self.envelope > target {
+            self.envelope = (self.envelope - step).max(target);
+        }
+
+        //stay silent without advancing the wave when faded out so each beep starts the same
+        if self.envelope == 0.0 {
+            self.phase = 0.0;
+            return 0.0;
+        }
+
+        //generate the wave at the current phase
+        let wave: f32 = match self.settings.waveform {
+            Waveform::Square => {
+                if self.phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Pulse => {
+                if self.phase < self.settings.pulse_width {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Waveform::Sine => (self.phase * std::f32::consts::TAU).sin(),
+            Waveform::Noise => self.noise_level,
+        };
+
+        //advance the phase, picking a new noise level each cycle
+        self.phase += self.settings.frequency / self.sample_rate as f32;
+        if self.phase >= 1.0 {
+            self.phase %= 1.0;
+            self.noise_seed ^= self.noise_seed << 13;
+            self.noise_seed ^= self.noise_seed >> 17;
+            self.noise_seed ^= self.noise_seed << 5;
+            self.noise_level = (self.noise_seed >> 8) as f32 / (1 << 23) as f32 - 1.0;
+        }
+
+        let volume: f32 = if self.settings.muted {
+            0.0
+        } else {
+            self.settings.volume
+        };
+        wave * volume * self.envelope
+    }
+}
+
+//audio stream struct and methods
+//keeps a list of when the sound timer started and stopped during each emulated frame,
+//then renders the frame's samples with each change at the matching sample, so a sound timer
+//of N always plays for exactly N frames of emulated time no matter how the host is scheduled
+pub struct AudioStream {
+    beeper: Beeper,
+    sample_rate: u32,
+    //frames and samples rendered so far
+    frames: u64,
+    samples: u64,
+    //instructions run this frame, and the sound changes this frame as (instruction, on) pairs
+    instruction: u32,
+    events: Vec<(u32, bool)>,
+    sound_on: bool,
+}
+
+impl AudioStream {
+    pub fn new(settings: BeeperSettings, sample_rate: u32) -> AudioStream {
+        AudioStream {
+            beeper: Beeper::new(settings, sample_rate),
+            sample_rate,
+            frames: 0,
+            samples: 0,
+            instruction: 0,
+            events: Vec::new(),
+            sound_on: false,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn beeper_mut(&mut self) -> &mut Beeper {
+        &mut self.beeper
+    }
+
+    //note an instruction was run, and whether the sound timer is running after it
+    pub fn instruction(&mut self, sound_on: bool) {
+        self.instruction += 1;
+        if sound_on != self.sound_on {
+            self.events.push((self.instruction, sound_on));
+            self.sound_on = sound_on;
+        }
+    }
+
+    //render the samples for the frame that just ended
+    pub fn end_frame(&mut self) -> Vec<f32> {
+        //work out how many samples this frame gets, keeping the total exact over many frames
+        self.frames += 1;
+        let total: u64 =
+            (self.frames as f64 * self.sample_rate as f64 / FRAMES_PER_SECOND).round() as u64;
+        let count: usize = (total - self.samples) as usize;
+        self.samples = total;
+
+        //render the samples, switching the gate at each sound change
+        let mut samples: Vec<f32> = Vec::with_capacity(count);
+        let mut events = self.events.iter().peekable();
+        for i in 0..count {
+            while let Some((instruction, on)) = events.peek() {
+                if (*instruction as usize * count) / self.instruction as usize > i {
+                    break;
+                }
+                self.beeper.set_gate(*on);
+                events.next();
+            }
+            samples.push(self.beeper.next_sample());
+        }
+
+        //changes at the very end of the frame take effect from the start of the next one
+        for (_, on) in events {
+            self.beeper.set_gate(*on);
+        }
+        self.events.clear();
+        self.instruction = 0;
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+
+    //settings with no fade, so the sound starts and stops on the sample
+    fn no_fade() -> BeeperSettings {
+        BeeperSettings {
+            attack: 0.0,
+            release: 0.0,
+            ..BeeperSettings::default()
+        }
+    }
+
+    #[test]
+    fn frames_add_up_to_the_sample_rate() {
+        for sample_rate in [44100, 48000, 22051, 1] {
+            let mut stream: AudioStream = AudioStream::new(no_fade(), sample_rate);
+            let mut samples: usize = 0;
+            for _ in 0..FRAMES_PER_SECOND as usize * 3 {
+                for _ in 0..8 {
+                    stream.instruction(false);
+                }
+                let frame: Vec<f32> = stream.end_frame();
+                assert!(frame.len().abs_diff(sample_rate as usize / 60) <= 1);
+                samples += frame.len();
+            }
+            assert_eq!(samples, sample_rate as usize * 3);
+        }
+    }
+
+    #[test]
+    fn sound_timer_plays_for_its_frames() {
+        //V0 = 5, set the sound timer, then loop
+        let mut chip8: Chip8 = Chip8::new();
+        chip8
+            .load_rom(&[0x60, 0x05, 0xF0, 0x18, 0x12, 0x04])
+            .unwrap();
+        let mut stream: AudioStream = AudioStream::new(no_fade(), 48000);
+        let mut sounding: Vec<bool> = Vec::new();
+        for _ in 0..8 {
+            while !chip8.step() {
+                stream.instruction(chip8.sound_timer != 0);
+            }
+            stream.instruction(chip8.sound_timer != 0);
+            let samples: Vec<f32> = stream.end_frame();
+            sounding.push(samples.iter().any(|sample| *sample != 0.0));
+        }
+        assert_eq!(
+            sounding,
+            [true, true, true, true, true, false, false, false]
+        );
+    }
+
+    #[test]
+    fn muted_beeper_is_silent() {
+        let mut beeper: Beeper = Beeper::new(
+            BeeperSettings {
+                muted: true,
+                ..no_fade()
+            },
+            48000,
+        );
+        beeper.set_gate(true);
+        assert!((0..1000).all(|_| beeper.next_sample() == 0.0));
+    }
+
+    #[test]
+    fn waveforms_stay_within_the_volume() {
+        for waveform in Waveform::ALL {
+            let mut beeper: Beeper = Beeper::new(
+                BeeperSettings {
+                    waveform,
+                    volume: 0.5,
+                    ..no_fade()
+                },
+                48000,
+            );
+            beeper.set_gate(true);
+            let samples: Vec<f32> = (0..4800).map(|_| beeper.next_sample()).collect();
+            assert!(samples.iter().all(|sample| sample.abs() <= 0.5));
+            assert!(samples.iter().any(|sample| *sample != 0.0));
+        }
+    }
+
+    #[test]
+    fn fades_in_over_the_attack() {
+        //10 milliseconds at 1000 samples a second is 10 samples
+        let mut beeper: Beeper = Beeper::new(
+            BeeperSettings {
+                attack: 10.0,
+                volume: 1.0,
+                frequency: 1.0,
+                ..BeeperSettings::default()
+            },
+            1000,
+        );
+        beeper.set_gate(true);
+        let samples: Vec<f32> = (0..12).map(|_| beeper.next_sample()).collect();
+        assert!(samples[0] > 0.0 && samples[0] < 0.2);
+        assert!(samples.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert_eq!(samples[11], 1.0);
+    }
+
+    #[test]
+    fn waveform_names_round_trip() {
+        for waveform in Waveform::ALL {
+            assert_eq!(Waveform::parse(waveform.name()), Ok(waveform));
+        }
+        assert!(Waveform::parse("sawtooth").is_err());
+    }
+
+    #[test]
+    fn audio_config_overrides_what_is_set() {
+        let mut config: AudioConfig = AudioConfig {
+            frequency: Some(220.0),
+            volume: Some(0.5),
+            ..AudioConfig::default()
+        };
+        config.merge(&AudioConfig {
+            volume: Some(0.1),
+            ..AudioConfig::default()
+        });
+        let mut settings: BeeperSettings = BeeperSettings::default();
+        config.apply(&mut settings);
+        assert_eq!(settings.frequency, 220.0);
+        assert_eq!(settings.volume, 0.1);
+        assert_eq!(settings.waveform, Waveform::Square);
+    }
+}
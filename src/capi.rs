@@ -0,0 +1,132 @@
+//c api for embedding the interpreter, the header is made by build.rs with the capi feature and
+//checked in as include/chip8.h
+//machines are opaque pointers from chip8_new that have to be freed with chip8_free, and null
+//pointers are ignored
+//doc comments here are copied into the header
+
+//use declarations
+use crate::chip8::{Chip8, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+
+/// Size of the display returned by `chip8_framebuffer`, one byte per pixel in rows.
+pub const CHIP8_DISPLAY_WIDTH: usize = 64;
+pub const CHIP8_DISPLAY_HEIGHT: usize = 32;
+const _: () =
+    assert!(CHIP8_DISPLAY_WIDTH == DISPLAY_WIDTH && CHIP8_DISPLAY_HEIGHT == DISPLAY_HEIGHT);
+
+/// Version of the C API, raised when a function changes.
+pub const CHIP8_API_VERSION: u32 = 1;
+
+/// Make a machine with the font loaded and nothing else in memory.
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    Box::into_raw(Box::new(Chip8::new()))
+}
+
+/// Free a machine from `chip8_new`.
+///
+/// # Safety
+/// `chip8` must be null or a pointer from `chip8_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Reset the machine and load a ROM at 0x200. Returns 0, or -1 if it does not fit in memory.
+///
+/// # Safety
+/// `chip8` must be null or a live machine, and `rom` must point to `length` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, rom: *const u8, length: usize) -> i32 {
+    let chip8: &mut Chip8 = match chip8.as_mut() {
+        Some(chip8) if !rom.is_null() => chip8,
+        _ => return -1,
+    };
+    chip8.reset();
+    match chip8.load_rom(slice::from_raw_parts(rom, length)) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Seed the random number generator so runs repeat exactly.
+///
+/// # Safety
+/// `chip8` must be null or a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_seed(chip8: *mut Chip8, seed: u64) {
+    if let Some(chip8) = chip8.as_mut() {
+        chip8.seed(seed);
+    }
+}
+
+/// Run instructions until the end of the frame, call 60 times a second for normal speed.
+/// Returns 0, or -1 if `chip8` is null. Any ROM can be run, instructions that do not exist are
+/// skipped.
+///
+/// # Safety
+/// `chip8` must be null or a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip8: *mut Chip8) -> i32 {
+    let chip8: &mut Chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return -1,
+    };
+    //the interpreter does not panic on any rom, but a panic must never unwind into c
+    match panic::catch_unwind(AssertUnwindSafe(|| while !chip8.step() {})) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Press or release a key from 0 to 15, other keys are ignored.
+///
+/// # Safety
+/// `chip8` must be null or a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, pressed: bool) {
+    if let Some(input) = chip8
+        .as_mut()
+        .and_then(|chip8| chip8.input.get_mut(key as usize))
+    {
+        *input = pressed as u8;
+    }
+}
+
+/// Get the display, `CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT` bytes in rows, 1 for a lit pixel.
+/// The pointer stays valid until the machine is freed.
+///
+/// # Safety
+/// `chip8` must be null or a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const Chip8) -> *const u8 {
+    match chip8.as_ref() {
+        Some(chip8) => chip8.display.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// Check if the beeper should be sounding.
+///
+/// # Safety
+/// `chip8` must be null or a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound_active(chip8: *const Chip8) -> bool {
+    chip8.as_ref().is_some_and(|chip8| chip8.sound_timer != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn header_is_up_to_date() {
+        assert!(
+            include_str!(concat!(env!("OUT_DIR"), "/chip8.h"))
+                == include_str!("../include/chip8.h"),
+            "include/chip8.h is out of date, copy it from {}/chip8.h",
+            env!("OUT_DIR")
+        );
+    }
+}
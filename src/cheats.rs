@@ -0,0 +1,542 @@
+//use declarations
+use crate::osd;
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::EventPump;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+//most search matches listed on the cheats screen, more have to be narrowed down first
+const MAX_SHOWN_MATCHES: usize = 16;
+
+//a code that writes a value to an address every frame, freezing it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat {
+    pub address: usize,
+    pub value: u8,
+    pub name: String,
+    pub enabled: bool,
+}
+
+//cheats struct and methods
+//holds the cheats for a rom and the file they are kept in
+//the file has a code on each line written as hex address=value, with a name after a # and a -
+//in front for a cheat that is switched off, lines starting with # are comments
+pub struct Cheats {
+    pub path: PathBuf,
+    pub cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    //load the cheats from a file, a missing file has no cheats
+    pub fn load(path: &Path) -> Result<Cheats, String> {
+        let mut cheats: Cheats = Cheats {
+            path: path.to_path_buf(),
+            cheats: Vec::new(),
+        };
+        if !path.exists() {
+            return Ok(cheats);
+        }
+        let text: String = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        for (number, line) in text.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cheat: Cheat = parse_cheat(line)
+                .map_err(|e| format!("{} line {}: {}", path.display(), number + 1, e))?;
+            cheats.cheats.push(cheat);
+        }
+        Ok(cheats)
+    }
+
+    //write the cheats back to their file
+    pub fn save(&self) -> Result<(), String> {
+        let mut text: String = String::new();
+        for cheat in &self.cheats {
+            text.push_str(&format!(
+                "{}{:03X}={:02X}",
+                if cheat.enabled { "" } else { "-" },
+                cheat.address,
+                cheat.value
+            ));
+            if !cheat.name.is_empty() {
+                text.push_str(&format!(" # {}", cheat.name));
+            }
+            text.push('\n');
+        }
+        fs::write(&self.path, text)
+            .map_err(|e| format!("could not write {}: {}", self.path.display(), e))
+    }
+
+    //write the enabled cheats to memory, done every frame
+    pub fn apply(&self, memory: &mut [u8]) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            memory[cheat.address] = cheat.value;
+        }
+    }
+}
+
+//parse a cheat such as -2F0=03 # lives
+fn parse_cheat(line: &str) -> Result<Cheat, String> {
+    let (code, name): (&str, &str) = line.split_once('#').unwrap_or((line, ""));
+    let code: &str = code.trim();
+    let (enabled, code): (bool, &str) = match code.strip_prefix('-') {
+        Some(code) => (false, code),
+        None => (true, code),
+    };
+    let (address, value): (&str, &str) = code
+        .split_once('=')
+        .ok_or_else(|| format!("expected address=value, found \"{}\"", code))?;
+    let address: usize = usize::from_str_radix(address.trim().trim_start_matches("0x"), 16)
+        .ok()
+        .filter(|address| *address < 0x1000)
+        .ok_or_else(|| format!("invalid address \"{}\"", address.trim()))?;
+    let value: u8 = u8::from_str_radix(value.trim().trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid value \"{}\"", value.trim()))?;
+    Ok(Cheat {
+        address,
+        value,
+        name: name.trim().to_string(),
+        enabled,
+    })
+}
+
+//ways to narrow down a ram search, comparing memory now with the last snapshot
+#[derive(Clone, Copy, PartialEq)]
+pub enum Comparison {
+    //equal to the search value
+    EqualTo,
+    //equal to the snapshot
+    Unchanged,
+    Changed,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    pub const ALL: [Comparison; 5] = [
+        Comparison::EqualTo,
+        Comparison::Unchanged,
+        Comparison::Changed,
+        Comparison::Increased,
+        Comparison::Decreased,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Comparison::EqualTo => "equal to value",
+            Comparison::Unchanged => "unchanged",
+            Comparison::Changed => "changed",
+            Comparison::Increased => "increased",
+            Comparison::Decreased => "decreased",
+        }
+    }
+
+    //check a byte against the snapshot
+    fn keeps(self, now: u8, before: u8, value: u8) -> bool {
+        match self {
+            Comparison::EqualTo => now == value,
+            Comparison::Unchanged => now == before,
+            Comparison::Changed => now != before,
+            Comparison::Increased => now > before,
+            Comparison::Decreased => now < before,
+        }
+    }
+}
+
+//ram search struct and methods
+//finds addresses holding things like a lives counter by taking a snapshot of memory and keeping
+//the addresses that compare with it the right way as the game is played
+#[derive(Default)]
+pub struct RamSearch {
+    snapshot: Vec<u8>,
+    matches: Vec<usize>,
+    //value compared with by Comparison::EqualTo
+    pub value: u8,
+}
+
+impl RamSearch {
+    //start a new search with every address matching
+    pub fn start(&mut self, memory: &[u8]) {
+        self.snapshot = memory.to_vec();
+        self.matches = (0..memory.len()).collect();
+    }
+
+    //check if a search has been started
+    pub fn started(&self) -> bool {
+        !self.snapshot.is_empty()
+    }
+
+    //keep the addresses that compare with the snapshot, then take a new snapshot
+    pub fn narrow(&mut self, memory: &[u8], comparison: Comparison) {
+        let snapshot: &[u8] = &self.snapshot;
+        let value: u8 = self.value;
+        self.matches
+            .retain(|address| comparison.keeps(memory[*address], snapshot[*address], value));
+        self.snapshot = memory.to_vec();
+    }
+
+    //addresses still matching
+    pub fn matches(&self) -> &[usize] {
+        &self.matches
+    }
+}
+
+//lines on the cheats screen
+#[derive(Clone, Copy, PartialEq)]
+enum Item {
+    NewSearch,
+    //value for Comparison::EqualTo
+    Value,
+    Narrow(Comparison),
+    //a search match by its address, picked to add it as a cheat
+    Match(usize),
+    //a cheat by its index
+    Cheat(usize),
+}
+
+//get the lines on the screen for the current search and cheats
+fn items(search: &RamSearch, cheats: &Cheats) -> Vec<Item> {
+    let mut items: Vec<Item> = vec![Item::NewSearch];
+    if search.started() {
+        items.push(Item::Value);
+        items.extend(
+            Comparison::ALL
+                .iter()
+                .map(|comparison| Item::Narrow(*comparison)),
+        );
+        if search.matches().len() <= MAX_SHOWN_MATCHES {
+            items.extend(search.matches().iter().map(|address| Item::Match(*address)));
+        }
+    }
+    items.extend((0..cheats.cheats.len()).map(Item::Cheat));
+    items
+}
+
+//get the label and value shown for an item
+fn line(item: Item, search: &RamSearch, cheats: &Cheats, memory: &[u8]) -> (String, String) {
+    match item {
+        Item::NewSearch => (
+            "new search".to_string(),
+            if search.started() {
+                format!("{} matches", search.matches().len())
+            } else {
+                "snapshot memory".to_string()
+            },
+        ),
+        Item::Value => (
+            "value".to_string(),
+            format!("{:02X} ({})", search.value, search.value),
+        ),
+        Item::Narrow(comparison) => (format!("keep {}", comparison.name()), String::new()),
+        Item::Match(address) => (
+            format!("match {:03X}", address),
+            format!(
+                "{:02X} ({}), add as cheat",
+                memory[address], memory[address]
+            ),
+        ),
+        Item::Cheat(index) => {
+            let cheat: &Cheat = &cheats.cheats[index];
+            (
+                format!(
+                    "cheat {:03X}={:02X} {}",
+                    cheat.address, cheat.value, cheat.name
+                ),
+                if cheat.enabled { "on" } else { "off" }.to_string(),
+            )
+        }
+    }
+}
+
+//show the cheats screen until it is closed, searching memory and changing the cheats
+//returns whether the cheats were changed, or None if the window was closed
+pub fn run(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut EventPump,
+    cheats: &mut Cheats,
+    search: &mut RamSearch,
+    memory: &[u8],
+    title: &str,
+) -> Result<Option<bool>, String> {
+    let mut selected: usize = 0;
+    let mut changed: bool = false;
+
+    loop {
+        let items: Vec<Item> = items(search, cheats);
+        selected = selected.min(items.len() - 1);
+
+        //input handling, with the keyboard or a game controller
+        //enter picks an item, left and right change values and switch cheats, delete removes them
+        let mut step: Option<i32> = None;
+        let mut pick: bool = false;
+        let mut remove: bool = false;
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => return Ok(None),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::B, ..
+                } => return Ok(Some(changed)),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Up),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::DPadUp,
+                    ..
+                } => selected = selected.saturating_sub(1),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Down),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::DPadDown,
+                    ..
+                } => selected = (selected + 1).min(items.len() - 1),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Left),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::DPadLeft,
+                    ..
+                } => step = Some(-1),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Right),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::DPadRight,
+                    ..
+                } => step = Some(1),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::A, ..
+                } => pick = true,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Delete),
+                    ..
+                } => remove = true,
+                _ => {}
+            }
+        }
+
+        match (items[selected], pick, step) {
+            (Item::Cheat(index), _, _) if remove => {
+                cheats.cheats.remove(index);
+                changed = true;
+            }
+            (Item::NewSearch, true, _) => search.start(memory),
+            (Item::Value, _, Some(step)) => search.value = search.value.wrapping_add(step as u8),
+            (Item::Narrow(comparison), true, _) => {
+                search.narrow(memory, comparison);
+            }
+            (Item::Match(address), true, _) => {
+                cheats.cheats.push(Cheat {
+                    address,
+                    value: memory[address],
+                    name: String::new(),
+                    enabled: true,
+                });
+                changed = true;
+            }
+            (Item::Cheat(index), true, _) => {
+                cheats.cheats[index].enabled = !cheats.cheats[index].enabled;
+                changed = true;
+            }
+            (Item::Cheat(index), _, Some(step)) => {
+                let cheat: &mut Cheat = &mut cheats.cheats[index];
+                cheat.value = cheat.value.wrapping_add(step as u8);
+                changed = true;
+            }
+            _ => {}
+        }
+
+        draw(canvas, &items, selected, search, cheats, memory, title)?;
+        ::std::thread::sleep(Duration::from_millis(16));
+    }
+}
+
+//draw the cheats screen
+fn draw(
+    canvas: &mut Canvas<Window>,
+    items: &[Item],
+    selected: usize,
+    search: &RamSearch,
+    cheats: &Cheats,
+    memory: &[u8],
+    title: &str,
+) -> Result<(), String> {
+    //size the font to the window, keeping lines for the header and the help at the bottom
+    let (_, output_height): (u32, u32) = canvas.output_size()?;
+    let size: i32 = (output_height as i32 / 160).max(1);
+    let line_height: i32 = 7 * size;
+    let lines: usize = (output_height as i32 / line_height) as usize;
+    let visible: usize = lines.saturating_sub(3).max(1);
+
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    osd::draw_text(canvas, &format!("cheats - {}", title), size, size, 0)?;
+
+    //the part of the list around the selected item, with an arrow at the selected one
+    let first: usize = selected.saturating_sub(visible - 1);
+    for (row, (index, item)) in items
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(visible)
+        .enumerate()
+    {
+        let (label, value): (String, String) = line(*item, search, cheats, memory);
+        let marker: &str = if index == selected { "> " } else { "  " };
+        let y: i32 = (row as i32 + 2) * line_height;
+        osd::draw_text(
+            canvas,
+            &format!("{}{:<30}{}", marker, label, value),
+            size,
+            size,
+            y,
+        )?;
+    }
+
+    osd::draw_text(
+        canvas,
+        "up/down pick, enter use, left/right change, del remove, esc done",
+        size,
+        size,
+        (lines as i32 - 1) * line_height,
+    )?;
+    canvas.present();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cheats() {
+        assert_eq!(
+            parse_cheat("-2F0=03 # lives"),
+            Ok(Cheat {
+                address: 0x2F0,
+                value: 0x03,
+                name: "lives".to_string(),
+                enabled: false,
+            })
+        );
+        assert_eq!(
+            parse_cheat("0x1ff = 0xAB"),
+            Ok(Cheat {
+                address: 0x1FF,
+                value: 0xAB,
+                name: String::new(),
+                enabled: true,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_cheats() {
+        assert!(parse_cheat("2F0").unwrap_err().contains("address=value"));
+        assert!(parse_cheat("1000=01")
+            .unwrap_err()
+            .contains("invalid address"));
+        assert!(parse_cheat("2F0=100")
+            .unwrap_err()
+            .contains("invalid value"));
+    }
+
+    #[test]
+    fn saves_and_loads_cheats() {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("chip8_cheats_test_{}.cht", std::process::id()));
+        let cheats: Cheats = Cheats {
+            path: path.clone(),
+            cheats: vec![
+                parse_cheat("2F0=03 # lives").unwrap(),
+                parse_cheat("-300=FF").unwrap(),
+            ],
+        };
+        cheats.save().unwrap();
+        let text: String = fs::read_to_string(&path).unwrap();
+        fs::write(&path, format!("# a comment\n\n{}", text)).unwrap();
+        let loaded: Cheats = Cheats::load(&path).unwrap();
+        fs::write(&path, "2F0=03\nnonsense\n").unwrap();
+        let error: String = Cheats::load(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.cheats, cheats.cheats);
+        assert!(error.contains("line 2"));
+
+        //a missing file has no cheats
+        assert!(Cheats::load(&path).unwrap().cheats.is_empty());
+    }
+
+    #[test]
+    fn applies_enabled_cheats() {
+        let cheats: Cheats = Cheats {
+            path: PathBuf::new(),
+            cheats: vec![
+                parse_cheat("002=07").unwrap(),
+                parse_cheat("-003=09").unwrap(),
+            ],
+        };
+        let mut memory: [u8; 4] = [0; 4];
+        cheats.apply(&mut memory);
+        assert_eq!(memory, [0, 0, 7, 0]);
+    }
+
+    #[test]
+    fn ram_search_narrows_down_addresses() {
+        let mut search: RamSearch = RamSearch::default();
+        assert!(!search.started());
+        let mut memory: [u8; 6] = [3, 3, 3, 3, 0, 9];
+        search.start(&memory);
+        assert!(search.started());
+        assert_eq!(search.matches().len(), 6);
+
+        //lives go down at 1 and 3, something else changes at 5
+        memory[1] = 2;
+        memory[3] = 2;
+        memory[5] = 10;
+        search.narrow(&memory, Comparison::Changed);
+        assert_eq!(search.matches(), [1, 3, 5]);
+        search.narrow(&memory, Comparison::Unchanged);
+        assert_eq!(search.matches(), [1, 3, 5]);
+
+        memory[1] = 1;
+        memory[3] = 1;
+        memory[5] = 11;
+        search.narrow(&memory, Comparison::Decreased);
+        assert_eq!(search.matches(), [1, 3]);
+
+        memory[3] = 4;
+        search.narrow(&memory, Comparison::Increased);
+        assert_eq!(search.matches(), [3]);
+
+        search.value = 4;
+        search.narrow(&memory, Comparison::EqualTo);
+        assert_eq!(search.matches(), [3]);
+        search.value = 5;
+        search.narrow(&memory, Comparison::EqualTo);
+        assert!(search.matches().is_empty());
+    }
+}
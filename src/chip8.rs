@@ -0,0 +1,1140 @@
+//use declarations
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+//size of the emulated display in pixels
+pub const DISPLAY_WIDTH: usize = 64;
+pub const DISPLAY_HEIGHT: usize = 32;
+
+//frames per second, with the timers ticking at the end of every frame at 60hz like the original
+//default instructions run per frame and per second, roms can run more instructions per frame,
+//which keeps the frame rate and runs them faster
+pub const FRAMES_PER_SECOND: f64 = 60.0;
+pub const INSTRUCTIONS_PER_FRAME: u32 = 8;
+pub const INSTRUCTIONS_PER_SECOND: f64 = FRAMES_PER_SECOND * INSTRUCTIONS_PER_FRAME as f64;
+
+//address programs are loaded at
+pub const PROGRAM_START: usize = 0x200;
+
+//behaviours that differ between chip 8 interpreters, named as in the community chip 8 database
+//the defaults match how this emulator has always run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    //8XY6 and 8XYE shift Vx in place instead of copying Vy first
+    pub shift: bool,
+    //FX55 and FX65 add X to the index register, or X + 1 when memory_leave_i_unchanged is off
+    pub memory_increment_by_x: bool,
+    pub memory_leave_i_unchanged: bool,
+    //sprites wrap around the edges of the display instead of being clipped
+    pub wrap: bool,
+    //BNNN jumps to NNN + VX, where X is the top digit of NNN, instead of NNN + V0
+    pub jump: bool,
+    //DXYN waits for the end of the frame before the next instruction
+    pub vblank: bool,
+    //8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub logic: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: false,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
+
+//names of the quirks in the community chip 8 database, in the order quirk_mut takes them
+pub const QUIRK_NAMES: [&str; 7] = [
+    "shift",
+    "memoryIncrementByX",
+    "memoryLeaveIUnchanged",
+    "wrap",
+    "jump",
+    "vblank",
+    "logic",
+];
+
+//get a quirk by its index in QUIRK_NAMES
+pub fn quirk_mut(quirks: &mut Quirks, index: usize) -> &mut bool {
+    match index {
+        0 => &mut quirks.shift,
+        1 => &mut quirks.memory_increment_by_x,
+        2 => &mut quirks.memory_leave_i_unchanged,
+        3 => &mut quirks.wrap,
+        4 => &mut quirks.jump,
+        5 => &mut quirks.vblank,
+        _ => &mut quirks.logic,
+    }
+}
+
+//save states start with a magic number and version, and are a fixed size
+const STATE_MAGIC: &[u8] = b"C8ST\x01";
+const STATE_SIZE: usize = 5 + 0x1000 + 0x800 + 0x10 + 2 + 2 + 0xFF * 2 + 1 + 1 + 1 + 4 + 8 + 1 + 8;
+
+//chip 8 machine struct and methods
+//holds the emulated memory, registers, timers, display and keypad
+pub struct Chip8 {
+    pub memory: [u8; 0x1000],
+    pub display: [u8; 0x800],
+    pub registers: [u8; 0x10],
+    pub program_counter: usize,
+    pub index_register: u16,
+    pub stack: [usize; 0xFF],
+    pub stack_index: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub input: [u8; 0x10],
+    //instructions run since the timers last ticked, and how many are run each frame
+    pub timer_counter: u32,
+    pub instructions_per_frame: u32,
+    //frames run since the program started
+    pub frame: u64,
+    pub quirks: Quirks,
+    //set after drawing with the vblank quirk, no more instructions run until the frame ends
+    pub waiting_for_vblank: bool,
+    //state of the random number generator used by CXNN
+    pub random_state: u64,
+    //start and length of the memory last written by FX33 or FX55, for the memory viewer to pick up
+    pub last_write: Option<(usize, usize)>,
+    //address and opcode of the last instruction that does not exist, for the frontend to report
+    pub bad_opcode: Option<(usize, u16)>,
+}
+
+impl Default for Chip8 {
+    fn default() -> Chip8 {
+        Chip8::new()
+    }
+}
+
+impl Chip8 {
+    //make a machine with the font loaded and nothing else in memory
+    //the random numbers are seeded from the clock, except in webassembly where there is no clock
+    //and every machine starts from the same seed, so call seed there for different runs
+    pub fn new() -> Chip8 {
+        let mut chip8: Chip8 = Chip8 {
+            memory: [0; 0x1000],
+            display: [0; 0x800],
+            registers: [0; 0x10],
+            program_counter: PROGRAM_START,
+            index_register: 0x0,
+            stack: [0; 0xFF],
+            stack_index: 0x0,
+            delay_timer: 0x0,
+            sound_timer: 0x0,
+            input: [0; 0x10],
+            timer_counter: 0,
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            frame: 0,
+            quirks: Quirks::default(),
+            waiting_for_vblank: false,
+            random_state: 0,
+            last_write: None,
+            bad_opcode: None,
+        };
+
+        //seed the random numbers from the clock, use seed for repeatable runs
+        chip8.seed(clock_seed());
+
+        //font setup
+        chip8.memory[..0x50].clone_from_slice(&[
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+            0x20, 0x60, 0x20, 0x20, 0x70, // 1
+            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+        ]);
+
+        chip8
+    }
+
+    //copy a program into memory at the program start address
+    pub fn load_rom(&mut self, contents: &[u8]) -> Result<(), String> {
+        self.load_rom_at(contents, PROGRAM_START)
+    }
+
+    //copy a program into memory at an address and start running it from there
+    pub fn load_rom_at(&mut self, contents: &[u8], address: usize) -> Result<(), String> {
+        if address >= self.memory.len() || contents.len() > self.memory.len() - address {
+            return Err(format!(
+                "rom is {} bytes, the most that fits in memory at {:#05x} is {}",
+                contents.len(),
+                address,
+                self.memory.len().saturating_sub(address)
+            ));
+        }
+        self.memory[address..address + contents.len()].copy_from_slice(contents);
+        self.program_counter = address;
+        Ok(())
+    }
+
+    //put the machine back to how it starts, with the font loaded and nothing else in memory
+    //the quirks, speed, keypad and random numbers are kept, the rom has to be loaded again
+    pub fn reset(&mut self) {
+        *self = Chip8 {
+            input: self.input,
+            instructions_per_frame: self.instructions_per_frame,
+            quirks: self.quirks,
+            random_state: self.random_state,
+            ..Chip8::new()
+        };
+    }
+
+    //save the machine state as bytes, for restoring it later with load_state
+    //the rom is kept in memory so it does not have to be loaded again, while the quirks, speed and
+    //keys held are settings of the frontend and are left out
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state: Vec<u8> = Vec::with_capacity(STATE_SIZE);
+        state.extend_from_slice(STATE_MAGIC);
+        state.extend_from_slice(&self.memory);
+        state.extend_from_slice(&self.display);
+        state.extend_from_slice(&self.registers);
+        state.extend_from_slice(&(self.program_counter as u16).to_le_bytes());
+        state.extend_from_slice(&self.index_register.to_le_bytes());
+        for address in self.stack {
+            state.extend_from_slice(&(address as u16).to_le_bytes());
+        }
+        state.push(self.stack_index as u8);
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        state.extend_from_slice(&self.timer_counter.to_le_bytes());
+        state.extend_from_slice(&self.frame.to_le_bytes());
+        state.push(self.waiting_for_vblank as u8);
+        state.extend_from_slice(&self.random_state.to_le_bytes());
+        state
+    }
+
+    //restore a machine state saved by save_state
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        if state.len() != STATE_SIZE || !state.starts_with(STATE_MAGIC) {
+            return Err("not a chip 8 save state".to_string());
+        }
+        let mut rest: &[u8] = &state[STATE_MAGIC.len()..];
+        let mut stack: [usize; 0xFF] = [0; 0xFF];
+        let memory: &[u8] = take(&mut rest, 0x1000);
+        let display: &[u8] = take(&mut rest, 0x800);
+        let registers: &[u8] = take(&mut rest, 0x10);
+        let program_counter: u16 = u16::from_le_bytes(take_array(&mut rest));
+        let index_register: u16 = u16::from_le_bytes(take_array(&mut rest));
+        for address in stack.iter_mut() {
+            *address = u16::from_le_bytes(take_array(&mut rest)) as usize;
+        }
+        let [stack_index, delay_timer, sound_timer]: [u8; 3] = take_array(&mut rest);
+        let timer_counter: u32 = u32::from_le_bytes(take_array(&mut rest));
+        let frame: u64 = u64::from_le_bytes(take_array(&mut rest));
+        let [waiting_for_vblank]: [u8; 1] = take_array(&mut rest);
+        let random_state: u64 = u64::from_le_bytes(take_array(&mut rest));
+        let stack_index: usize = stack_index as usize;
+        if program_counter as usize >= self.memory.len() || stack_index > stack.len() {
+            return Err("save state has an invalid program counter or stack".to_string());
+        }
+
+        self.memory.copy_from_slice(memory);
+        self.display.copy_from_slice(display);
+        self.registers.copy_from_slice(registers);
+        self.program_counter = program_counter as usize;
+        self.index_register = index_register;
+        self.stack = stack;
+        self.stack_index = stack_index;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.timer_counter = timer_counter;
+        self.frame = frame;
+        self.waiting_for_vblank = waiting_for_vblank != 0;
+        self.random_state = random_state;
+        self.last_write = None;
+        self.bad_opcode = None;
+        Ok(())
+    }
+
+    //seed the random number generator
+    pub fn seed(&mut self, seed: u64) {
+        //splitmix64 spreads the seed out, and keeps the xorshift state from being 0
+        let mut z: u64 = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        self.random_state = (z ^ (z >> 31)) | 1;
+    }
+
+    //get the next random byte with xorshift64*
+    fn random_byte(&mut self) -> u8 {
+        self.random_state ^= self.random_state >> 12;
+        self.random_state ^= self.random_state << 25;
+        self.random_state ^= self.random_state >> 27;
+        (self.random_state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    //run one instruction and update the timers
+    //returns true if the timers ticked, which marks the end of a frame
+    //any rom can be run without panicking, addresses wrap around memory and the stack wraps around
+    //when it overflows or underflows
+    pub fn step(&mut self) -> bool {
+        //load 2 byte opcode
+        let opcode = ((self.memory[self.program_counter & 0xFFF] as u16) << 8)
+            | (self.memory[(self.program_counter + 1) & 0xFFF] as u16);
+
+        //check opcode and execute
+        if self.waiting_for_vblank {
+            //waiting for the end of the frame after drawing, run nothing
+            //undo the program counter increment below
+            self.program_counter = self.program_counter.wrapping_sub(2);
+        } else if opcode == 0x00E0 {
+            //clear screen (00E0)
+            //clear
+            for i in 0..0xFF {
+                self.display[i as usize] = 0x00;
+            }
+        } else if opcode == 0x00EE {
+            //return from subroutine
+            //take the highest part of stack off and set program counter to it
+            self.stack_index = (self.stack_index + self.stack.len() - 1) % self.stack.len();
+            self.program_counter = self.stack[self.stack_index];
+        } else if (opcode & 0xF000) == 0x1000 {
+            //jump (1NNN)
+            //the program counter increment below is undone with a wrapping subtraction
+            self.program_counter = ((opcode & 0x0FFF) as usize).wrapping_sub(2);
+        } else if (opcode & 0xF000) == 0x2000 {
+            //call subroutine (2NNN)
+            //set highest part of stack to program counter
+            self.stack[self.stack_index % self.stack.len()] = self.program_counter;
+            self.stack_index = (self.stack_index + 1) % self.stack.len();
+
+            //set program counter equal to passed value
+            self.program_counter = ((opcode & 0x0FFF) as usize).wrapping_sub(2);
+        } else if (opcode & 0xF000) == 0x3000 {
+            //instruction skip (3XNN) if Vx = NN
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //get value to compare register to
+            let cmp_val = (opcode & 0x00FF) as u8;
+
+            //compare
+            if self.registers[reg_num] == cmp_val {
+                self.program_counter = self.program_counter.wrapping_add(2);
+            }
+        } else if (opcode & 0xF000) == 0x4000 {
+            //instruction skip (4XNN) if Vx != NN
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //get value to compare register to
+            let cmp_val = (opcode & 0x00FF) as u8;
+
+            //compare
+            if self.registers[reg_num] != cmp_val {
+                self.program_counter = self.program_counter.wrapping_add(2);
+            }
+        } else if (opcode & 0xF00F) == 0x5000 {
+            //instruction skip (5XY0) if Vx = Vy
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //compare
+            if self.registers[reg_num_x] == self.registers[reg_num_y] {
+                self.program_counter = self.program_counter.wrapping_add(2);
+            }
+        } else if (opcode & 0xF000) == 0x6000 {
+            //set register vx (6XNN)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //get value to set register to
+            let reg_val = (opcode & 0x00FF) as u8;
+
+            //update register
+            self.registers[reg_num] = reg_val;
+        } else if (opcode & 0xF000) == 0x7000 {
+            //add value to register vx (7XNN)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //get value to set register to
+            let reg_val = (opcode & 0x00FF) as u8;
+
+            //update register
+            self.registers[reg_num] = self.registers[reg_num].wrapping_add(reg_val);
+        } else if (opcode & 0xF00F) == 0x8000 {
+            //set value of Vx to value of Vy (8XY0)
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //update register
+            self.registers[reg_num_x] = self.registers[reg_num_y];
+        } else if (opcode & 0xF00F) == 0x8001 {
+            //set value of Vx to value of Vx or Vy (8XY1)
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //update register
+            self.registers[reg_num_x] |= self.registers[reg_num_y];
+            if self.quirks.logic {
+                self.registers[0xF] = 0;
+            }
+        } else if (opcode & 0xF00F) == 0x8002 {
+            //set value of Vx to value of Vx and Vy (8XY2)
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //update register
+            self.registers[reg_num_x] &= self.registers[reg_num_y];
+            if self.quirks.logic {
+                self.registers[0xF] = 0;
+            }
+        } else if (opcode & 0xF00F) == 0x8003 {
+            //set value of Vx to value of Vx xor Vy (8XY3)
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //update register
+            self.registers[reg_num_x] ^= self.registers[reg_num_y];
+            if self.quirks.logic {
+                self.registers[0xF] = 0;
+            }
+        } else if (opcode & 0xF00F) == 0x8004 {
+            //set value of Vx to value of Vx + Vy (8XY4), set VF to whether or not there was an overflow
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //check for overflow
+            if self.registers[reg_num_x]
+                .checked_add(self.registers[reg_num_y])
+                .is_none()
+            {
+                self.registers[0xF] = 1;
+            } else {
+                self.registers[0xF] = 0;
+            }
+
+            //update register
+            self.registers[reg_num_x] =
+                self.registers[reg_num_x].wrapping_add(self.registers[reg_num_y]);
+        } else if (opcode & 0xF00F) == 0x8005 {
+            //set value of Vx to value of Vx - Vy (8XY5), set VF to whether or not there was an underflow
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //check for overflow
+            if self.registers[reg_num_x]
+                .checked_sub(self.registers[reg_num_y])
+                .is_none()
+            {
+                self.registers[0xF] = 0;
+            } else {
+                self.registers[0xF] = 1;
+            }
+
+            //update register
+            self.registers[reg_num_x] =
+                self.registers[reg_num_x].wrapping_sub(self.registers[reg_num_y]);
+        } else if (opcode & 0xF00F) == 0x8006 {
+            //set value of Vx to value of Vy shifted 1 bit to the right (8XY6), set Vf to the shifted bit
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //update register
+            if !self.quirks.shift {
+                self.registers[reg_num_x] = self.registers[reg_num_y];
+            }
+            self.registers[0xF] = self.registers[reg_num_x] % 2;
+            self.registers[reg_num_x] >>= 1;
+        } else if (opcode & 0xF00F) == 0x8007 {
+            //set value of Vx to value of Vy - Vx (8XY7), set VF to whether or not there was an underflow
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //check for overflow
+            if self.registers[reg_num_y]
+                .checked_sub(self.registers[reg_num_x])
+                .is_none()
+            {
+                self.registers[0xF] = 0;
+            } else {
+                self.registers[0xF] = 1;
+            }
+
+            //update register
+            self.registers[reg_num_x] =
+                self.registers[reg_num_y].wrapping_sub(self.registers[reg_num_x]);
+        } else if (opcode & 0xF00F) == 0x800E {
+            //set value of Vx to value of Vy shifted 7 bits to the right (8XYE), set Vf to the shifted bit
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //update register
+            if !self.quirks.shift {
+                self.registers[reg_num_x] = self.registers[reg_num_y];
+            }
+            self.registers[0xF] = self.registers[reg_num_x] >> 7;
+            self.registers[reg_num_x] <<= 1;
+        } else if (opcode & 0xF00F) == 0x9000 {
+            //skip next instruction if Vx != Vy (9XY0)
+            //get register numbers
+            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
+            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
+
+            //update register
+            if self.registers[reg_num_x] != self.registers[reg_num_y] {
+                self.program_counter = self.program_counter.wrapping_add(2);
+            }
+        } else if (opcode & 0xF000) == 0xA000 {
+            //set index register I (ANNN)
+            //get value to set register to and update register
+            self.index_register = opcode & 0x0FFF;
+        } else if (opcode & 0xF000) == 0xB000 {
+            //jump to NNN + V0 (BNNN), or to NNN + VX with the jump quirk
+            let reg_num = if self.quirks.jump {
+                ((opcode & 0x0F00) >> 0x8) as usize
+            } else {
+                0
+            };
+            //the address wraps around memory, and the program counter increment below is undone
+            //with a wrapping subtraction so jumping to 0 does not overflow
+            let address: u16 =
+                (opcode & 0x0FFF).wrapping_add(self.registers[reg_num] as u16) & 0x0FFF;
+            self.program_counter = (address as usize).wrapping_sub(2);
+        } else if (opcode & 0xF000) == 0xC000 {
+            //set value of Vx to random & NN (CXNN)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //get value to bitwise and random value with
+            let val = (opcode & 0x00FF) as u8;
+
+            //update register
+            self.registers[reg_num] = self.random_byte();
+            self.registers[reg_num] &= val;
+        } else if (opcode & 0xF000) == 0xD000 {
+            //display / draw (DXYN)
+            //get coordinates and height
+            let x = self.registers[((opcode & 0x0F00) >> 0x8) as usize] % 64;
+            let y = self.registers[((opcode & 0x00F0) >> 0x4) as usize] % 32;
+            let h = (opcode & 0x000F) as u8;
+
+            //set VF to 0
+            self.registers[0xF] = 0x0;
+
+            //draw to screen
+            //iterate through each row to draw
+            for n in 0..h {
+                //get row of sprite data from memory at I
+                let row = self.memory[(self.index_register as usize + n as usize) & 0xFFF];
+
+                //loop through each pixel in byte
+                for i in 0..8 {
+                    //check if pixel is to be toggled
+                    if row & (1 << (7 - i)) != 0 {
+                        //get screen memory address
+                        //wrapping around the edges only matters with the wrap quirk, as drawing stops at the edges without it
+                        let location = (((y as usize) + (n as usize)) % DISPLAY_HEIGHT) * 64
+                            + ((x as usize) + (i as usize)) % DISPLAY_WIDTH;
+                        //toggle pixel
+                        self.display[location] = self.display[location].wrapping_add(1);
+                        if self.display[location] == 2 {
+                            self.display[location] = 0;
+                            self.registers[0xF] = 1;
+                        }
+                    }
+
+                    //check if horizontal edge reached, if so go to next line
+                    if x + i == 63 && !self.quirks.wrap {
+                        break;
+                    }
+                }
+
+                //check if vertical edge reached, if so stop drawing
+                if y + n == 31 && !self.quirks.wrap {
+                    break;
+                }
+            }
+
+            //wait for the end of the frame with the vblank quirk
+            self.waiting_for_vblank = self.quirks.vblank;
+        } else if (opcode & 0xF0FF) == 0xE09E {
+            //skip next instruction if key in VX is pressed (EX9E)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //check register
+            if self.registers[reg_num] < 0x10 {
+                //check key
+                if self.input[self.registers[reg_num] as usize] == 1 {
+                    self.program_counter += 2;
+                }
+            }
+        } else if (opcode & 0xF0FF) == 0xE0A1 {
+            //skip next instruction if key in VX is not pressed (EXA1)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //check register
+            if self.registers[reg_num] < 0x10 {
+                //check key
+                if self.input[self.registers[reg_num] as usize] == 0 {
+                    self.program_counter += 2;
+                }
+            }
+        } else if (opcode & 0xF0FF) == 0xF007 {
+            //set vx to delay timer (FX07)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //update register
+            self.registers[reg_num] = self.delay_timer;
+        } else if (opcode & 0xF0FF) == 0xF00A {
+            //await key press and store code in VX (FX0A)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //loop through inputs
+            for (i, key) in self.input.iter().enumerate() {
+                //check key press
+                if *key == 1 {
+                    self.registers[reg_num] = i as u8;
+                    self.program_counter += 2;
+                    break;
+                }
+            }
+
+            //decrement program counter
+            self.program_counter = self.program_counter.wrapping_sub(2);
+        } else if (opcode & 0xF0FF) == 0xF015 {
+            //set delay timer to vx (FX15)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //update timer
+            self.delay_timer = self.registers[reg_num];
+        } else if (opcode & 0xF0FF) == 0xF018 {
+            //set sound timer to vx (FX18)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //update timer
+            self.sound_timer = self.registers[reg_num];
+        } else if (opcode & 0xF0FF) == 0xF01E {
+            //adds Vx to index register (FX1E)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //update index register
+            self.index_register = self
+                .index_register
+                .wrapping_add(self.registers[reg_num] as u16);
+        } else if (opcode & 0xF0FF) == 0xF029 {
+            //sets index register to sprite address of char in Vx (FX29)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //update index register
+            self.index_register = ((self.registers[reg_num] & 0x0F) as u16) * 5;
+        } else if (opcode & 0xF0FF) == 0xF033 {
+            //store bcd representation of Vx in I, I+1, and I+2 (FX33)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //update index register
+            let address: usize = self.index_register as usize & 0xFFF;
+            self.last_write = Some((address, 3));
+            self.memory[address] = self.registers[reg_num] / 100;
+            self.memory[(address + 1) & 0xFFF] =
+                (self.registers[reg_num] - 100 * (self.registers[reg_num] / 100)) / 10;
+            self.memory[(address + 2) & 0xFFF] =
+                self.registers[reg_num] - 10 * (self.registers[reg_num] / 10);
+        } else if (opcode & 0xF0FF) == 0xF055 {
+            //store V0 to Vx in index register to index register + X (FX55)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //update index register
+            let address: usize = self.index_register as usize & 0xFFF;
+            self.last_write = Some((address, reg_num + 1));
+            for i in 0..(reg_num + 1) {
+                self.memory[(address + i) & 0xFFF] = self.registers[i];
+            }
+            self.increment_index_after_memory(reg_num);
+        } else if (opcode & 0xF0FF) == 0xF065 {
+            //fill V0 to Vx from index register to index register + X (FX65)
+            //get register number
+            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+
+            //update index register
+            for i in 0..(reg_num + 1) {
+                self.registers[i] = self.memory[(self.index_register as usize + i) & 0xFFF];
+            }
+            self.increment_index_after_memory(reg_num);
+        } else {
+            //opcode does not exist, left for the frontend to report
+            self.bad_opcode = Some((self.program_counter, opcode));
+        }
+
+        //increment program counter, wrapping around memory
+        self.program_counter = self.program_counter.wrapping_add(2) & 0xFFF;
+
+        //timer management, the timers ticking marks the end of a frame
+        let mut frame_ended: bool = false;
+        if self.timer_counter + 1 >= self.instructions_per_frame {
+            if self.delay_timer > 0 {
+                self.delay_timer = self.delay_timer.wrapping_sub(1);
+            }
+            if self.sound_timer > 0 {
+                self.sound_timer = self.sound_timer.wrapping_sub(1);
+            }
+            self.timer_counter = 0;
+            self.frame += 1;
+            self.waiting_for_vblank = false;
+            frame_ended = true;
+        } else {
+            self.timer_counter += 1;
+        }
+
+        frame_ended
+    }
+
+    //move the index register past the registers stored or loaded by FX55 and FX65, depending on the quirks
+    fn increment_index_after_memory(&mut self, reg_num: usize) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        let amount: u16 = if self.quirks.memory_increment_by_x {
+            reg_num as u16
+        } else {
+            reg_num as u16 + 1
+        };
+        self.index_register = self.index_register.wrapping_add(amount);
+    }
+}
+
+//get a seed from the clock
+#[cfg(not(target_arch = "wasm32"))]
+fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+//there is no clock in webassembly without javascript, so every machine gets the same seed
+//the web frontend seeds the machine from Math.random, other hosts have to call seed themselves
+#[cfg(target_arch = "wasm32")]
+fn clock_seed() -> u64 {
+    0
+}
+
+//take bytes from the front of a save state
+fn take<'a>(rest: &mut &'a [u8], length: usize) -> &'a [u8] {
+    let (taken, remaining): (&[u8], &[u8]) = rest.split_at(length);
+    *rest = remaining;
+    taken
+}
+
+//take a fixed number of bytes from the front of a save state
+fn take_array<const N: usize>(rest: &mut &[u8]) -> [u8; N] {
+    take(rest, N).try_into().expect("the length was checked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //make a machine running a rom from the program start
+    fn machine(rom: &[u8]) -> Chip8 {
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.load_rom(rom).unwrap();
+        chip8
+    }
+
+    //make a machine with quirks running a rom from the program start
+    fn machine_with(rom: &[u8], quirks: Quirks) -> Chip8 {
+        let mut chip8: Chip8 = machine(rom);
+        chip8.quirks = quirks;
+        chip8
+    }
+
+    //run a number of instructions
+    fn run(chip8: &mut Chip8, instructions: usize) {
+        for _ in 0..instructions {
+            chip8.step();
+        }
+    }
+
+    #[test]
+    fn adds_and_subtracts_with_flags() {
+        //V0 = 200, V1 = 100, V0 += V1
+        let mut chip8: Chip8 = machine(&[0x60, 200, 0x61, 100, 0x80, 0x14]);
+        run(&mut chip8, 3);
+        assert_eq!((chip8.registers[0], chip8.registers[0xF]), (44, 1));
+
+        //V0 = 5, V1 = 3, V0 -= V1
+        let mut chip8: Chip8 = machine(&[0x60, 5, 0x61, 3, 0x80, 0x15]);
+        run(&mut chip8, 3);
+        assert_eq!((chip8.registers[0], chip8.registers[0xF]), (2, 1));
+    }
+
+    #[test]
+    fn shift_quirk_shifts_vx_in_place() {
+        //V0 = 0x10, V1 = 3, V0 = V1 >> 1
+        let rom: [u8; 6] = [0x60, 0x10, 0x61, 0x03, 0x80, 0x16];
+        let mut chip8: Chip8 = machine(&rom);
+        run(&mut chip8, 3);
+        assert_eq!((chip8.registers[0], chip8.registers[0xF]), (1, 1));
+
+        let shift: Quirks = Quirks {
+            shift: true,
+            ..Quirks::default()
+        };
+        let mut chip8: Chip8 = machine_with(&rom, shift);
+        run(&mut chip8, 3);
+        assert_eq!((chip8.registers[0], chip8.registers[0xF]), (0x08, 0));
+    }
+
+    #[test]
+    fn logic_quirk_resets_vf() {
+        //VF = 5, V0 |= V1
+        let rom: [u8; 4] = [0x6F, 0x05, 0x80, 0x11];
+        let mut chip8: Chip8 = machine(&rom);
+        run(&mut chip8, 2);
+        assert_eq!(chip8.registers[0xF], 5);
+
+        let logic: Quirks = Quirks {
+            logic: true,
+            ..Quirks::default()
+        };
+        let mut chip8: Chip8 = machine_with(&rom, logic);
+        run(&mut chip8, 2);
+        assert_eq!(chip8.registers[0xF], 0);
+    }
+
+    #[test]
+    fn jump_quirk_adds_vx() {
+        //V0 = 4, V2 = 6, jump to 0x210 + V0 or V2
+        let rom: [u8; 6] = [0x60, 0x04, 0x62, 0x06, 0xB2, 0x10];
+        let mut chip8: Chip8 = machine(&rom);
+        run(&mut chip8, 3);
+        assert_eq!(chip8.program_counter, 0x214);
+
+        let jump: Quirks = Quirks {
+            jump: true,
+            ..Quirks::default()
+        };
+        let mut chip8: Chip8 = machine_with(&rom, jump);
+        run(&mut chip8, 3);
+        assert_eq!(chip8.program_counter, 0x216);
+    }
+
+    #[test]
+    fn memory_quirks_move_the_index_register() {
+        //I = 0x300, store V0 to V2
+        let rom: [u8; 4] = [0xA3, 0x00, 0xF2, 0x55];
+        for (by_x, unchanged, index) in [
+            (false, true, 0x300),
+            (false, false, 0x303),
+            (true, false, 0x302),
+        ] {
+            let mut chip8: Chip8 = machine_with(
+                &rom,
+                Quirks {
+                    memory_increment_by_x: by_x,
+                    memory_leave_i_unchanged: unchanged,
+                    ..Quirks::default()
+                },
+            );
+            run(&mut chip8, 2);
+            assert_eq!(chip8.index_register, index);
+        }
+    }
+
+    #[test]
+    fn vblank_quirk_waits_for_the_end_of_the_frame() {
+        //draw, then add 1 to V0 for the rest of the frame
+        let rom: [u8; 16] = [
+            0xA0, 0x00, 0xD0, 0x05, 0x70, 0x01, 0x70, 0x01, 0x70, 0x01, 0x70, 0x01, 0x70, 0x01,
+            0x70, 0x01,
+        ];
+        let mut chip8: Chip8 = machine(&rom);
+        while !chip8.step() {}
+        assert_eq!(chip8.registers[0], 6);
+
+        let vblank: Quirks = Quirks {
+            vblank: true,
+            ..Quirks::default()
+        };
+        let mut chip8: Chip8 = machine_with(&rom, vblank);
+        while !chip8.step() {}
+        assert_eq!(chip8.registers[0], 0);
+        assert!(!chip8.waiting_for_vblank);
+        chip8.step();
+        assert_eq!(chip8.registers[0], 1);
+    }
+
+    #[test]
+    fn wrap_quirk_draws_across_the_edge() {
+        //V0 = 62, draw the top row of the 0 glyph at x = V0, y = V1
+        let rom: [u8; 6] = [0x60, 62, 0xA0, 0x00, 0xD0, 0x11];
+        let mut chip8: Chip8 = machine(&rom);
+        run(&mut chip8, 3);
+        assert_eq!(chip8.display[..2], [0, 0]);
+        assert_eq!(chip8.display[62..64], [1, 1]);
+
+        let wrap: Quirks = Quirks {
+            wrap: true,
+            ..Quirks::default()
+        };
+        let mut chip8: Chip8 = machine_with(&rom, wrap);
+        run(&mut chip8, 3);
+        assert_eq!(chip8.display[..2], [1, 1]);
+    }
+
+    #[test]
+    fn drawing_twice_erases_and_sets_vf() {
+        let mut chip8: Chip8 = machine(&[0xA0, 0x00, 0xD0, 0x05, 0xD0, 0x05]);
+        run(&mut chip8, 2);
+        assert_eq!(chip8.display[..4], [1, 1, 1, 1]);
+        assert_eq!(chip8.registers[0xF], 0);
+        chip8.step();
+        assert!(chip8.display.iter().all(|pixel| *pixel == 0));
+        assert_eq!(chip8.registers[0xF], 1);
+    }
+
+    #[test]
+    fn timers_tick_once_a_frame() {
+        //V0 = 3, set the delay and sound timers, then loop
+        let mut chip8: Chip8 = machine(&[0x60, 0x03, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06]);
+        for frame in 1..=4 {
+            while !chip8.step() {}
+            assert_eq!(chip8.frame, frame);
+        }
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (0, 0));
+        chip8.delay_timer = 2;
+        while !chip8.step() {}
+        assert_eq!(chip8.delay_timer, 1);
+    }
+
+    #[test]
+    fn reports_opcodes_that_do_not_exist() {
+        let mut chip8: Chip8 = machine(&[0x00, 0xE0, 0x50, 0x01]);
+        chip8.step();
+        assert_eq!(chip8.bad_opcode, None);
+        chip8.step();
+        assert_eq!(chip8.bad_opcode, Some((0x202, 0x5001)));
+        assert_eq!(chip8.program_counter, 0x204);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_random_numbers() {
+        let rom: [u8; 6] = [0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF];
+        let mut first: Chip8 = machine(&rom);
+        let mut second: Chip8 = machine(&rom);
+        first.seed(7);
+        second.seed(7);
+        run(&mut first, 3);
+        run(&mut second, 3);
+        assert_eq!(first.registers, second.registers);
+
+        //seeding again after a reset repeats the same numbers
+        first.reset();
+        first.load_rom(&rom).unwrap();
+        first.seed(7);
+        run(&mut first, 3);
+        assert_eq!(first.registers, second.registers);
+    }
+
+    #[test]
+    fn save_states_round_trip() {
+        let rom: [u8; 10] = [0x22, 0x04, 0xC0, 0xFF, 0xA3, 0x00, 0xF0, 0x33, 0x12, 0x04];
+        let mut chip8: Chip8 = machine(&rom);
+        chip8.seed(3);
+        run(&mut chip8, 5);
+        let state: Vec<u8> = chip8.save_state();
+        assert_eq!(state.len(), STATE_SIZE);
+
+        run(&mut chip8, 20);
+        let memory: [u8; 0x1000] = chip8.memory;
+        let registers: [u8; 0x10] = chip8.registers;
+
+        let mut restored: Chip8 = Chip8::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.save_state(), state);
+        run(&mut restored, 20);
+        assert_eq!(restored.memory, memory);
+        assert_eq!(restored.registers, registers);
+        assert_eq!(restored.stack_index, 1);
+    }
+
+    #[test]
+    fn rejects_bad_save_states() {
+        let state: Vec<u8> = Chip8::new().save_state();
+        let mut chip8: Chip8 = machine(&[0x12, 0x00]);
+        let before: Vec<u8> = chip8.save_state();
+        assert!(chip8.load_state(&state[1..]).is_err());
+
+        let mut bad_magic: Vec<u8> = state.clone();
+        bad_magic[0] = b'X';
+        assert!(chip8.load_state(&bad_magic).is_err());
+
+        //the program counter comes after the magic, memory, display and registers
+        let mut bad_program_counter: Vec<u8> = state.clone();
+        let offset: usize = STATE_MAGIC.len() + 0x1000 + 0x800 + 0x10;
+        bad_program_counter[offset..offset + 2].copy_from_slice(&0xFFFFu16.to_le_bytes());
+        assert!(chip8.load_state(&bad_program_counter).is_err());
+
+        //nothing is changed by a state that is rejected
+        assert_eq!(chip8.save_state(), before);
+    }
+
+    #[test]
+    fn calls_and_returns_nest() {
+        //call 0x206, which calls 0x20A, which returns to 0x208, which returns to 0x202
+        let mut chip8: Chip8 = machine(&[
+            0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x22, 0x0A, 0x00, 0xEE, 0x00, 0xEE,
+        ]);
+        for _ in 0..4 {
+            chip8.step();
+        }
+        assert_eq!(chip8.program_counter, 0x202);
+        assert_eq!(chip8.stack_index, 0);
+    }
+
+    #[test]
+    fn returning_with_an_empty_stack_wraps_around() {
+        let mut chip8: Chip8 = machine(&[0x00, 0xEE]);
+        chip8.step();
+        assert_eq!(chip8.stack_index, chip8.stack.len() - 1);
+        assert!(chip8.program_counter < chip8.memory.len());
+    }
+
+    #[test]
+    fn calling_past_the_top_of_the_stack_wraps_around() {
+        let mut chip8: Chip8 = machine(&[0x22, 0x00]);
+        for _ in 0..chip8.stack.len() + 10 {
+            chip8.step();
+        }
+        assert_eq!(chip8.stack_index, 10);
+        assert_eq!(chip8.program_counter, 0x200);
+    }
+
+    #[test]
+    fn jumps_to_the_start_of_memory() {
+        for opcode in [[0x10, 0x00], [0x10, 0x01], [0x20, 0x00], [0xB0, 0x00]] {
+            let mut chip8: Chip8 = machine(&opcode);
+            chip8.step();
+            assert_eq!(chip8.program_counter, opcode[1] as usize);
+        }
+    }
+
+    #[test]
+    fn jump_with_offset_wraps_around_memory() {
+        //V0 = 0xFF, jump to 0xFFF + V0
+        let mut chip8: Chip8 = machine(&[0x60, 0xFF, 0xBF, 0xFF]);
+        chip8.step();
+        chip8.step();
+        assert_eq!(chip8.program_counter, 0x0FE);
+    }
+
+    #[test]
+    fn subtracting_a_larger_register_wraps() {
+        //V0 = 5, V1 = 3, V0 = V1 - V0
+        let mut chip8: Chip8 = machine(&[0x60, 0x05, 0x61, 0x03, 0x80, 0x17]);
+        for _ in 0..3 {
+            chip8.step();
+        }
+        assert_eq!(chip8.registers[0], 254);
+        assert_eq!(chip8.registers[0xF], 0);
+    }
+
+    #[test]
+    fn memory_past_the_end_wraps_around() {
+        //V0 = 123, I = 0xFFF, store its digits
+        let mut chip8: Chip8 = machine(&[0x60, 123, 0xAF, 0xFF, 0xF0, 0x33]);
+        for _ in 0..3 {
+            chip8.step();
+        }
+        assert_eq!(
+            [chip8.memory[0xFFF], chip8.memory[0], chip8.memory[1]],
+            [1, 2, 3]
+        );
+
+        //the index register can be set past memory by frontends, and wraps too
+        for opcode in [0xF01E, 0xF033, 0xFF55, 0xFF65, 0xD00F] {
+            let mut chip8: Chip8 = machine(&[(opcode >> 8) as u8, opcode as u8]);
+            chip8.index_register = 0xFFFF;
+            chip8.step();
+        }
+    }
+
+    #[test]
+    fn program_counter_wraps_at_the_end_of_memory() {
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.load_rom_at(&[0x00, 0xE0], 0xFFE).unwrap();
+        chip8.step();
+        assert_eq!(chip8.program_counter, 0);
+
+        chip8.program_counter = 0xFFF;
+        chip8.step();
+        assert_eq!(chip8.program_counter, 1);
+    }
+
+    #[test]
+    fn loads_a_state_at_the_end_of_memory() {
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.program_counter = 0xFFF;
+        let state: Vec<u8> = chip8.save_state();
+
+        let mut restored: Chip8 = Chip8::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.program_counter, 0xFFF);
+        assert_eq!(restored.save_state(), state);
+        restored.step();
+        assert_eq!(restored.program_counter, 1);
+    }
+
+    #[test]
+    fn random_roms_do_not_panic() {
+        let mut random: Chip8 = Chip8::new();
+        for seed in 0..64 {
+            random.seed(seed);
+            let mut chip8: Chip8 = Chip8::new();
+            chip8
+                .memory
+                .iter_mut()
+                .for_each(|byte| *byte = random.random_byte());
+            chip8
+                .registers
+                .iter_mut()
+                .for_each(|byte| *byte = random.random_byte());
+            chip8.index_register = u16::from_be_bytes([random.random_byte(), random.random_byte()]);
+            for (index, _) in QUIRK_NAMES.iter().enumerate() {
+                *quirk_mut(&mut chip8.quirks, index) = random.random_byte() & 1 == 1;
+            }
+            for _ in 0..10_000 {
+                chip8.step();
+            }
+        }
+    }
+}
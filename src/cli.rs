@@ -0,0 +1,544 @@
+//use declarations
+use crate::audio::{AudioConfig, Waveform};
+use crate::config;
+use crate::database::{self, QuirkOverrides};
+use crate::filter::FilterMode;
+use crate::palette;
+use crate::upscale::Upscaler;
+
+//exit codes, for a failure while running, a bad command line, and a rom that can not be read
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NO_INPUT: i32 = 3;
+
+//short usage shown with command line errors
+pub const USAGE: &str = "usage: chip8_emulator [run|disasm|info|test|serve] [options] [rom_name.ch8]\ntry chip8_emulator --help for more";
+
+//full help shown by --help
+pub const HELP: &str = "\
+usage: chip8_emulator [run|disasm|info|test|serve] [options] [rom_name.ch8]
+
+commands:
+  run       run a rom in a window, or open the rom browser without one (default)
+  disasm    print the instructions in a rom
+  info      print the size, sha1 and rom database entry of a rom
+  test      run a rom without a window and check the display, see --frames and --expect
+  serve     run a rom without a window, controlled by json requests over a local socket
+
+options:
+  --scale N                 size of an emulated pixel in window pixels, 1 to 40
+  --integer-scale           only scale the display by whole numbers
+  --palette NAME            classic, green, amber, lcd or custom
+  --filter MODE             off, frame-end, blend:N or decay:F
+  --upscaler NAME           nearest, scale2x, scale3x, eagle or xbr
+  --speed X                 run X times as fast as normal
+  --tickrate N              instructions per frame, 8 by default
+  --quirk-preset PLATFORM   start from a platform's quirks, such as originalChip8 or superchip
+  --quirk NAME=on|off       turn one quirk on or off
+  --no-database             ignore the rom database
+  --seed N                  seed the random number generator so runs repeat exactly
+  --paused                  start paused
+  --watch                   reload the rom when the file changes
+  --load-address ADDR       load the rom at a hex address instead of 0x200
+  --screenshot FRAMES       run FRAMES frames without a window and save a screenshot
+  --raw                     save the raw display with --screenshot
+  --headless FRAMES         run FRAMES frames without a window
+  --record PATH             record a gif, or raw video and audio
+  --record-audio PATH       record the audio to a wav file
+  --tone HZ                 beeper frequency
+  --waveform NAME           square, pulse, triangle, sine or noise
+  --volume V                beeper volume from 0 to 1
+  --mute                    start muted
+  --frames N                frames to run for test, 600 by default
+  --expect SHA1             display hash test should finish with
+  --port N                  localhost port for serve to listen on, 8064 by default
+  --socket PATH             unix socket for serve to listen on instead of a port
+  -h, --help                show this help
+  -V, --version             show the version
+
+exit codes: 0 success, 1 failure or test mismatch, 2 bad command line, 3 rom could not be read";
+
+//an error to report before exiting, with the exit code to use
+pub struct Failure {
+    pub message: String,
+    pub code: i32,
+}
+
+impl Failure {
+    //a bad command line, reported with the short usage
+    pub fn usage(message: String) -> Failure {
+        Failure {
+            message: format!("{}\n{}", message, USAGE),
+            code: EXIT_USAGE,
+        }
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Failure {
+        Failure {
+            message,
+            code: EXIT_FAILURE,
+        }
+    }
+}
+
+//subcommands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Run,
+    Disasm,
+    Info,
+    Test,
+    Serve,
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Run => "run",
+            Command::Disasm => "disasm",
+            Command::Info => "info",
+            Command::Test => "test",
+            Command::Serve => "serve",
+        }
+    }
+}
+
+//what the command line asked for
+pub enum Parsed {
+    Options(Box<Options>),
+    Help,
+    Version,
+}
+
+//command line options
+pub struct Options {
+    pub command: Command,
+    pub file_name: Option<String>,
+    pub scale: Option<u32>,
+    pub integer_scale: bool,
+    pub filter: Option<FilterMode>,
+    pub upscaler: Option<Upscaler>,
+    pub screenshot: Option<u64>,
+    pub raw_screenshot: bool,
+    pub record: Option<String>,
+    pub record_audio: Option<String>,
+    pub headless: Option<u64>,
+    pub audio: AudioConfig,
+    pub quirks: QuirkOverrides,
+    pub tickrate: Option<u32>,
+    pub no_database: bool,
+    pub palette: Option<String>,
+    pub quirk_preset: Option<String>,
+    pub seed: Option<u64>,
+    pub paused: bool,
+    pub watch: bool,
+    pub load_address: Option<usize>,
+    pub speed: Option<f64>,
+    pub frames: Option<u64>,
+    pub expect: Option<String>,
+    pub port: Option<u16>,
+    pub socket: Option<String>,
+}
+
+//read the command line: an optional subcommand, a rom file name and the options listed in the help
+pub fn parse_args(args: &[String]) -> Result<Parsed, String> {
+    //the subcommand can be left out to run a rom
+    let (command, first): (Command, usize) = match args.get(1).map(String::as_str) {
+        Some("run") => (Command::Run, 2),
+        Some("disasm") => (Command::Disasm, 2),
+        Some("info") => (Command::Info, 2),
+        Some("test") => (Command::Test, 2),
+        Some("serve") => (Command::Serve, 2),
+        _ => (Command::Run, 1),
+    };
+
+    let mut file_name: Option<String> = None;
+    let mut scale: Option<u32> = None;
+    let mut integer_scale: bool = false;
+    let mut filter: Option<FilterMode> = None;
+    let mut upscaler: Option<Upscaler> = None;
+    let mut screenshot: Option<u64> = None;
+    let mut raw_screenshot: bool = false;
+    let mut record: Option<String> = None;
+    let mut record_audio: Option<String> = None;
+    let mut headless: Option<u64> = None;
+    let mut quirks: QuirkOverrides = QuirkOverrides::default();
+    let mut tickrate: Option<u32> = None;
+    let mut no_database: bool = false;
+    let mut audio: AudioConfig = AudioConfig::default();
+    let mut palette: Option<String> = None;
+    let mut quirk_preset: Option<String> = None;
+    let mut seed: Option<u64> = None;
+    let mut paused: bool = false;
+    let mut watch: bool = false;
+    let mut load_address: Option<usize> = None;
+    let mut speed: Option<f64> = None;
+    let mut frames: Option<u64> = None;
+    let mut expect: Option<String> = None;
+    let mut port: Option<u16> = None;
+    let mut socket: Option<String> = None;
+
+    //loop through each argument after the program name and subcommand
+    let mut i: usize = first;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--scale" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--scale needs a value")?;
+                match value.parse::<u32>() {
+                    Ok(value) if config::valid_scale(value) => scale = Some(value),
+                    _ => {
+                        return Err(format!(
+                            "invalid scale \"{}\", expected 1 to {}",
+                            value,
+                            config::MAX_SCALE
+                        ))
+                    }
+                }
+            }
+            "--integer-scale" => integer_scale = true,
+            "--filter" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--filter needs a value")?;
+                filter = Some(FilterMode::parse(value)?);
+            }
+            "--upscaler" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--upscaler needs a value")?;
+                upscaler = Some(Upscaler::parse(value)?);
+            }
+            "--screenshot" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--screenshot needs a frame count")?;
+                screenshot = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid frame count \"{}\"", value))?,
+                );
+            }
+            "--headless" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--headless needs a frame count")?;
+                headless = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid frame count \"{}\"", value))?,
+                );
+            }
+            "--raw" => raw_screenshot = true,
+            "--tone" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--tone needs a frequency")?;
+                match value.parse::<f32>() {
+                    Ok(value) if config::valid_frequency(value) => audio.frequency = Some(value),
+                    _ => return Err(format!("invalid frequency \"{}\"", value)),
+                }
+            }
+            "--waveform" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--waveform needs a value")?;
+                audio.waveform = Some(Waveform::parse(value)?);
+            }
+            "--volume" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--volume needs a value")?;
+                match value.parse::<f32>() {
+                    Ok(value) if config::valid_volume(value) => audio.volume = Some(value),
+                    _ => return Err(format!("invalid volume \"{}\", expected 0 to 1", value)),
+                }
+            }
+            "--mute" => audio.muted = Some(true),
+            "--record" => {
+                i += 1;
+                record = Some(args.get(i).ok_or("--record needs a path")?.clone());
+            }
+            "--record-audio" => {
+                i += 1;
+                record_audio = Some(args.get(i).ok_or("--record-audio needs a path")?.clone());
+            }
+            "--quirk" => {
+                i += 1;
+                quirks.set(
+                    args.get(i)
+                        .ok_or("--quirk needs a NAME=on or NAME=off value")?,
+                )?;
+            }
+            "--tickrate" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--tickrate needs a value")?;
+                match value.parse::<u32>() {
+                    Ok(value) if config::valid_tickrate(value) => tickrate = Some(value),
+                    _ => return Err(format!("invalid tickrate \"{}\"", value)),
+                }
+            }
+            "--no-database" => no_database = true,
+            "--palette" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--palette needs a name")?;
+                if value != "custom" && !palette::PRESET_NAMES.contains(&value.as_str()) {
+                    return Err(format!(
+                        "unknown palette \"{}\", expected {} or custom",
+                        value,
+                        palette::PRESET_NAMES.join(", ")
+                    ));
+                }
+                palette = Some(value.clone());
+            }
+            "--quirk-preset" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--quirk-preset needs a platform")?;
+                if database::platform_defaults(value).is_none() {
+                    return Err(format!(
+                        "unknown platform \"{}\", expected {}",
+                        value,
+                        database::PLATFORMS.join(", ")
+                    ));
+                }
+                quirk_preset = Some(value.clone());
+            }
+            "--seed" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--seed needs a number")?;
+                seed = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid seed \"{}\"", value))?,
+                );
+            }
+            "--paused" => paused = true,
+            "--watch" => watch = true,
+            "--load-address" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--load-address needs an address")?;
+                let digits: &str = value.trim_start_matches("0x").trim_start_matches("0X");
+                //instructions are 2 bytes, so the rom has to start on an even address with room
+                //for at least one instruction before the end of memory
+                match usize::from_str_radix(digits, 16) {
+                    Ok(address) if address < 0xFFF && address % 2 == 0 => {
+                        load_address = Some(address)
+                    }
+                    _ => {
+                        return Err(format!(
+                            "invalid load address \"{}\", expected an even hex address below 0xFFF",
+                            value
+                        ))
+                    }
+                }
+            }
+            "--speed" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--speed needs a multiplier")?;
+                match value.parse::<f64>() {
+                    Ok(value) if config::valid_speed(value) => speed = Some(value),
+                    _ => return Err(format!("invalid speed \"{}\"", value)),
+                }
+            }
+            "--frames" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--frames needs a frame count")?;
+                frames = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid frame count \"{}\"", value))?,
+                );
+            }
+            "--expect" => {
+                i += 1;
+                expect = Some(args.get(i).ok_or("--expect needs a hash")?.to_lowercase());
+            }
+            "--port" => {
+                i += 1;
+                let value: &String = args.get(i).ok_or("--port needs a number")?;
+                match value.parse::<u16>() {
+                    Ok(value) if value > 0 => port = Some(value),
+                    _ => return Err(format!("invalid port \"{}\"", value)),
+                }
+            }
+            "--socket" => {
+                i += 1;
+                socket = Some(args.get(i).ok_or("--socket needs a path")?.clone());
+            }
+            "-h" | "--help" => return Ok(Parsed::Help),
+            "-V" | "--version" => return Ok(Parsed::Version),
+            arg if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option \"{}\"", arg))
+            }
+            arg => {
+                if let Some(other) = &file_name {
+                    return Err(format!(
+                        "only one rom can be given, got \"{}\" and \"{}\"",
+                        other, arg
+                    ));
+                }
+                file_name = Some(arg.to_string())
+            }
+        }
+        i += 1;
+    }
+    if headless.is_some() && screenshot.is_some() {
+        return Err(
+            "--headless and --screenshot both set a frame count, use one of them".to_string(),
+        );
+    }
+    if port.is_some() && socket.is_some() {
+        return Err("--port and --socket both set where to listen, use one of them".to_string());
+    }
+
+    Ok(Parsed::Options(Box::new(Options {
+        command,
+        file_name,
+        scale,
+        integer_scale,
+        filter,
+        upscaler,
+        screenshot,
+        raw_screenshot,
+        record,
+        record_audio,
+        headless,
+        audio,
+        quirks,
+        tickrate,
+        no_database,
+        palette,
+        quirk_preset,
+        seed,
+        paused,
+        watch,
+        load_address,
+        speed,
+        frames,
+        expect,
+        port,
+        socket,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //parse a command line given as one string, after the program name
+    fn parse(line: &str) -> Result<Parsed, String> {
+        let args: Vec<String> = std::iter::once("chip8_emulator")
+            .chain(line.split_whitespace())
+            .map(str::to_string)
+            .collect();
+        parse_args(&args)
+    }
+
+    //parse a command line that has to give options
+    fn options(line: &str) -> Options {
+        match parse(line) {
+            Ok(Parsed::Options(options)) => *options,
+            Ok(_) => panic!("\"{}\" did not give options", line),
+            Err(e) => panic!("\"{}\" failed: {}", line, e),
+        }
+    }
+
+    //parse a command line that has to fail, returning the message
+    fn error(line: &str) -> String {
+        match parse(line) {
+            Err(e) => e,
+            Ok(_) => panic!("\"{}\" was accepted", line),
+        }
+    }
+
+    #[test]
+    fn runs_a_rom_without_a_subcommand() {
+        let parsed: Options = options("game.ch8 --scale 10 --seed 4 --paused");
+        assert_eq!(parsed.command, Command::Run);
+        assert_eq!(parsed.file_name.as_deref(), Some("game.ch8"));
+        assert_eq!(parsed.scale, Some(10));
+        assert_eq!(parsed.seed, Some(4));
+        assert!(parsed.paused);
+
+        assert_eq!(options("").file_name, None);
+        assert_eq!(options("-").file_name.as_deref(), Some("-"));
+    }
+
+    #[test]
+    fn reads_subcommands() {
+        for command in [
+            Command::Run,
+            Command::Disasm,
+            Command::Info,
+            Command::Test,
+            Command::Serve,
+        ] {
+            assert_eq!(options(command.name()).command, command);
+        }
+        let parsed: Options = options("test --frames 300 --expect ABCDEF game.ch8");
+        assert_eq!(parsed.frames, Some(300));
+        assert_eq!(parsed.expect.as_deref(), Some("abcdef"));
+    }
+
+    #[test]
+    fn reads_help_and_version() {
+        assert!(matches!(parse("--help"), Ok(Parsed::Help)));
+        assert!(matches!(parse("game.ch8 -V"), Ok(Parsed::Version)));
+    }
+
+    #[test]
+    fn checks_the_load_address() {
+        assert_eq!(options("--load-address 0x600").load_address, Some(0x600));
+        assert_eq!(options("--load-address FFE").load_address, Some(0xFFE));
+        for address in ["FFF", "201", "1000", "0xZZ"] {
+            assert!(error(&format!("--load-address {}", address)).contains("even hex address"));
+        }
+    }
+
+    #[test]
+    fn checks_numbers_against_the_config_rules() {
+        assert_eq!(options("--scale 40").scale, Some(40));
+        assert!(error("--scale 0").contains("expected 1 to 40"));
+        assert!(error("--scale 41").contains("expected 1 to 40"));
+        assert_eq!(options("--speed 0.5").speed, Some(0.5));
+        assert!(error("--speed 0").contains("invalid speed"));
+        assert!(error("--tickrate 0").contains("invalid tickrate"));
+        assert!(error("--volume 2").contains("expected 0 to 1"));
+        assert!(error("--tone inf").contains("invalid frequency"));
+        assert!(error("--port 0").contains("invalid port"));
+    }
+
+    #[test]
+    fn reads_audio_and_quirk_options() {
+        let parsed: Options = options("--tone 220 --waveform sine --mute --quirk-preset superchip");
+        assert_eq!(parsed.audio.frequency, Some(220.0));
+        assert_eq!(parsed.audio.waveform, Some(Waveform::Sine));
+        assert_eq!(parsed.audio.muted, Some(true));
+        assert_eq!(parsed.quirk_preset.as_deref(), Some("superchip"));
+        assert!(error("--quirk-preset nothing").contains("unknown platform"));
+        assert!(error("--waveform saw").contains("unknown waveform"));
+    }
+
+    #[test]
+    fn rejects_conflicting_options() {
+        assert!(error("--headless 10 --screenshot 10").contains("--headless and --screenshot"));
+        assert!(error("serve --port 1234 --socket /tmp/chip8").contains("--port and --socket"));
+        assert!(error("one.ch8 two.ch8").contains("only one rom"));
+    }
+
+    #[test]
+    fn rejects_unknown_and_missing_values() {
+        assert!(error("--fast").contains("unknown option"));
+        assert!(error("--scale").contains("needs a value"));
+        assert!(error("--seed x").contains("invalid seed"));
+    }
+
+    #[test]
+    fn checks_the_palette_name() {
+        assert_eq!(options("--palette amber").palette.as_deref(), Some("amber"));
+        assert_eq!(
+            options("--palette custom").palette.as_deref(),
+            Some("custom")
+        );
+        assert_eq!(
+            error("--palette purple"),
+            "unknown palette \"purple\", expected classic, green, amber, lcd or custom"
+        );
+        assert!(error("--palette").contains("needs a name"));
+    }
+}
@@ -0,0 +1,589 @@
+//use declarations
+use crate::audio::{AudioConfig, BeeperSettings};
+use crate::crt::CrtSettings;
+use crate::database::QuirkOverrides;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//how many recent roms are remembered
+const MAX_RECENT: usize = 10;
+
+//largest window scale
+pub const MAX_SCALE: u32 = 40;
+
+//config structs and methods
+//the config file is stored at ~/.config/chip8_emulator/config.toml and holds global settings,
+//with per rom overrides stored in [rom."<sha1 of rom>"] sections
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    //default palette name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<String>,
+
+    //default custom palette as a list of 2 to 4 hex colors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colors: Option<Vec<String>>,
+
+    //size of an emulated pixel in window pixels when the window opens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<u32>,
+
+    //only scale the display by whole numbers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integer_scale: Option<bool>,
+
+    //show the fps and speed over the game
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub show_stats: Option<bool>,
+
+    //speed multiplier
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+
+    //speed multiplier while fast forward is held, 0 runs as fast as possible
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fast_forward_speed: Option<f64>,
+
+    //pause when the window loses focus
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pause_on_focus_loss: Option<bool>,
+
+    //instructions per frame for roms that are not in the rom database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tickrate: Option<u32>,
+
+    //default anti flicker filter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+
+    //default upscaler
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upscaler: Option<String>,
+
+    //full paths of the most recently run roms, newest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recent: Vec<String>,
+
+    //keyboard keys for chip 8 keys 0 to F
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keypad: Option<Vec<String>>,
+
+    //crt effect settings, stored in a [crt] section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crt: Option<CrtSettings>,
+
+    //beeper settings, stored in an [audio] section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioConfig>,
+
+    //quirks for roms that are not in the rom database, stored in a [quirks] section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quirks: Option<QuirkOverrides>,
+
+    //hotkeys keyed by action name, stored in a [hotkeys] section
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hotkeys: BTreeMap<String, String>,
+
+    //per rom overrides keyed by sha1
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rom: BTreeMap<String, RomConfig>,
+
+    //set when the config file could not be read, so it is never written over with the defaults
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RomConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colors: Option<Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tickrate: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upscaler: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keypad: Option<Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quirks: Option<QuirkOverrides>,
+}
+
+impl RomConfig {
+    //overwrite the settings that are set in other
+    pub fn merge(&mut self, other: &RomConfig) {
+        merge_option(&mut self.palette, &other.palette);
+        merge_option(&mut self.colors, &other.colors);
+        merge_option(&mut self.scale, &other.scale);
+        merge_option(&mut self.speed, &other.speed);
+        merge_option(&mut self.tickrate, &other.tickrate);
+        merge_option(&mut self.filter, &other.filter);
+        merge_option(&mut self.upscaler, &other.upscaler);
+        merge_option(&mut self.keypad, &other.keypad);
+        if let Some(audio) = &other.audio {
+            self.audio
+                .get_or_insert_with(AudioConfig::default)
+                .merge(audio);
+        }
+        if let Some(quirks) = &other.quirks {
+            self.quirks
+                .get_or_insert_with(QuirkOverrides::default)
+                .merge(quirks);
+        }
+    }
+}
+
+impl Config {
+    //load the config file, a missing file gives the default config
+    pub fn load() -> Result<Config, String> {
+        let path: PathBuf = match config_path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let text: String = fs::read_to_string(&path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        let config: Config = toml::from_str(&text)
+            .map_err(|e| format!("could not parse {}: {}", path.display(), e))?;
+        config
+            .check()
+            .map_err(|e| format!("could not use {}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    //check the settings with the same rules as the command line, as bad values can crash the emulator
+    fn check(&self) -> Result<(), String> {
+        check_section("", self.scale, self.speed, self.tickrate, &self.audio)?;
+        if self
+            .fast_forward_speed
+            .is_some_and(|speed| !valid_fast_forward_speed(speed))
+        {
+            return Err("invalid fast_forward_speed, expected 0 or more".to_string());
+        }
+        for (rom_hash, rom) in &self.rom {
+            check_section(
+                &format!(" for rom {}", rom_hash),
+                rom.scale,
+                rom.speed,
+                rom.tickrate,
+                &rom.audio,
+            )?;
+        }
+        if let Some(crt) = &self.crt {
+            for (name, intensity) in [
+                ("scanlines", crt.scanlines),
+                ("grid", crt.grid),
+                ("bloom", crt.bloom),
+                ("curvature", crt.curvature),
+            ] {
+                if !valid_intensity(intensity) {
+                    return Err(format!("invalid crt {}, expected 0 to 1", name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //write the config file, creating the config directory if needed
+    pub fn save(&self) -> Result<(), String> {
+        if self.read_only {
+            return Err(
+                "not saving settings, the config file could not be read when starting".to_string(),
+            );
+        }
+        let path: PathBuf = config_path().ok_or("could not find a config directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+        }
+
+        let text: String =
+            toml::to_string(self).map_err(|e| format!("could not write config: {}", e))?;
+        fs::write(&path, text).map_err(|e| format!("could not write {}: {}", path.display(), e))
+    }
+
+    //get the palette name for a rom, falling back to the global setting
+    pub fn palette_for(&self, rom_hash: &str) -> Option<String> {
+        self.rom
+            .get(rom_hash)
+            .and_then(|rom| rom.palette.clone())
+            .or_else(|| self.palette.clone())
+    }
+
+    //get the custom palette colors for a rom, falling back to the global setting
+    pub fn colors_for(&self, rom_hash: &str) -> Option<Vec<String>> {
+        self.rom
+            .get(rom_hash)
+            .and_then(|rom| rom.colors.clone())
+            .or_else(|| self.colors.clone())
+    }
+
+    //get the anti flicker filter for a rom, falling back to the global setting
+    pub fn filter_for(&self, rom_hash: &str) -> Option<String> {
+        self.rom
+            .get(rom_hash)
+            .and_then(|rom| rom.filter.clone())
+            .or_else(|| self.filter.clone())
+    }
+
+    //get the upscaler for a rom, falling back to the global setting
+    pub fn upscaler_for(&self, rom_hash: &str) -> Option<String> {
+        self.rom
+            .get(rom_hash)
+            .and_then(|rom| rom.upscaler.clone())
+            .or_else(|| self.upscaler.clone())
+    }
+
+    //get the window scale for a rom, falling back to the global setting
+    pub fn scale_for(&self, rom_hash: &str) -> Option<u32> {
+        self.rom
+            .get(rom_hash)
+            .and_then(|rom| rom.scale)
+            .or(self.scale)
+    }
+
+    //get the speed multiplier for a rom, falling back to the global setting
+    pub fn speed_for(&self, rom_hash: &str) -> Option<f64> {
+        self.rom
+            .get(rom_hash)
+            .and_then(|rom| rom.speed)
+            .or(self.speed)
+    }
+
+    //get the keypad keys for a rom, falling back to the global setting
+    pub fn keypad_for(&self, rom_hash: &str) -> Option<&[String]> {
+        self.rom
+            .get(rom_hash)
+            .and_then(|rom| rom.keypad.as_deref())
+            .or(self.keypad.as_deref())
+    }
+
+    //get the beeper settings for a rom, with rom settings taking priority over global settings
+    pub fn audio_for(&self, rom_hash: &str) -> BeeperSettings {
+        let mut settings: BeeperSettings = BeeperSettings::default();
+        if let Some(audio) = &self.audio {
+            audio.apply(&mut settings);
+        }
+        if let Some(audio) = self.rom.get(rom_hash).and_then(|rom| rom.audio.as_ref()) {
+            audio.apply(&mut settings);
+        }
+        settings
+    }
+
+    //store changed settings in a rom's section, or as global settings if no rom is given
+    pub fn set_overrides(&mut self, changes: &RomConfig, rom_hash: Option<&str>) {
+        if let Some(rom_hash) = rom_hash {
+            self.rom_mut(rom_hash).merge(changes);
+            return;
+        }
+        merge_option(&mut self.palette, &changes.palette);
+        merge_option(&mut self.colors, &changes.colors);
+        merge_option(&mut self.scale, &changes.scale);
+        merge_option(&mut self.speed, &changes.speed);
+        merge_option(&mut self.tickrate, &changes.tickrate);
+        merge_option(&mut self.filter, &changes.filter);
+        merge_option(&mut self.upscaler, &changes.upscaler);
+        merge_option(&mut self.keypad, &changes.keypad);
+        if let Some(audio) = &changes.audio {
+            self.audio
+                .get_or_insert_with(AudioConfig::default)
+                .merge(audio);
+        }
+        if let Some(quirks) = &changes.quirks {
+            self.quirks
+                .get_or_insert_with(QuirkOverrides::default)
+                .merge(quirks);
+        }
+    }
+
+    //get the overrides for a rom to change, creating them if needed
+    pub fn rom_mut(&mut self, rom_hash: &str) -> &mut RomConfig {
+        self.rom.entry(rom_hash.to_string()).or_default()
+    }
+
+    //move a rom to the top of the recent roms, stored as a full path so it opens from anywhere
+    pub fn add_recent(&mut self, file_name: &str) {
+        let path: String = fs::canonicalize(file_name)
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_else(|_| file_name.to_string());
+        self.recent.retain(|recent| *recent != path);
+        self.recent.insert(0, path);
+        self.recent.truncate(MAX_RECENT);
+    }
+}
+
+//check the settings that can be set both globally and for a rom
+fn check_section(
+    section: &str,
+    scale: Option<u32>,
+    speed: Option<f64>,
+    tickrate: Option<u32>,
+    audio: &Option<AudioConfig>,
+) -> Result<(), String> {
+    if scale.is_some_and(|scale| !valid_scale(scale)) {
+        return Err(format!(
+            "invalid scale{}, expected 1 to {}",
+            section, MAX_SCALE
+        ));
+    }
+    if speed.is_some_and(|speed| !valid_speed(speed)) {
+        return Err(format!("invalid speed{}, expected more than 0", section));
+    }
+    if tickrate.is_some_and(|tickrate| !valid_tickrate(tickrate)) {
+        return Err(format!("invalid tickrate{}, expected more than 0", section));
+    }
+    if let Some(audio) = audio {
+        if audio
+            .frequency
+            .is_some_and(|frequency| !valid_frequency(frequency))
+        {
+            return Err(format!(
+                "invalid audio frequency{}, expected more than 0",
+                section
+            ));
+        }
+        if audio.volume.is_some_and(|volume| !valid_volume(volume)) {
+            return Err(format!("invalid audio volume{}, expected 0 to 1", section));
+        }
+    }
+    Ok(())
+}
+
+//rules for settings, shared by the command line and the config file
+pub fn valid_scale(scale: u32) -> bool {
+    (1..=MAX_SCALE).contains(&scale)
+}
+
+pub fn valid_speed(speed: f64) -> bool {
+    speed > 0.0
+}
+
+//0 runs as fast as possible
+pub fn valid_fast_forward_speed(speed: f64) -> bool {
+    speed >= 0.0
+}
+
+pub fn valid_tickrate(tickrate: u32) -> bool {
+    tickrate > 0
+}
+
+pub fn valid_frequency(frequency: f32) -> bool {
+    frequency > 0.0 && frequency.is_finite()
+}
+
+pub fn valid_volume(volume: f32) -> bool {
+    (0.0..=1.0).contains(&volume)
+}
+
+//crt effect strengths
+pub fn valid_intensity(intensity: f32) -> bool {
+    (0.0..=1.0).contains(&intensity)
+}
+
+//overwrite a setting if the new value is set
+fn merge_option<T: Clone>(setting: &mut Option<T>, value: &Option<T>) {
+    if value.is_some() {
+        setting.clone_from(value);
+    }
+}
+
+//get the location of the config file
+pub fn config_path() -> Option<PathBuf> {
+    let base: PathBuf = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+            PathBuf::from(home).join(".config")
+        }
+    };
+    Some(base.join("chip8_emulator").join("config.toml"))
+}
+
+//get the sha1 of a rom as a lowercase hex string, used to key per rom settings
+pub fn rom_hash(contents: &[u8]) -> String {
+    Sha1::digest(contents)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+//get the name of a rom from its path without the extension, used to name saved files
+pub fn rom_name(rom_path: &str) -> String {
+    Path::new(rom_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "chip8".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //parse a config file and check it the way load does
+    fn parse(text: &str) -> Result<Config, String> {
+        let config: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+        config.check()?;
+        Ok(config)
+    }
+
+    //a config file with global settings and overrides for one rom
+    const CONFIG: &str = r#"
+palette = "green"
+scale = 10
+speed = 1.5
+tickrate = 20
+keypad = ["X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V"]
+
+[audio]
+frequency = 440.0
+volume = 0.5
+
+[quirks]
+shift = true
+
+[hotkeys]
+pause = "Space"
+
+[rom.abc123]
+palette = "amber"
+scale = 5
+tickrate = 30
+
+[rom.abc123.audio]
+volume = 0.25
+
+[rom.abc123.quirks]
+logic = false
+"#;
+
+    #[test]
+    fn reads_global_and_rom_settings() {
+        let config: Config = parse(CONFIG).unwrap();
+        assert_eq!(config.palette.as_deref(), Some("green"));
+        assert_eq!(config.tickrate, Some(20));
+        assert_eq!(config.keypad.as_ref().map(Vec::len), Some(16));
+        assert_eq!(config.quirks.unwrap().shift, Some(true));
+        assert_eq!(
+            config.hotkeys.get("pause").map(String::as_str),
+            Some("Space")
+        );
+
+        let rom: &RomConfig = &config.rom["abc123"];
+        assert_eq!(rom.tickrate, Some(30));
+        assert_eq!(rom.quirks.unwrap().logic, Some(false));
+        assert_eq!(rom.quirks.unwrap().shift, None);
+    }
+
+    #[test]
+    fn rom_settings_come_before_global_settings() {
+        let config: Config = parse(CONFIG).unwrap();
+        assert_eq!(config.palette_for("abc123").as_deref(), Some("amber"));
+        assert_eq!(config.scale_for("abc123"), Some(5));
+        assert_eq!(config.speed_for("abc123"), Some(1.5));
+
+        //audio settings are merged one by one
+        let settings: BeeperSettings = config.audio_for("abc123");
+        assert_eq!((settings.frequency, settings.volume), (440.0, 0.25));
+
+        //other roms only get the global settings
+        assert_eq!(config.palette_for("def456").as_deref(), Some("green"));
+        assert_eq!(config.scale_for("def456"), Some(10));
+        assert_eq!(config.audio_for("def456").volume, 0.5);
+        assert_eq!(Config::default().palette_for("abc123"), None);
+    }
+
+    #[test]
+    fn stores_changes_for_a_rom_or_globally() {
+        let mut config: Config = parse(CONFIG).unwrap();
+        let changes: RomConfig = RomConfig {
+            palette: Some("lcd".to_string()),
+            ..RomConfig::default()
+        };
+        config.set_overrides(&changes, Some("def456"));
+        assert_eq!(config.palette_for("def456").as_deref(), Some("lcd"));
+        assert_eq!(config.palette.as_deref(), Some("green"));
+
+        config.set_overrides(&changes, None);
+        assert_eq!(config.palette.as_deref(), Some("lcd"));
+        assert_eq!(config.palette_for("abc123").as_deref(), Some("amber"));
+    }
+
+    #[test]
+    fn rejects_settings_the_command_line_would() {
+        for (text, message) in [
+            ("scale = 0", "invalid scale, expected 1 to 40"),
+            ("scale = 41", "invalid scale, expected 1 to 40"),
+            ("speed = 0.0", "invalid speed, expected more than 0"),
+            ("tickrate = 0", "invalid tickrate, expected more than 0"),
+            (
+                "fast_forward_speed = -1.0",
+                "invalid fast_forward_speed, expected 0 or more",
+            ),
+            (
+                "[audio]\nfrequency = 0.0",
+                "invalid audio frequency, expected more than 0",
+            ),
+            (
+                "[audio]\nvolume = 1.5",
+                "invalid audio volume, expected 0 to 1",
+            ),
+            (
+                "[rom.abc123]\nspeed = -2.0",
+                "invalid speed for rom abc123, expected more than 0",
+            ),
+            (
+                "[rom.abc123.audio]\nvolume = -1.0",
+                "invalid audio volume for rom abc123, expected 0 to 1",
+            ),
+        ] {
+            assert_eq!(parse(text).unwrap_err(), message, "{}", text);
+        }
+        assert!(parse("scale = \"big\"").is_err());
+        assert!(parse("").is_ok());
+    }
+
+    #[test]
+    fn checks_crt_intensities() {
+        let config: Config =
+            parse("[crt]\nenabled = true\nscanlines = 1.0\nbloom = 0.0\n").unwrap();
+        let crt: CrtSettings = config.crt.unwrap();
+        assert!(crt.enabled);
+        assert_eq!((crt.scanlines, crt.bloom), (1.0, 0.0));
+        //unset intensities keep their defaults
+        assert_eq!(crt.grid, CrtSettings::default().grid);
+
+        for (name, value) in [
+            ("scanlines", "1.5"),
+            ("grid", "-0.1"),
+            ("bloom", "2"),
+            ("curvature", "nan"),
+        ] {
+            let error: String = parse(&format!("[crt]\n{} = {}\n", name, value)).unwrap_err();
+            assert_eq!(error, format!("invalid crt {}, expected 0 to 1", name));
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//use declarations
+use sdl2::pixels::Color;
+use serde::{Deserialize, Serialize};
+
+//crt settings struct and methods
+//each effect intensity goes from 0 (off) to 1 (strongest)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrtSettings {
+    pub enabled: bool,
+    //darkening between pixel rows
+    pub scanlines: f32,
+    //darkening of the gaps between pixels
+    pub grid: f32,
+    //glow spreading out from lit pixels
+    pub bloom: f32,
+    //barrel bend of the screen
+    pub curvature: f32,
+}
+
+impl Default for CrtSettings {
+    fn default() -> CrtSettings {
+        CrtSettings {
+            enabled: false,
+            scanlines: 0.5,
+            grid: 0.2,
+            bloom: 0.3,
+            curvature: 0.1,
+        }
+    }
+}
+
+//draw a frame scaled to the output size with the crt effects applied
+//the scanlines and grid follow the emulated display pixels even if the frame was upscaled
+//returns the output image as rgb24 bytes
+#[allow(clippy::too_many_arguments)]
+pub fn apply(
+    frame: &[Color],
+    frame_width: usize,
+    frame_height: usize,
+    display_width: usize,
+    display_height: usize,
+    width: usize,
+    height: usize,
+    settings: &CrtSettings,
+) -> Vec<u8> {
+    let mut output: Vec<u8> = vec![0; width * height * 3];
+
+    //blur the frame once at its own size for the bloom
+    let glow: Vec<[f32; 3]> = if settings.bloom > 0.0 {
+        blur(frame, frame_width, frame_height)
+    } else {
+        Vec::new()
+    };
+
+    //size of an output pixel measured in display pixels, used for the width of the grid lines
+    let edge_x: f32 = display_width as f32 / width as f32;
+    let edge_y: f32 = display_height as f32 / height as f32;
+    let bend: f32 = settings.curvature.clamp(0.0, 1.0) * 0.25;
+
+    //loop through each output pixel
+    for y in 0..height {
+        for x in 0..width {
+            //get the position on the screen from -1 to 1
+            let screen_u: f32 = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+            let screen_v: f32 = (y as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+
+            //bend the screen, anything bent off the edge stays black
+            let u: f32 = screen_u * (1.0 + bend * screen_v * screen_v);
+            let v: f32 = screen_v * (1.0 + bend * screen_u * screen_u);
+            if u.abs() > 1.0 || v.abs() > 1.0 {
+                continue;
+            }
+
+            //get the matching position in the frame
+            let source_x: f32 = (u + 1.0) * 0.5 * frame_width as f32;
+            let source_y: f32 = (v + 1.0) * 0.5 * frame_height as f32;
+            let pixel_x: usize = (source_x as usize).min(frame_width - 1);
+            let pixel_y: usize = (source_y as usize).min(frame_height - 1);
+            let color: Color = frame[pixel_y * frame_width + pixel_x];
+            let base: [f32; 3] = [color.r as f32, color.g as f32, color.b as f32];
+            let mut rgb: [f32; 3] = base;
+
+            //get the position within the display pixel
+            let column_position: f32 = ((u + 1.0) * 0.5 * display_width as f32).fract();
+            let row_position: f32 = ((v + 1.0) * 0.5 * display_height as f32).fract();
+
+            //scanlines, darkest at the top and bottom edge of each pixel row
+            let mut brightness: f32 =
+                1.0 - settings.scanlines * (1.0 - 4.0 * row_position * (1.0 - row_position));
+
+            //grid, darken the first output pixel of each display pixel
+            if column_position < edge_x || row_position < edge_y {
+                brightness *= 1.0 - settings.grid;
+            }
+            for channel in rgb.iter_mut() {
+                *channel *= brightness;
+            }
+
+            //bloom, add the light blurred in from brighter neighbours
+            if !glow.is_empty() {
+                let light: [f32; 3] = sample(
+                    &glow,
+                    frame_width,
+                    frame_height,
+                    source_x - 0.5,
+                    source_y - 0.5,
+                );
+                for i in 0..3 {
+                    rgb[i] += (light[i] - base[i]).max(0.0) * settings.bloom;
+                }
+            }
+
+            //store the pixel
+            let index: usize = (y * width + x) * 3;
+            for (i, channel) in rgb.iter().enumerate() {
+                output[index + i] = channel.clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    output
+}
+
+//blur a frame with a 5 pixel wide gaussian, first across then down
+fn blur(frame: &[Color], width: usize, height: usize) -> Vec<[f32; 3]> {
+    const WEIGHTS: [f32; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
+
+    //blur each row
+    let mut across: Vec<[f32; 3]> = vec![[0.0; 3]; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            for (i, weight) in WEIGHTS.iter().enumerate() {
+                let source_x: usize = (x + i).saturating_sub(2).min(width - 1);
+                let color: Color = frame[y * width + source_x];
+                let sum: &mut [f32; 3] = &mut across[y * width + x];
+                sum[0] += color.r as f32 * weight;
+                sum[1] += color.g as f32 * weight;
+                sum[2] += color.b as f32 * weight;
+            }
+        }
+    }
+
+    //blur each column
+    let mut blurred: Vec<[f32; 3]> = vec![[0.0; 3]; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            for (i, weight) in WEIGHTS.iter().enumerate() {
+                let source_y: usize = (y + i).saturating_sub(2).min(height - 1);
+                let color: [f32; 3] = across[source_y * width + x];
+                let sum: &mut [f32; 3] = &mut blurred[y * width + x];
+                for channel in 0..3 {
+                    sum[channel] += color[channel] * weight;
+                }
+            }
+        }
+    }
+
+    blurred
+}
+
+//read an image at a fractional position, mixing the 4 nearest pixels
+fn sample(image: &[[f32; 3]], width: usize, height: usize, x: f32, y: f32) -> [f32; 3] {
+    let x: f32 = x.clamp(0.0, (width - 1) as f32);
+    let y: f32 = y.clamp(0.0, (height - 1) as f32);
+    let left: usize = x as usize;
+    let top: usize = y as usize;
+    let right: usize = (left + 1).min(width - 1);
+    let bottom: usize = (top + 1).min(height - 1);
+    let amount_x: f32 = x - left as f32;
+    let amount_y: f32 = y - top as f32;
+
+    let mut result: [f32; 3] = [0.0; 3];
+    for (channel, value) in result.iter_mut().enumerate() {
+        let upper: f32 = image[top * width + left][channel] * (1.0 - amount_x)
+            + image[top * width + right][channel] * amount_x;
+        let lower: f32 = image[bottom * width + left][channel] * (1.0 - amount_x)
+            + image[bottom * width + right][channel] * amount_x;
+        *value = upper * (1.0 - amount_y) + lower * amount_y;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Color = Color::RGB(0xFF, 0xFF, 0xFF);
+    const BLACK: Color = Color::RGB(0, 0, 0);
+
+    //settings with every effect turned off
+    fn off() -> CrtSettings {
+        CrtSettings {
+            enabled: true,
+            scanlines: 0.0,
+            grid: 0.0,
+            bloom: 0.0,
+            curvature: 0.0,
+        }
+    }
+
+    //the red channel of each output pixel
+    fn red(output: &[u8]) -> Vec<u8> {
+        output.iter().step_by(3).copied().collect()
+    }
+
+    #[test]
+    fn scales_the_frame_with_the_effects_off() {
+        let output: Vec<u8> = apply(&[WHITE, BLACK], 2, 1, 2, 1, 4, 2, &off());
+        assert_eq!(output.len(), 4 * 2 * 3);
+        assert_eq!(red(&output), [255, 255, 0, 0, 255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn scanlines_darken_the_edges_of_each_row() {
+        let settings: CrtSettings = CrtSettings {
+            scanlines: 1.0,
+            ..off()
+        };
+        let output: Vec<u8> = apply(&[WHITE], 1, 1, 1, 1, 1, 4, &settings);
+        let rows: Vec<u8> = red(&output);
+        assert!(rows[0] < rows[1]);
+        assert_eq!(rows[0], rows[3]);
+        assert_eq!(rows[1], rows[2]);
+    }
+
+    #[test]
+    fn grid_darkens_the_first_pixel_of_each_display_pixel() {
+        let settings: CrtSettings = CrtSettings { grid: 0.5, ..off() };
+        let output: Vec<u8> = apply(&[WHITE; 4], 2, 2, 2, 2, 4, 4, &settings);
+        assert_eq!(
+            red(&output),
+            [127, 127, 127, 127, 127, 255, 127, 255, 127, 127, 127, 127, 127, 255, 127, 255]
+        );
+    }
+
+    #[test]
+    fn bloom_spreads_light_to_dark_neighbours() {
+        let frame: [Color; 5] = [BLACK, BLACK, WHITE, BLACK, BLACK];
+        assert_eq!(
+            red(&apply(&frame, 5, 1, 5, 1, 5, 1, &off())),
+            [0, 0, 255, 0, 0]
+        );
+        let settings: CrtSettings = CrtSettings {
+            bloom: 1.0,
+            ..off()
+        };
+        let glow: Vec<u8> = red(&apply(&frame, 5, 1, 5, 1, 5, 1, &settings));
+        assert_eq!(glow[2], 255);
+        assert!(glow[1] > glow[0] && glow[0] > 0);
+        assert_eq!(glow[1], glow[3]);
+    }
+
+    #[test]
+    fn curvature_leaves_the_corners_black() {
+        let settings: CrtSettings = CrtSettings {
+            curvature: 1.0,
+            ..off()
+        };
+        let output: Vec<u8> = red(&apply(&[WHITE], 1, 1, 1, 1, 16, 16, &settings));
+        assert_eq!(output[0], 0);
+        assert_eq!(output[16 * 16 - 1], 0);
+        assert_eq!(output[8 * 16 + 8], 255);
+    }
+}
@@ -0,0 +1,468 @@
+//use declarations
+use crate::chip8::{Quirks, INSTRUCTIONS_PER_FRAME};
+use crate::config;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+//rom database built into the program, in the format of programs.json from the community chip 8 database
+const EMBEDDED: &str = include_str!("../data/programs.json");
+
+//a program in the database, with one entry for each known version of its rom
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    release: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: BTreeMap<String, RomEntry>,
+}
+
+//a rom in the database, keyed by its sha1
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    #[serde(default)]
+    description: Option<String>,
+    //platforms the rom runs on, best first
+    #[serde(default)]
+    platforms: Vec<String>,
+    //quirks that differ from a platform's defaults for this rom
+    #[serde(default)]
+    quirky_platforms: BTreeMap<String, QuirkOverrides>,
+    #[serde(default)]
+    tickrate: Option<u32>,
+    #[serde(default)]
+    colors: Option<Colors>,
+    //chip 8 keys for named buttons such as up, down, left, right, a and b
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+}
+
+//colors of a rom, only the pixel colors are used
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+//quirks to change, the unset ones are left alone
+//also used for the quirks in the config file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuirkOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shift: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_increment_by_x: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_leave_i_unchanged: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrap: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jump: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vblank: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logic: Option<bool>,
+}
+
+impl QuirkOverrides {
+    //set a quirk from text like shift=on, using the database names
+    pub fn set(&mut self, text: &str) -> Result<(), String> {
+        let (name, value): (&str, &str) = text
+            .split_once('=')
+            .ok_or_else(|| format!("invalid quirk \"{}\", expected NAME=on or NAME=off", text))?;
+        let on: bool = match value {
+            "on" | "true" | "1" => true,
+            "off" | "false" | "0" => false,
+            _ => {
+                return Err(format!(
+                    "invalid quirk value \"{}\", expected on or off",
+                    value
+                ))
+            }
+        };
+        let quirk: &mut Option<bool> = match name {
+            "shift" => &mut self.shift,
+            "memoryIncrementByX" => &mut self.memory_increment_by_x,
+            "memoryLeaveIUnchanged" => &mut self.memory_leave_i_unchanged,
+            "wrap" => &mut self.wrap,
+            "jump" => &mut self.jump,
+            "vblank" => &mut self.vblank,
+            "logic" => &mut self.logic,
+            _ => {
+                return Err(format!(
+                    "unknown quirk \"{}\", expected shift, memoryIncrementByX, memoryLeaveIUnchanged, wrap, jump, vblank or logic",
+                    name
+                ))
+            }
+        };
+        *quirk = Some(on);
+        Ok(())
+    }
+
+    //get the overrides that turn each quirk that differs between two sets of quirks to its new value
+    pub fn changes(from: &Quirks, to: &Quirks) -> QuirkOverrides {
+        let changed = |from: bool, to: bool| if from != to { Some(to) } else { None };
+        QuirkOverrides {
+            shift: changed(from.shift, to.shift),
+            memory_increment_by_x: changed(from.memory_increment_by_x, to.memory_increment_by_x),
+            memory_leave_i_unchanged: changed(
+                from.memory_leave_i_unchanged,
+                to.memory_leave_i_unchanged,
+            ),
+            wrap: changed(from.wrap, to.wrap),
+            jump: changed(from.jump, to.jump),
+            vblank: changed(from.vblank, to.vblank),
+            logic: changed(from.logic, to.logic),
+        }
+    }
+
+    //overwrite the quirks that are set in other
+    pub fn merge(&mut self, other: &QuirkOverrides) {
+        self.shift = other.shift.or(self.shift);
+        self.memory_increment_by_x = other.memory_increment_by_x.or(self.memory_increment_by_x);
+        self.memory_leave_i_unchanged = other
+            .memory_leave_i_unchanged
+            .or(self.memory_leave_i_unchanged);
+        self.wrap = other.wrap.or(self.wrap);
+        self.jump = other.jump.or(self.jump);
+        self.vblank = other.vblank.or(self.vblank);
+        self.logic = other.logic.or(self.logic);
+    }
+
+    //overwrite the quirks that are set here
+    pub fn apply(&self, quirks: &mut Quirks) {
+        if let Some(shift) = self.shift {
+            quirks.shift = shift;
+        }
+        if let Some(memory_increment_by_x) = self.memory_increment_by_x {
+            quirks.memory_increment_by_x = memory_increment_by_x;
+        }
+        if let Some(memory_leave_i_unchanged) = self.memory_leave_i_unchanged {
+            quirks.memory_leave_i_unchanged = memory_leave_i_unchanged;
+        }
+        if let Some(wrap) = self.wrap {
+            quirks.wrap = wrap;
+        }
+        if let Some(jump) = self.jump {
+            quirks.jump = jump;
+        }
+        if let Some(vblank) = self.vblank {
+            quirks.vblank = vblank;
+        }
+        if let Some(logic) = self.logic {
+            quirks.logic = logic;
+        }
+    }
+}
+
+//what the database knows about a rom
+pub struct RomInfo {
+    pub title: String,
+    pub description: Option<String>,
+    pub release: Option<String>,
+    pub authors: Vec<String>,
+    pub platform: Option<String>,
+    //quirks and instructions per frame the rom needs, if its platform is known
+    pub quirks: Option<Quirks>,
+    pub tickrate: Option<u32>,
+    pub colors: Option<Vec<String>>,
+    pub keys: BTreeMap<String, u8>,
+}
+
+//database struct and methods
+#[derive(Default)]
+pub struct Database {
+    programs: Vec<Program>,
+    //sha1 of each rom to the program it belongs to
+    hashes: HashMap<String, usize>,
+}
+
+impl Database {
+    //load the built in database, or the copy in the config folder if there is one
+    pub fn load() -> Database {
+        let user_copy: Option<String> = user_path().and_then(|path| fs::read_to_string(path).ok());
+        user_copy
+            .and_then(|text| {
+                Database::parse(&text)
+                    .map_err(|e| eprintln!("could not read the rom database: {}", e))
+                    .ok()
+            })
+            .or_else(|| Database::parse(EMBEDDED).ok())
+            .unwrap_or_default()
+    }
+
+    //read a database in the programs.json format
+    pub fn parse(text: &str) -> Result<Database, String> {
+        let programs: Vec<Program> = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let mut hashes: HashMap<String, usize> = HashMap::new();
+        for (index, program) in programs.iter().enumerate() {
+            for hash in program.roms.keys() {
+                hashes.insert(hash.to_lowercase(), index);
+            }
+        }
+        Ok(Database { programs, hashes })
+    }
+
+    //find a rom by its sha1
+    pub fn lookup(&self, rom_hash: &str) -> Option<RomInfo> {
+        let program: &Program = &self.programs[*self.hashes.get(rom_hash)?];
+        let rom: &RomEntry = program
+            .roms
+            .iter()
+            .find(|(hash, _)| hash.to_lowercase() == rom_hash)
+            .map(|(_, rom)| rom)?;
+
+        //start from the defaults of the rom's best platform and apply its quirks for that platform
+        let platform: Option<String> = rom.platforms.first().cloned();
+        let defaults: Option<(Quirks, u32)> = platform.as_deref().and_then(platform_defaults);
+        let quirks: Option<Quirks> = defaults.map(|(mut quirks, _)| {
+            if let Some(overrides) = platform
+                .as_ref()
+                .and_then(|platform| rom.quirky_platforms.get(platform))
+            {
+                overrides.apply(&mut quirks);
+            }
+            quirks
+        });
+
+        Some(RomInfo {
+            title: program.title.clone(),
+            description: rom
+                .description
+                .clone()
+                .or_else(|| program.description.clone()),
+            release: program.release.clone(),
+            authors: program.authors.clone(),
+            platform,
+            quirks,
+            tickrate: rom.tickrate.or(defaults.map(|(_, tickrate)| tickrate)),
+            colors: rom
+                .colors
+                .as_ref()
+                .map(|colors| colors.pixels.clone())
+                .filter(|pixels| pixels.len() >= 2),
+            keys: rom.keys.clone(),
+        })
+    }
+}
+
+//list the quirks as NAME=on or NAME=off using the database names
+pub fn format_quirks(quirks: &Quirks) -> String {
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    format!(
+        "shift={} memoryIncrementByX={} memoryLeaveIUnchanged={} wrap={} jump={} vblank={} logic={}",
+        on_off(quirks.shift),
+        on_off(quirks.memory_increment_by_x),
+        on_off(quirks.memory_leave_i_unchanged),
+        on_off(quirks.wrap),
+        on_off(quirks.jump),
+        on_off(quirks.vblank),
+        on_off(quirks.logic)
+    )
+}
+
+//get where a user copy of the database can be put, next to the config file
+pub fn user_path() -> Option<PathBuf> {
+    Some(config::config_path()?.parent()?.join("programs.json"))
+}
+
+//platforms in the database that have default quirks
+pub const PLATFORMS: [&str; 10] = [
+    "originalChip8",
+    "hybridVIP",
+    "modernChip8",
+    "chip8x",
+    "chip48",
+    "superchip1",
+    "superchip",
+    "megachip8",
+    "xochip",
+    "default",
+];
+
+//get the default quirks and instructions per frame for a platform in the database
+//default is how this emulator runs roms that are not in the database
+pub fn platform_defaults(platform: &str) -> Option<(Quirks, u32)> {
+    let cosmac: Quirks = Quirks {
+        shift: false,
+        memory_increment_by_x: false,
+        memory_leave_i_unchanged: false,
+        wrap: false,
+        jump: false,
+        vblank: true,
+        logic: true,
+    };
+    let superchip: Quirks = Quirks {
+        shift: true,
+        memory_increment_by_x: false,
+        memory_leave_i_unchanged: true,
+        wrap: false,
+        jump: true,
+        vblank: false,
+        logic: false,
+    };
+    match platform {
+        "originalChip8" | "hybridVIP" | "chip8x" => Some((cosmac, 15)),
+        "modernChip8" => Some((
+            Quirks {
+                vblank: false,
+                logic: false,
+                ..cosmac
+            },
+            12,
+        )),
+        "chip48" => Some((
+            Quirks {
+                memory_increment_by_x: true,
+                memory_leave_i_unchanged: false,
+                ..superchip
+            },
+            30,
+        )),
+        "superchip1" => Some((
+            Quirks {
+                memory_increment_by_x: true,
+                memory_leave_i_unchanged: false,
+                ..superchip
+            },
+            30,
+        )),
+        "superchip" | "megachip8" => Some((superchip, 30)),
+        "xochip" => Some((
+            Quirks {
+                wrap: true,
+                vblank: false,
+                logic: false,
+                ..cosmac
+            },
+            100,
+        )),
+        "default" => Some((Quirks::default(), INSTRUCTIONS_PER_FRAME)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a rom and a database listing it in the programs.json format, with its sha1 in capitals
+    const ROM: [u8; 4] = [0x00, 0xE0, 0x12, 0x00];
+
+    //the ibm logo rom, as hex
+    const IBM_LOGO: &str = concat!(
+        "00e0a22a600c6108d01f7009a239d01fa2487008d01f7004a257d01f7008a266d01f7008a275d01f1228",
+        "ff00ff003c003c003c003c00ff00ffff00ff0038003f003f003800ff00ff8000e000e00080008000e000",
+        "e00080f800fc003e003f003b003900f800f8030007000f00bf00fb00f300e30043e000e0008000800080",
+        "008000e000e0"
+    );
+
+    fn database() -> Database {
+        let text: String = format!(
+            r##"[
+                {{
+                    "title": "Other Program",
+                    "roms": {{ "0000000000000000000000000000000000000000": {{ "platforms": ["superchip"] }} }}
+                }},
+                {{
+                    "title": "Test Program",
+                    "description": "a test",
+                    "authors": ["Someone"],
+                    "roms": {{
+                        "{}": {{
+                            "platforms": ["originalChip8", "modernChip8"],
+                            "quirkyPlatforms": {{ "originalChip8": {{ "shift": true }} }},
+                            "colors": {{ "pixels": ["#000000", "#ff0000"] }},
+                            "keys": {{ "up": 5, "a": 6 }}
+                        }}
+                    }}
+                }}
+            ]"##,
+            config::rom_hash(&ROM).to_uppercase()
+        );
+        Database::parse(&text).unwrap()
+    }
+
+    #[test]
+    fn looks_up_a_rom_by_sha1() {
+        let info: RomInfo = database().lookup(&config::rom_hash(&ROM)).unwrap();
+        assert_eq!(info.title, "Test Program");
+        assert_eq!(info.description.as_deref(), Some("a test"));
+        assert_eq!(info.authors, ["Someone"]);
+        assert_eq!(info.platform.as_deref(), Some("originalChip8"));
+        assert_eq!(
+            info.colors,
+            Some(vec!["#000000".to_string(), "#ff0000".to_string()])
+        );
+        assert_eq!(info.keys.get("up"), Some(&5));
+        assert_eq!(info.keys.get("a"), Some(&6));
+    }
+
+    #[test]
+    fn uses_the_quirks_and_tickrate_of_the_best_platform() {
+        let info: RomInfo = database().lookup(&config::rom_hash(&ROM)).unwrap();
+        let (quirks, tickrate): (Quirks, u32) = platform_defaults("originalChip8").unwrap();
+        assert_eq!(
+            info.quirks,
+            Some(Quirks {
+                shift: true,
+                ..quirks
+            })
+        );
+        assert_eq!(info.tickrate, Some(tickrate));
+    }
+
+    #[test]
+    fn unknown_roms_are_not_found() {
+        assert!(database()
+            .lookup(&config::rom_hash(&[0x12, 0x00]))
+            .is_none());
+    }
+
+    #[test]
+    fn built_in_database_can_be_read() {
+        let database: Database = Database::parse(EMBEDDED).unwrap();
+        for rom in database
+            .programs
+            .iter()
+            .flat_map(|program| program.roms.values())
+        {
+            assert!(rom
+                .platforms
+                .iter()
+                .all(|platform| PLATFORMS.contains(&platform.as_str())));
+        }
+    }
+
+    #[test]
+    fn built_in_database_knows_the_ibm_logo() {
+        let rom: Vec<u8> = (0..IBM_LOGO.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&IBM_LOGO[i..i + 2], 16).unwrap())
+            .collect();
+        assert_eq!(rom.len(), 132);
+        let info: RomInfo = Database::parse(EMBEDDED)
+            .unwrap()
+            .lookup(&config::rom_hash(&rom))
+            .unwrap();
+        assert_eq!(info.title, "IBM Logo");
+        assert_eq!(info.platform.as_deref(), Some("originalChip8"));
+        let (quirks, tickrate): (Quirks, u32) = platform_defaults("originalChip8").unwrap();
+        assert_eq!(info.quirks, Some(quirks));
+        assert_eq!(info.tickrate, Some(tickrate));
+        assert_eq!(info.colors, None);
+    }
+
+    #[test]
+    fn rejects_a_database_that_is_not_programs_json() {
+        assert!(Database::parse("{}").is_err());
+    }
+}
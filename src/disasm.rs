@@ -0,0 +1,83 @@
+//use declarations
+use std::fmt::Write;
+
+//get the mnemonic for an opcode, using the common chip 8 assembly names
+pub fn instruction(opcode: u16) -> String {
+    let x: u16 = (opcode & 0x0F00) >> 8;
+    let y: u16 = (opcode & 0x00F0) >> 4;
+    let n: u16 = opcode & 0x000F;
+    let nn: u16 = opcode & 0x00FF;
+    let nnn: u16 = opcode & 0x0FFF;
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            _ => format!("SYS {:#05x}", nnn),
+        },
+        0x1000 => format!("JP {:#05x}", nnn),
+        0x2000 => format!("CALL {:#05x}", nnn),
+        0x3000 => format!("SE V{:X}, {:#04x}", x, nn),
+        0x4000 => format!("SNE V{:X}, {:#04x}", x, nn),
+        0x5000 if n == 0 => format!("SE V{:X}, V{:X}", x, y),
+        0x6000 => format!("LD V{:X}, {:#04x}", x, nn),
+        0x7000 => format!("ADD V{:X}, {:#04x}", x, nn),
+        0x8000 => match n {
+            0x0 => format!("LD V{:X}, V{:X}", x, y),
+            0x1 => format!("OR V{:X}, V{:X}", x, y),
+            0x2 => format!("AND V{:X}, V{:X}", x, y),
+            0x3 => format!("XOR V{:X}, V{:X}", x, y),
+            0x4 => format!("ADD V{:X}, V{:X}", x, y),
+            0x5 => format!("SUB V{:X}, V{:X}", x, y),
+            0x6 => format!("SHR V{:X}, V{:X}", x, y),
+            0x7 => format!("SUBN V{:X}, V{:X}", x, y),
+            0xE => format!("SHL V{:X}, V{:X}", x, y),
+            _ => data(opcode),
+        },
+        0x9000 if n == 0 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA000 => format!("LD I, {:#05x}", nnn),
+        0xB000 => format!("JP V0, {:#05x}", nnn),
+        0xC000 => format!("RND V{:X}, {:#04x}", x, nn),
+        0xD000 => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE000 => match nn {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => data(opcode),
+        },
+        0xF000 => match nn {
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            _ => data(opcode),
+        },
+        _ => data(opcode),
+    }
+}
+
+//list every 2 bytes of a rom as an instruction, with its address and opcode
+//data mixed in with the code is listed as instructions too, as there is no way to tell them apart
+pub fn listing(contents: &[u8], address: usize) -> String {
+    let mut text: String = String::new();
+    for (i, pair) in contents.chunks(2).enumerate() {
+        let opcode: u16 = ((pair[0] as u16) << 8) | pair.get(1).copied().unwrap_or(0) as u16;
+        let _ = writeln!(
+            text,
+            "{:#05x}  {:04X}  {}",
+            address + i * 2,
+            opcode,
+            instruction(opcode)
+        );
+    }
+    text
+}
+
+//show an opcode that is not an instruction as data
+fn data(opcode: u16) -> String {
+    format!("DW {:#06x}", opcode)
+}
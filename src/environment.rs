@@ -0,0 +1,524 @@
+//use declarations
+use crate::chip8::{Chip8, Quirks, INSTRUCTIONS_PER_FRAME, PROGRAM_START};
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+//instructions a step has to run across all environments before it is split between threads,
+//below this starting the threads takes longer than running the instructions
+const PARALLEL_INSTRUCTIONS: u64 = 1_000_000;
+
+//a number read from memory, such as a score or a lives counter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MemoryValue {
+    Byte(usize),
+    //two bytes, high byte first
+    Word(usize),
+    //decimal digits one to a byte, most significant first, as FX33 stores them
+    Digits { address: usize, count: usize },
+}
+
+impl MemoryValue {
+    //read the value from memory, addresses past the end wrap around
+    pub fn read(&self, memory: &[u8]) -> i64 {
+        let byte = |address: usize| memory[address % memory.len()] as i64;
+        match *self {
+            MemoryValue::Byte(address) => byte(address),
+            MemoryValue::Word(address) => byte(address) << 8 | byte(address + 1),
+            MemoryValue::Digits { address, count } => {
+                (0..count).fold(0, |total, digit| total * 10 + byte(address + digit))
+            }
+        }
+    }
+}
+
+//a reward for a value in memory changing, weight times how much it went up in a step
+//a score is rewarded with a positive weight and losing lives punished with a positive weight
+//on the lives counter, as it goes down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reward {
+    pub value: MemoryValue,
+    pub weight: f64,
+}
+
+//when an episode ends, checked after each frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Done {
+    //a value is at or below a number, such as no lives left
+    AtMost(MemoryValue, i64),
+    //a value is a number, such as a game over flag being set
+    Equals(MemoryValue, i64),
+}
+
+impl Done {
+    fn reached(&self, memory: &[u8]) -> bool {
+        match *self {
+            Done::AtMost(value, limit) => value.read(memory) <= limit,
+            Done::Equals(value, target) => value.read(memory) == target,
+        }
+    }
+}
+
+//what an agent sees after each step
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    //the 64x32 display, one byte per pixel, 1 for lit
+    pub display: Vec<u8>,
+    //whether the beeper is sounding
+    pub sound: bool,
+    pub frame: u64,
+}
+
+//settings for an environment
+#[derive(Clone, Debug)]
+pub struct EnvironmentConfig {
+    pub rom: Vec<u8>,
+    pub load_address: usize,
+    pub quirks: Quirks,
+    pub instructions_per_frame: u32,
+    //frames run for each step, with the same keys held
+    pub frame_skip: u32,
+    //seed for the random number generator at every reset, so episodes repeat exactly
+    pub seed: u64,
+    pub rewards: Vec<Reward>,
+    pub done: Vec<Done>,
+    //frames an episode can last before it is ended, None for no limit
+    pub max_frames: Option<u64>,
+}
+
+impl EnvironmentConfig {
+    //settings for a rom with the default quirks and speed, one frame a step and no rewards
+    pub fn new(rom: Vec<u8>) -> EnvironmentConfig {
+        EnvironmentConfig {
+            rom,
+            load_address: PROGRAM_START,
+            quirks: Quirks::default(),
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            frame_skip: 1,
+            seed: 0,
+            rewards: Vec::new(),
+            done: Vec::new(),
+            max_frames: None,
+        }
+    }
+}
+
+//environment struct and methods
+//runs a rom without a window for reinforcement learning, with a gym style reset and step
+pub struct Environment {
+    config: EnvironmentConfig,
+    chip8: Chip8,
+    done: bool,
+}
+
+impl Environment {
+    //make an environment, failing if the rom does not fit in memory
+    pub fn new(config: EnvironmentConfig) -> Result<Environment, String> {
+        let mut environment: Environment = Environment {
+            config,
+            chip8: Chip8::new(),
+            done: false,
+        };
+        environment.restart()?;
+        Ok(environment)
+    }
+
+    //start a new episode from the beginning of the rom
+    pub fn reset(&mut self) -> Observation {
+        self.restart()
+            .expect("the rom was loaded when the environment was made");
+        self.observation()
+    }
+
+    //hold the keys in a mask, bit n for key n, for frame_skip frames
+    //returns what the agent sees after, the reward for the step, and whether the episode is over
+    pub fn step(&mut self, keys: u16) -> (Observation, f64, bool) {
+        if self.done {
+            return (self.observation(), 0.0, true);
+        }
+        for (key, pressed) in self.chip8.input.iter_mut().enumerate() {
+            *pressed = (keys >> key) as u8 & 1;
+        }
+
+        let before: Vec<i64> = self.read_rewards();
+        for _ in 0..self.config.frame_skip.max(1) {
+            self.run_frame();
+            self.done = self
+                .config
+                .done
+                .iter()
+                .any(|done| done.reached(&self.chip8.memory))
+                || self
+                    .config
+                    .max_frames
+                    .is_some_and(|max_frames| self.chip8.frame >= max_frames);
+            if self.done {
+                break;
+            }
+        }
+        let reward: f64 = self
+            .read_rewards()
+            .iter()
+            .zip(before)
+            .zip(&self.config.rewards)
+            .map(|((after, before), reward)| (after - before) as f64 * reward.weight)
+            .sum();
+        (self.observation(), reward, self.done)
+    }
+
+    //the machine, for reading anything the observation leaves out
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    //change the seed used from the next reset
+    pub fn set_seed(&mut self, seed: u64) {
+        self.config.seed = seed;
+    }
+
+    //reload the rom with the settings and seed
+    fn restart(&mut self) -> Result<(), String> {
+        self.chip8.reset();
+        self.chip8.quirks = self.config.quirks;
+        self.chip8.instructions_per_frame = self.config.instructions_per_frame;
+        self.chip8.input = [0; 0x10];
+        self.chip8
+            .load_rom_at(&self.config.rom, self.config.load_address)?;
+        self.chip8.seed(self.config.seed);
+        self.done = false;
+        Ok(())
+    }
+
+    //run instructions until the timers tick at the end of the frame
+    fn run_frame(&mut self) {
+        while !self.chip8.step() {}
+    }
+
+    fn read_rewards(&self) -> Vec<i64> {
+        self.config
+            .rewards
+            .iter()
+            .map(|reward| reward.value.read(&self.chip8.memory))
+            .collect()
+    }
+
+    fn observation(&self) -> Observation {
+        Observation {
+            display: self.chip8.display.to_vec(),
+            sound: self.chip8.sound_timer != 0,
+            frame: self.chip8.frame,
+        }
+    }
+}
+
+//vectorised environment struct and methods
+//steps many environments at once across threads, each seeded differently
+//environments that finish are reset as part of the step, returning the first observation of the
+//new episode along with done set
+pub struct VecEnvironment {
+    environments: Vec<Environment>,
+}
+
+impl VecEnvironment {
+    //make a number of environments from the same settings, the nth seeded with seed + n
+    pub fn new(config: EnvironmentConfig, count: usize) -> Result<VecEnvironment, String> {
+        let environments: Vec<Environment> = (0..count)
+            .map(|index| {
+                Environment::new(EnvironmentConfig {
+                    seed: config.seed.wrapping_add(index as u64),
+                    ..config.clone()
+                })
+            })
+            .collect::<Result<Vec<Environment>, String>>()?;
+        Ok(VecEnvironment { environments })
+    }
+
+    //number of environments
+    pub fn len(&self) -> usize {
+        self.environments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.environments.is_empty()
+    }
+
+    //the environments, for reading their machines
+    pub fn environments(&self) -> &[Environment] {
+        &self.environments
+    }
+
+    //start a new episode in every environment
+    pub fn reset(&mut self) -> Vec<Observation> {
+        self.environments
+            .iter_mut()
+            .map(Environment::reset)
+            .collect()
+    }
+
+    //step every environment with its own keys, in parallel when there is enough work
+    pub fn step(&mut self, keys: &[u16]) -> Vec<(Observation, f64, bool)> {
+        assert_eq!(
+            keys.len(),
+            self.environments.len(),
+            "one key mask is needed for each environment"
+        );
+        //without threads to spare, such as in webassembly, or with little to run, they are
+        //stepped in turn
+        let threads: usize = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let instructions: u64 = self
+            .environments
+            .iter()
+            .map(|environment| {
+                environment.config.frame_skip.max(1) as u64
+                    * environment.config.instructions_per_frame.max(1) as u64
+            })
+            .sum();
+        if threads == 1 || instructions < PARALLEL_INSTRUCTIONS {
+            return step_all(&mut self.environments, keys);
+        }
+        let chunk: usize = self.environments.len().div_ceil(threads).max(1);
+        thread::scope(|scope| {
+            let handles: Vec<thread::ScopedJoinHandle<Vec<(Observation, f64, bool)>>> = self
+                .environments
+                .chunks_mut(chunk)
+                .zip(keys.chunks(chunk))
+                .map(|(environments, keys)| scope.spawn(move || step_all(environments, keys)))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+                })
+                .collect()
+        })
+    }
+}
+
+//step environments with their keys, resetting any that finish
+//an environment that panics ends its episode instead of taking the others down with it
+fn step_all(environments: &mut [Environment], keys: &[u16]) -> Vec<(Observation, f64, bool)> {
+    environments
+        .iter_mut()
+        .zip(keys)
+        .map(|(environment, keys)| {
+            match panic::catch_unwind(AssertUnwindSafe(|| environment.step(*keys))) {
+                Ok((observation, reward, false)) => (observation, reward, false),
+                Ok((_, reward, true)) => (environment.reset(), reward, true),
+                Err(_) => (environment.reset(), 0.0, true),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COUNTER: usize = 0x300;
+
+    //a rom that adds a number to a byte at 0x300 once a frame, with 4 instructions a frame
+    fn counter(add: u8) -> EnvironmentConfig {
+        EnvironmentConfig {
+            instructions_per_frame: 4,
+            ..EnvironmentConfig::new(vec![0x70, add, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00])
+        }
+    }
+
+    //a rom that stores a random byte at 0x300 once a frame
+    fn random() -> EnvironmentConfig {
+        EnvironmentConfig {
+            instructions_per_frame: 4,
+            ..EnvironmentConfig::new(vec![0xC0, 0xFF, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00])
+        }
+    }
+
+    //the random bytes an environment stores over a number of steps
+    fn random_bytes(environment: &mut Environment, steps: usize) -> Vec<u8> {
+        (0..steps)
+            .map(|_| {
+                environment.step(0);
+                environment.chip8().memory[COUNTER]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_memory_values() {
+        let memory: [u8; 4] = [0x01, 0x02, 0x03, 0x04];
+        assert_eq!(MemoryValue::Byte(5).read(&memory), 0x02);
+        assert_eq!(MemoryValue::Word(1).read(&memory), 0x0203);
+        assert_eq!(MemoryValue::Word(3).read(&memory), 0x0401);
+        let digits: MemoryValue = MemoryValue::Digits {
+            address: 0,
+            count: 3,
+        };
+        assert_eq!(digits.read(&memory), 123);
+    }
+
+    #[test]
+    fn rejects_a_rom_too_big_for_memory() {
+        assert!(Environment::new(EnvironmentConfig::new(vec![0; 0x1000])).is_err());
+    }
+
+    #[test]
+    fn reset_repeats_an_episode_with_the_same_seed() {
+        let mut environment: Environment = Environment::new(random()).unwrap();
+        let first: Vec<u8> = random_bytes(&mut environment, 16);
+        environment.reset();
+        assert_eq!(random_bytes(&mut environment, 16), first);
+
+        let mut other: Environment = Environment::new(random()).unwrap();
+        assert_eq!(random_bytes(&mut other, 16), first);
+
+        environment.set_seed(1);
+        environment.reset();
+        assert_ne!(random_bytes(&mut environment, 16), first);
+    }
+
+    #[test]
+    fn rewards_the_change_over_a_step() {
+        let mut environment: Environment = Environment::new(EnvironmentConfig {
+            frame_skip: 3,
+            rewards: vec![Reward {
+                value: MemoryValue::Byte(COUNTER),
+                weight: 0.5,
+            }],
+            ..counter(2)
+        })
+        .unwrap();
+        for frame in [3, 6, 9] {
+            let (observation, reward, done): (Observation, f64, bool) = environment.step(0);
+            assert_eq!(observation.frame, frame);
+            assert_eq!(reward, 3.0);
+            assert!(!done);
+        }
+    }
+
+    #[test]
+    fn holds_the_keys_in_the_mask() {
+        let mut environment: Environment = Environment::new(counter(1)).unwrap();
+        environment.step(0b1000_0000_0000_0101);
+        assert_eq!(
+            environment.chip8().input,
+            [1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn ends_when_a_value_equals_a_number() {
+        let mut environment: Environment = Environment::new(EnvironmentConfig {
+            done: vec![Done::Equals(MemoryValue::Byte(COUNTER), 3)],
+            ..counter(1)
+        })
+        .unwrap();
+        assert!(!environment.step(0).2);
+        assert!(!environment.step(0).2);
+        assert!(environment.step(0).2);
+
+        //stepping a finished episode does nothing until it is reset
+        let (observation, reward, done): (Observation, f64, bool) = environment.step(0);
+        assert_eq!((observation.frame, reward, done), (3, 0.0, true));
+        assert_eq!(environment.reset().frame, 0);
+        assert!(!environment.step(0).2);
+    }
+
+    #[test]
+    fn ends_when_a_value_drops_to_a_number() {
+        //the counter goes down from 255 by one a frame
+        let mut environment: Environment = Environment::new(EnvironmentConfig {
+            frame_skip: 100,
+            done: vec![Done::AtMost(MemoryValue::Byte(COUNTER), 250)],
+            ..counter(0xFF)
+        })
+        .unwrap();
+        let (observation, _, done): (Observation, f64, bool) = environment.step(0);
+        assert!(done);
+        assert_eq!(observation.frame, 6);
+    }
+
+    #[test]
+    fn ends_after_the_most_frames() {
+        let mut environment: Environment = Environment::new(EnvironmentConfig {
+            frame_skip: 2,
+            max_frames: Some(3),
+            ..counter(1)
+        })
+        .unwrap();
+        assert!(!environment.step(0).2);
+        let (observation, _, done): (Observation, f64, bool) = environment.step(0);
+        assert!(done);
+        assert_eq!(observation.frame, 3);
+    }
+
+    #[test]
+    fn seeds_each_environment_differently() {
+        let mut environments: VecEnvironment = VecEnvironment::new(random(), 3).unwrap();
+        environments.step(&[0; 3]);
+        let bytes: Vec<u8> = environments
+            .environments()
+            .iter()
+            .map(|environment| environment.chip8().memory[COUNTER])
+            .collect();
+        let mut single: Environment = Environment::new(EnvironmentConfig {
+            seed: 2,
+            ..random()
+        })
+        .unwrap();
+        assert_eq!(bytes[2], random_bytes(&mut single, 1)[0]);
+        assert!(bytes[0] != bytes[1] || bytes[1] != bytes[2]);
+    }
+
+    #[test]
+    fn resets_environments_that_finish() {
+        let mut environments: VecEnvironment = VecEnvironment::new(
+            EnvironmentConfig {
+                done: vec![Done::Equals(MemoryValue::Byte(COUNTER), 2)],
+                rewards: vec![Reward {
+                    value: MemoryValue::Byte(COUNTER),
+                    weight: 1.0,
+                }],
+                ..counter(1)
+            },
+            2,
+        )
+        .unwrap();
+        assert_eq!(environments.len(), 2);
+        for (observation, reward, done) in environments.step(&[0, 0]) {
+            assert_eq!((observation.frame, reward, done), (1, 1.0, false));
+        }
+        //the reward is for the step that finished, the observation from the new episode
+        for (observation, reward, done) in environments.step(&[0, 0]) {
+            assert_eq!((observation.frame, reward, done), (0, 1.0, true));
+        }
+        for (observation, _, done) in environments.step(&[0, 0]) {
+            assert_eq!((observation.frame, done), (1, false));
+        }
+    }
+
+    #[test]
+    fn steps_in_parallel_like_in_turn() {
+        //enough instructions a step to be split between threads
+        let config: EnvironmentConfig = EnvironmentConfig {
+            frame_skip: 50_000,
+            ..random()
+        };
+        let mut environments: VecEnvironment = VecEnvironment::new(config.clone(), 8).unwrap();
+        environments.step(&[0; 8]);
+        for (index, environment) in environments.environments().iter().enumerate() {
+            let mut single: Environment = Environment::new(EnvironmentConfig {
+                seed: index as u64,
+                ..config.clone()
+            })
+            .unwrap();
+            single.step(0);
+            assert_eq!(single.chip8().memory, environment.chip8().memory);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "one key mask is needed for each environment")]
+    fn needs_keys_for_every_environment() {
+        VecEnvironment::new(counter(1), 2).unwrap().step(&[0]);
+    }
+}
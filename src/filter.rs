@@ -0,0 +1,276 @@
+//use declarations
+use crate::palette::{blend_color, Palette};
+use sdl2::pixels::Color;
+use std::collections::VecDeque;
+
+//anti flicker filter modes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterMode {
+    //redraw after every instruction like the original program
+    Off,
+    //only show pixels lit at the end of each frame
+    FrameEnd,
+    //or together the last N frames
+    Blend(usize),
+    //lit pixels fade out by this factor each frame like a phosphor screen
+    Decay(f32),
+}
+
+impl FilterMode {
+    //parse a filter written as off, frame-end, blend:N, or decay:F
+    pub fn parse(text: &str) -> Result<FilterMode, String> {
+        let (name, value): (&str, Option<&str>) = match text.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (text, None),
+        };
+
+        match (name, value) {
+            ("off", None) => Ok(FilterMode::Off),
+            ("frame-end", None) => Ok(FilterMode::FrameEnd),
+            ("blend", None) => Ok(FilterMode::Blend(2)),
+            ("blend", Some(value)) => match value.parse::<usize>() {
+                Ok(frames) if (1..=16).contains(&frames) => Ok(FilterMode::Blend(frames)),
+                _ => Err(format!(
+                    "invalid blend frame count \"{}\", expected 1 to 16",
+                    value
+                )),
+            },
+            ("decay", None) => Ok(FilterMode::Decay(0.5)),
+            ("decay", Some(value)) => match value.parse::<f32>() {
+                Ok(factor) if (0.0..1.0).contains(&factor) => Ok(FilterMode::Decay(factor)),
+                _ => Err(format!(
+                    "invalid decay factor \"{}\", expected 0 to 1",
+                    value
+                )),
+            },
+            _ => Err(format!(
+                "unknown filter \"{}\", expected off, frame-end, blend:N or decay:F",
+                text
+            )),
+        }
+    }
+
+    //get the next filter for the filter hotkey
+    pub fn next(self) -> FilterMode {
+        match self {
+            FilterMode::Off => FilterMode::FrameEnd,
+            FilterMode::FrameEnd => FilterMode::Blend(2),
+            FilterMode::Blend(_) => FilterMode::Decay(0.5),
+            FilterMode::Decay(_) => FilterMode::Off,
+        }
+    }
+
+    //get the name of the filter in the same form parse reads
+    pub fn name(self) -> String {
+        match self {
+            FilterMode::Off => "off".to_string(),
+            FilterMode::FrameEnd => "frame-end".to_string(),
+            FilterMode::Blend(frames) => format!("blend:{}", frames),
+            FilterMode::Decay(factor) => format!("decay:{}", factor),
+        }
+    }
+}
+
+//display filter struct and methods
+pub struct DisplayFilter {
+    mode: FilterMode,
+    //displays captured at the end of recent frames, newest last
+    history: VecDeque<Vec<u8>>,
+    //brightness and last lit value of each pixel for the decay filter
+    intensity: Vec<f32>,
+    lit_value: Vec<u8>,
+}
+
+impl DisplayFilter {
+    pub fn new(mode: FilterMode, size: usize) -> DisplayFilter {
+        DisplayFilter {
+            mode,
+            history: VecDeque::new(),
+            intensity: vec![0.0; size],
+            lit_value: vec![0; size],
+        }
+    }
+
+    pub fn mode(&self) -> FilterMode {
+        self.mode
+    }
+
+    //change the filter mode, forgetting previous frames
+    pub fn set_mode(&mut self, mode: FilterMode) {
+        self.mode = mode;
+        self.history.clear();
+        self.intensity.iter_mut().for_each(|value| *value = 0.0);
+    }
+
+    //check if the screen should be redrawn after every instruction instead of every frame
+    pub fn redraws_every_instruction(&self) -> bool {
+        self.mode == FilterMode::Off
+    }
+
+    //capture the display at the end of a frame
+    pub fn push_frame(&mut self, display: &[u8]) {
+        match self.mode {
+            FilterMode::Off => {}
+            FilterMode::FrameEnd => {
+                self.history.clear();
+                self.history.push_back(display.to_vec());
+            }
+            FilterMode::Blend(frames) => {
+                self.history.push_back(display.to_vec());
+                while self.history.len() > frames {
+                    self.history.pop_front();
+                }
+            }
+            FilterMode::Decay(factor) => {
+                //relight lit pixels and fade the rest
+                for (i, pixel) in display.iter().enumerate() {
+                    if *pixel > 0 {
+                        self.intensity[i] = 1.0;
+                        self.lit_value[i] = *pixel;
+                    } else {
+                        self.intensity[i] *= factor;
+                    }
+                }
+            }
+        }
+    }
+
+    //get the color of each pixel to show
+    pub fn frame(&self, display: &[u8], palette: &Palette) -> Vec<Color> {
+        match self.mode {
+            FilterMode::Decay(_) => {
+                let background: Color = palette.color(0);
+                (0..display.len())
+                    .map(|i| {
+                        if self.intensity[i] < 1.0 / 255.0 {
+                            background
+                        } else {
+                            blend_color(
+                                background,
+                                palette.color(self.lit_value[i]),
+                                self.intensity[i],
+                            )
+                        }
+                    })
+                    .collect()
+            }
+            FilterMode::FrameEnd | FilterMode::Blend(_) if !self.history.is_empty() => {
+                //or together each captured frame
+                let mut pixels: Vec<u8> = vec![0; display.len()];
+                for frame in &self.history {
+                    for (pixel, value) in pixels.iter_mut().zip(frame) {
+                        *pixel |= *value;
+                    }
+                }
+                pixels.iter().map(|pixel| palette.color(*pixel)).collect()
+            }
+            _ => display.iter().map(|pixel| palette.color(*pixel)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_parse_back_to_the_same_mode() {
+        for mode in [
+            FilterMode::Off,
+            FilterMode::FrameEnd,
+            FilterMode::Blend(1),
+            FilterMode::Blend(16),
+            FilterMode::Decay(0.0),
+            FilterMode::Decay(0.75),
+        ] {
+            assert_eq!(FilterMode::parse(&mode.name()), Ok(mode));
+        }
+        assert_eq!(FilterMode::parse("blend"), Ok(FilterMode::Blend(2)));
+        assert_eq!(FilterMode::parse("decay"), Ok(FilterMode::Decay(0.5)));
+    }
+
+    #[test]
+    fn rejects_bad_filters() {
+        for text in [
+            "",
+            "on",
+            "off:1",
+            "blend:0",
+            "blend:17",
+            "blend:x",
+            "decay:1",
+            "decay:-0.5",
+        ] {
+            assert!(FilterMode::parse(text).is_err(), "{} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn the_hotkey_cycles_through_every_mode() {
+        let mut mode: FilterMode = FilterMode::Off;
+        let mut names: Vec<String> = Vec::new();
+        for _ in 0..4 {
+            mode = mode.next();
+            names.push(mode.name());
+        }
+        assert_eq!(names, ["frame-end", "blend:2", "decay:0.5", "off"]);
+    }
+
+    #[test]
+    fn blend_ors_the_last_frames_together() {
+        let palette: Palette = Palette::preset("classic").unwrap();
+        let mut filter: DisplayFilter = DisplayFilter::new(FilterMode::Blend(2), 3);
+        filter.push_frame(&[1, 0, 0]);
+        filter.push_frame(&[0, 1, 0]);
+        assert_eq!(
+            filter.frame(&[0, 0, 1], &palette),
+            [palette.color(1), palette.color(1), palette.color(0)]
+        );
+        filter.push_frame(&[0, 0, 1]);
+        assert_eq!(
+            filter.frame(&[0, 0, 1], &palette),
+            [palette.color(0), palette.color(1), palette.color(1)]
+        );
+    }
+
+    #[test]
+    fn frame_end_shows_the_last_frame_only() {
+        let palette: Palette = Palette::preset("classic").unwrap();
+        let mut filter: DisplayFilter = DisplayFilter::new(FilterMode::FrameEnd, 2);
+        //until a frame ends the display is shown as it is
+        assert_eq!(
+            filter.frame(&[1, 0], &palette),
+            [palette.color(1), palette.color(0)]
+        );
+        filter.push_frame(&[0, 1]);
+        assert_eq!(
+            filter.frame(&[1, 0], &palette),
+            [palette.color(0), palette.color(1)]
+        );
+        assert!(!filter.redraws_every_instruction());
+    }
+
+    #[test]
+    fn decay_fades_pixels_out_over_frames() {
+        let palette: Palette = Palette::preset("classic").unwrap();
+        let mut filter: DisplayFilter = DisplayFilter::new(FilterMode::Decay(0.5), 1);
+        filter.push_frame(&[1]);
+        assert_eq!(filter.frame(&[1], &palette), [Color::RGB(0xFF, 0xFF, 0xFF)]);
+
+        //each frame off halves the brightness until it is too dim to see
+        let mut levels: Vec<u8> = Vec::new();
+        for _ in 0..9 {
+            filter.push_frame(&[0]);
+            levels.push(filter.frame(&[0], &palette)[0].r);
+        }
+        assert_eq!(levels, [128, 64, 32, 16, 8, 4, 2, 0, 0]);
+
+        //lighting the pixel again brings it back to full brightness
+        filter.push_frame(&[1]);
+        assert_eq!(filter.frame(&[1], &palette)[0].r, 0xFF);
+
+        //changing the mode forgets the brightness
+        filter.set_mode(FilterMode::Decay(0.5));
+        assert_eq!(filter.frame(&[0], &palette)[0], palette.color(0));
+    }
+}
@@ -0,0 +1,377 @@
+//use declarations
+use sdl2::keyboard::{Keycode, Mod};
+use std::collections::BTreeMap;
+
+//default keyboard keys for chip 8 keys 0 to F, laid out on the left of a qwerty keyboard as
+//1 2 3 4 / Q W E R / A S D F / Z X C V
+pub const DEFAULT_KEYPAD: [&str; 16] = [
+    "X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V",
+];
+
+//things the hotkeys do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Quit,
+    Browse,
+    Settings,
+    Fullscreen,
+    IntegerScale,
+    Filter,
+    Crt,
+    Upscaler,
+    Screenshot,
+    RawScreenshot,
+    Record,
+    RecordAudio,
+    Pause,
+    //start the rom again from the beginning
+    Reset,
+    //run one frame while paused
+    FrameAdvance,
+    //run faster while held
+    FastForward,
+    //cycle through half and quarter speed
+    SlowMotion,
+    Stats,
+    MemoryViewer,
+    SpriteViewer,
+    Cheats,
+    Mute,
+    Palette,
+}
+
+impl Action {
+    pub const ALL: [Action; 23] = [
+        Action::Quit,
+        Action::Browse,
+        Action::Settings,
+        Action::Fullscreen,
+        Action::IntegerScale,
+        Action::Filter,
+        Action::Crt,
+        Action::Upscaler,
+        Action::Screenshot,
+        Action::RawScreenshot,
+        Action::Record,
+        Action::RecordAudio,
+        Action::Pause,
+        Action::Reset,
+        Action::FrameAdvance,
+        Action::FastForward,
+        Action::SlowMotion,
+        Action::Stats,
+        Action::MemoryViewer,
+        Action::SpriteViewer,
+        Action::Cheats,
+        Action::Mute,
+        Action::Palette,
+    ];
+
+    //name used in the [hotkeys] section of the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Browse => "browse",
+            Action::Settings => "settings",
+            Action::Fullscreen => "fullscreen",
+            Action::IntegerScale => "integer_scale",
+            Action::Filter => "filter",
+            Action::Crt => "crt",
+            Action::Upscaler => "upscaler",
+            Action::Screenshot => "screenshot",
+            Action::RawScreenshot => "raw_screenshot",
+            Action::Record => "record",
+            Action::RecordAudio => "record_audio",
+            Action::Pause => "pause",
+            Action::Reset => "reset",
+            Action::FrameAdvance => "frame_advance",
+            Action::FastForward => "fast_forward",
+            Action::SlowMotion => "slow_motion",
+            Action::Stats => "stats",
+            Action::MemoryViewer => "memory_viewer",
+            Action::SpriteViewer => "sprite_viewer",
+            Action::Cheats => "cheats",
+            Action::Mute => "mute",
+            Action::Palette => "palette",
+        }
+    }
+
+    //key used when the config file does not set one
+    fn default_key(self) -> &'static str {
+        match self {
+            Action::Quit => "Escape",
+            Action::Browse => "F2",
+            Action::Settings => "F1",
+            Action::Fullscreen => "Alt+Return",
+            Action::IntegerScale => "I",
+            Action::Filter => "O",
+            Action::Crt => "T",
+            Action::Upscaler => "U",
+            Action::Screenshot => "F12",
+            Action::RawScreenshot => "Shift+F12",
+            Action::Record => "F10",
+            Action::RecordAudio => "F9",
+            Action::Pause => "Pause",
+            Action::Reset => "F5",
+            Action::FrameAdvance => "N",
+            Action::FastForward => "Tab",
+            Action::SlowMotion => "L",
+            Action::Stats => "F3",
+            Action::MemoryViewer => "F4",
+            Action::SpriteViewer => "F6",
+            Action::Cheats => "F7",
+            Action::Mute => "M",
+            Action::Palette => "P",
+        }
+    }
+}
+
+//a key and the modifiers that have to be held with it
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Binding {
+    keycode: Keycode,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl Binding {
+    //parse a key written with sdl key names, with Ctrl+, Alt+ and Shift+ in front for modifiers
+    fn parse(text: &str) -> Result<Binding, String> {
+        let mut parts: Vec<&str> = text.split('+').collect();
+        let key: &str = parts.pop().unwrap_or_default();
+        let mut binding: Binding = Binding {
+            keycode: parse_key(key)?,
+            ctrl: false,
+            alt: false,
+            shift: false,
+        };
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" => binding.ctrl = true,
+                "alt" => binding.alt = true,
+                "shift" => binding.shift = true,
+                _ => {
+                    return Err(format!(
+                        "unknown modifier \"{}\" in \"{}\", expected Ctrl, Alt or Shift",
+                        modifier, text
+                    ))
+                }
+            }
+        }
+        Ok(binding)
+    }
+
+    //check if a key press is this key with exactly these modifiers
+    fn matches(&self, keycode: Keycode, keymod: Mod) -> bool {
+        keycode == self.keycode
+            && self.ctrl == keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+            && self.alt == keymod.intersects(Mod::LALTMOD | Mod::RALTMOD)
+            && self.shift == keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)
+    }
+}
+
+//keymap struct and methods
+//holds the keyboard keys for the chip 8 keypad and the hotkeys
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    keypad: [Keycode; 16],
+    hotkeys: Vec<(Binding, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::new(None, &BTreeMap::new()).expect("the default keys are valid")
+    }
+}
+
+impl Keymap {
+    //build a keymap from key names in the config file
+    //the keypad lists the keys for chip 8 keys 0 to F, hotkeys are keyed by action name,
+    //and anything not set keeps its default key
+    pub fn new(
+        keypad: Option<&[String]>,
+        hotkeys: &BTreeMap<String, String>,
+    ) -> Result<Keymap, String> {
+        let mut keymap: Keymap = Keymap {
+            keypad: [Keycode::X; 16],
+            hotkeys: Vec::new(),
+        };
+        match keypad {
+            Some(names) if names.len() != 16 => {
+                return Err(format!(
+                    "the keypad needs 16 keys for chip 8 keys 0 to F, found {}",
+                    names.len()
+                ))
+            }
+            Some(names) => {
+                for (key, name) in names.iter().enumerate() {
+                    keymap.keypad[key] = parse_key(name)?;
+                }
+            }
+            None => {
+                for (key, name) in DEFAULT_KEYPAD.iter().enumerate() {
+                    keymap.keypad[key] = parse_key(name)?;
+                }
+            }
+        }
+
+        if let Some(name) = hotkeys
+            .keys()
+            .find(|name| !Action::ALL.iter().any(|action| action.name() == *name))
+        {
+            return Err(format!("unknown hotkey \"{}\"", name));
+        }
+        for action in Action::ALL {
+            let text: &str = hotkeys
+                .get(action.name())
+                .map(String::as_str)
+                .unwrap_or(action.default_key());
+            keymap.hotkeys.push((Binding::parse(text)?, action));
+        }
+        Ok(keymap)
+    }
+
+    //get the chip 8 key for a keyboard key
+    pub fn key(&self, keycode: Keycode) -> Option<usize> {
+        self.keypad.iter().position(|key| *key == keycode)
+    }
+
+    //check if a released key is the key of a hotkey that is held down
+    //modifiers are not checked as they may be let go first
+    pub fn releases(&self, action: Action, keycode: Keycode) -> bool {
+        self.hotkeys
+            .iter()
+            .any(|(binding, bound)| *bound == action && binding.keycode == keycode)
+    }
+
+    //get the hotkey action for a key press
+    pub fn action(&self, keycode: Keycode, keymod: Mod) -> Option<Action> {
+        self.hotkeys
+            .iter()
+            .find(|(binding, _)| binding.matches(keycode, keymod))
+            .map(|(_, action)| *action)
+    }
+}
+
+//get a key from its sdl name, such as A, 1, F12, Return or Pause
+fn parse_key(name: &str) -> Result<Keycode, String> {
+    Keycode::from_name(name).ok_or_else(|| format!("unknown key \"{}\"", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //build a keymap with some hotkeys changed
+    fn keymap(hotkeys: &[(&str, &str)]) -> Result<Keymap, String> {
+        let hotkeys: BTreeMap<String, String> = hotkeys
+            .iter()
+            .map(|(name, key)| (name.to_string(), key.to_string()))
+            .collect();
+        Keymap::new(None, &hotkeys)
+    }
+
+    #[test]
+    fn reads_keys_with_modifiers() {
+        assert_eq!(
+            Binding::parse("Ctrl+Shift+F12"),
+            Ok(Binding {
+                keycode: Keycode::F12,
+                ctrl: true,
+                alt: false,
+                shift: true,
+            })
+        );
+        assert_eq!(Binding::parse("alt+Return").map(|b| b.alt), Ok(true));
+        assert_eq!(
+            Binding::parse("Super+A"),
+            Err(
+                "unknown modifier \"Super\" in \"Super+A\", expected Ctrl, Alt or Shift"
+                    .to_string()
+            )
+        );
+        assert_eq!(Binding::parse("Ctrl+"), Err("unknown key \"\"".to_string()));
+        assert_eq!(
+            Binding::parse("Banana"),
+            Err("unknown key \"Banana\"".to_string())
+        );
+    }
+
+    #[test]
+    fn hotkeys_need_exactly_their_modifiers() {
+        let keymap: Keymap = Keymap::default();
+        assert_eq!(
+            keymap.action(Keycode::F12, Mod::NOMOD),
+            Some(Action::Screenshot)
+        );
+        assert_eq!(
+            keymap.action(Keycode::F12, Mod::RSHIFTMOD),
+            Some(Action::RawScreenshot)
+        );
+        assert_eq!(keymap.action(Keycode::F12, Mod::LCTRLMOD), None);
+        assert_eq!(
+            keymap.action(Keycode::Return, Mod::LALTMOD),
+            Some(Action::Fullscreen)
+        );
+        assert_eq!(keymap.action(Keycode::Return, Mod::NOMOD), None);
+        //lock keys are not modifiers that have to match
+        assert_eq!(keymap.action(Keycode::M, Mod::NUMMOD), Some(Action::Mute));
+
+        //a hotkey is let go when its key is, whatever the modifiers
+        assert!(keymap.releases(Action::Fullscreen, Keycode::Return));
+        assert!(!keymap.releases(Action::Fullscreen, Keycode::F12));
+    }
+
+    #[test]
+    fn config_hotkeys_replace_the_defaults() {
+        let keymap: Keymap = keymap(&[("pause", "Space"), ("quit", "Ctrl+Q")]).unwrap();
+        assert_eq!(
+            keymap.action(Keycode::Space, Mod::NOMOD),
+            Some(Action::Pause)
+        );
+        assert_eq!(keymap.action(Keycode::Pause, Mod::NOMOD), None);
+        assert_eq!(keymap.action(Keycode::Q, Mod::LCTRLMOD), Some(Action::Quit));
+        assert_eq!(keymap.action(Keycode::F5, Mod::NOMOD), Some(Action::Reset));
+    }
+
+    #[test]
+    fn rejects_unknown_hotkeys_and_keys() {
+        assert_eq!(
+            keymap(&[("jump", "Space")]).unwrap_err(),
+            "unknown hotkey \"jump\""
+        );
+        assert_eq!(
+            keymap(&[("pause", "Hyper+P")]).unwrap_err(),
+            "unknown modifier \"Hyper\" in \"Hyper+P\", expected Ctrl, Alt or Shift"
+        );
+        assert_eq!(
+            keymap(&[("pause", "Nope")]).unwrap_err(),
+            "unknown key \"Nope\""
+        );
+    }
+
+    #[test]
+    fn reads_a_keypad_of_16_keys() {
+        assert_eq!(Keymap::default().key(Keycode::X), Some(0));
+        assert_eq!(Keymap::default().key(Keycode::V), Some(0xF));
+        assert_eq!(Keymap::default().key(Keycode::P), None);
+
+        let mut keys: Vec<String> = DEFAULT_KEYPAD.iter().map(|key| key.to_string()).collect();
+        keys[0] = "0".to_string();
+        let keymap: Keymap = Keymap::new(Some(&keys), &BTreeMap::new()).unwrap();
+        assert_eq!(keymap.key(Keycode::Num0), Some(0));
+        assert_eq!(keymap.key(Keycode::X), None);
+
+        keys.pop();
+        assert_eq!(
+            Keymap::new(Some(&keys), &BTreeMap::new()).unwrap_err(),
+            "the keypad needs 16 keys for chip 8 keys 0 to F, found 15"
+        );
+        keys.push("Nope".to_string());
+        assert_eq!(
+            Keymap::new(Some(&keys), &BTreeMap::new()).unwrap_err(),
+            "unknown key \"Nope\""
+        );
+    }
+}
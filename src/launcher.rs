@@ -0,0 +1,366 @@
+//use declarations
+use crate::config::{self, Config};
+use crate::database::{Database, RomInfo};
+use crate::osd;
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::EventPump;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+//file extensions listed as roms
+const ROM_EXTENSIONS: [&str; 3] = ["ch8", "sc8", "xo8"];
+
+//lines kept at the bottom of the screen for the selected rom's title and description
+const INFO_LINES: usize = 5;
+
+//an entry in the browser list
+enum Entry {
+    Recent(PathBuf),
+    Parent(PathBuf),
+    Directory(PathBuf),
+    Rom(PathBuf),
+}
+
+impl Entry {
+    //text shown in the list
+    fn label(&self) -> String {
+        match self {
+            Entry::Recent(path) => format!("* {}", file_name(path)),
+            Entry::Parent(_) => "../".to_string(),
+            Entry::Directory(path) => format!("{}/", file_name(path)),
+            Entry::Rom(path) => file_name(path),
+        }
+    }
+}
+
+//show the rom browser until a rom is picked or dropped onto the window
+//returns None if the window was closed
+pub fn run(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut EventPump,
+    config: &Config,
+    database: &Database,
+    message: Option<String>,
+) -> Result<Option<String>, String> {
+    canvas
+        .window_mut()
+        .set_title("Chip 8 Emulator")
+        .map_err(|e| e.to_string())?;
+
+    //start in the folder of the last rom run, or the current folder
+    let mut directory: PathBuf = config
+        .recent
+        .first()
+        .and_then(|recent| Path::new(recent).parent().map(Path::to_path_buf))
+        .filter(|directory| directory.is_dir())
+        .unwrap_or_else(|| PathBuf::from("."));
+    let mut entries: Vec<Entry> = list(&directory, &config.recent);
+    let mut selected: usize = 0;
+    let mut info: Vec<String> = describe(entries.get(selected), database);
+
+    loop {
+        //input handling, with the keyboard or a game controller
+        let page: usize = visible_lines(canvas)?;
+        let mut open: bool = false;
+        let mut back: bool = false;
+        let previous: usize = selected;
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return Ok(None),
+                Event::DropFile { filename, .. } => return Ok(Some(filename)),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Up),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::DPadUp,
+                    ..
+                } => selected = selected.saturating_sub(1),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Down),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::DPadDown,
+                    ..
+                } => selected += 1,
+                Event::KeyDown {
+                    keycode: Some(Keycode::PageUp),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::LeftShoulder,
+                    ..
+                } => selected = selected.saturating_sub(page),
+                Event::KeyDown {
+                    keycode: Some(Keycode::PageDown),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::RightShoulder,
+                    ..
+                } => selected += page,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::A, ..
+                } => open = true,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::B, ..
+                } => back = true,
+                _ => {}
+            }
+        }
+        selected = selected.min(entries.len().saturating_sub(1));
+
+        //run the selected rom or move to another folder
+        let mut next_directory: Option<PathBuf> = None;
+        if open {
+            match entries.get(selected) {
+                Some(Entry::Recent(path)) | Some(Entry::Rom(path)) => {
+                    return Ok(Some(path.to_string_lossy().to_string()));
+                }
+                Some(Entry::Parent(path)) | Some(Entry::Directory(path)) => {
+                    next_directory = Some(path.clone());
+                }
+                None => {}
+            }
+        } else if back {
+            next_directory = parent(&directory);
+        }
+        if let Some(next) = next_directory {
+            directory = next;
+            entries = list(&directory, &config.recent);
+            selected = 0;
+        }
+        if selected != previous || open || back {
+            info = describe(entries.get(selected), database);
+        }
+
+        draw(
+            canvas,
+            &directory,
+            &entries,
+            selected,
+            &info,
+            message.as_deref(),
+        )?;
+        ::std::thread::sleep(Duration::from_millis(16));
+    }
+}
+
+//list the recent roms, then the folders and roms in a folder
+fn list(directory: &Path, recent: &[String]) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = recent
+        .iter()
+        .map(PathBuf::from)
+        .filter(|path| path.is_file())
+        .map(Entry::Recent)
+        .collect();
+    if let Some(path) = parent(directory) {
+        entries.push(Entry::Parent(path));
+    }
+
+    //folders first, then roms, both sorted by name
+    let mut directories: Vec<PathBuf> = Vec::new();
+    let mut roms: Vec<PathBuf> = Vec::new();
+    if let Ok(read) = fs::read_dir(directory) {
+        for path in read.flatten().map(|item| item.path()) {
+            if path.is_dir() {
+                directories.push(path);
+            } else if is_rom(&path) {
+                roms.push(path);
+            }
+        }
+    }
+    directories.sort();
+    roms.sort();
+    entries.extend(directories.into_iter().map(Entry::Directory));
+    entries.extend(roms.into_iter().map(Entry::Rom));
+    entries
+}
+
+//get the title and description lines shown for an entry
+//they come from the rom database, or from the file name and a .txt file next to the rom,
+//as rom packs often include one
+fn describe(entry: Option<&Entry>, database: &Database) -> Vec<String> {
+    let path: &Path = match entry {
+        Some(Entry::Recent(path)) | Some(Entry::Rom(path)) => path,
+        _ => return Vec::new(),
+    };
+    let info: Option<RomInfo> = fs::read(path)
+        .ok()
+        .and_then(|contents| database.lookup(&config::rom_hash(&contents)));
+    if let Some(info) = info {
+        let mut byline: Vec<String> = Vec::new();
+        if !info.authors.is_empty() {
+            byline.push(format!("by {}", info.authors.join(", ")));
+        }
+        if let Some(release) = info.release {
+            byline.push(release);
+        }
+        let mut lines: Vec<String> = vec![info.title];
+        if !byline.is_empty() {
+            lines.push(byline.join(", "));
+        }
+        if let Some(description) = info.description {
+            lines.push(
+                description
+                    .split_whitespace()
+                    .collect::<Vec<&str>>()
+                    .join(" "),
+            );
+        }
+        return lines;
+    }
+
+    let title: String = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut lines: Vec<String> = vec![title];
+    if let Ok(text) = fs::read_to_string(path.with_extension("txt")) {
+        lines.push(text.split_whitespace().collect::<Vec<&str>>().join(" "));
+    }
+    lines
+}
+
+//check if a file has a rom extension
+fn is_rom(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| ROM_EXTENSIONS.contains(&extension.as_str()))
+}
+
+//get the parent of a folder, working from the full path so relative folders can go up too
+fn parent(directory: &Path) -> Option<PathBuf> {
+    fs::canonicalize(directory)
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+}
+
+//get the last part of a path for the list
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+//get the font size and how many characters and lines fit in the window
+fn text_layout(canvas: &Canvas<Window>) -> Result<(i32, usize, usize), String> {
+    let (output_width, output_height): (u32, u32) = canvas.output_size()?;
+    let size: i32 = (output_height as i32 / 160).max(1);
+    let columns: usize = (output_width as i32 / (4 * size)).max(2) as usize - 1;
+    let lines: usize = (output_height as i32 / (7 * size)) as usize;
+    Ok((size, columns, lines))
+}
+
+//get how many list entries fit between the header and the info lines
+fn visible_lines(canvas: &Canvas<Window>) -> Result<usize, String> {
+    let (_, _, lines): (i32, usize, usize) = text_layout(canvas)?;
+    Ok(lines.saturating_sub(INFO_LINES + 3).max(1))
+}
+
+//get the line the info at the bottom starts on, if it fits under the header and a list entry
+fn info_start(lines: usize) -> Option<usize> {
+    lines.checked_sub(INFO_LINES).filter(|start| *start >= 3)
+}
+
+//draw the browser
+fn draw(
+    canvas: &mut Canvas<Window>,
+    directory: &Path,
+    entries: &[Entry],
+    selected: usize,
+    info: &[String],
+    message: Option<&str>,
+) -> Result<(), String> {
+    let (size, columns, lines): (i32, usize, usize) = text_layout(canvas)?;
+    let line_height: i32 = 7 * size;
+    let visible: usize = visible_lines(canvas)?;
+    let cut = |text: &str| -> String { text.chars().take(columns).collect() };
+
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+
+    //header with the folder, or the last error
+    let directory_name: String = fs::canonicalize(directory)
+        .unwrap_or_else(|_| directory.to_path_buf())
+        .to_string_lossy()
+        .to_string();
+    osd::draw_text(canvas, &cut(&directory_name), size, size, 0)?;
+    if let Some(message) = message {
+        osd::draw_text(canvas, &cut(message), size, size, line_height)?;
+    }
+
+    //the part of the list around the selected entry, with an arrow at the selected one
+    let first: usize = selected.saturating_sub(visible - 1);
+    for (row, (index, entry)) in entries
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(visible)
+        .enumerate()
+    {
+        let marker: &str = if index == selected { "> " } else { "  " };
+        let y: i32 = (row as i32 + 2) * line_height;
+        osd::draw_text(
+            canvas,
+            &cut(&format!("{}{}", marker, entry.label())),
+            size,
+            size,
+            y,
+        )?;
+    }
+    if entries.is_empty() {
+        osd::draw_text(canvas, "NO ROMS HERE", size, size, 2 * line_height)?;
+    }
+
+    //title and wrapped description of the selected rom at the bottom
+    let mut info_lines: Vec<String> = Vec::new();
+    for text in info {
+        let characters: Vec<char> = text.chars().collect();
+        for chunk in characters.chunks(columns) {
+            info_lines.push(chunk.iter().collect());
+        }
+    }
+    if let Some(start) = info_start(lines) {
+        for (row, line) in info_lines.iter().take(INFO_LINES).enumerate() {
+            let y: i32 = (start + row) as i32 * line_height;
+            osd::draw_text(canvas, line, size, size, y)?;
+        }
+    }
+
+    canvas.present();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_is_skipped_when_it_does_not_fit() {
+        for lines in 0..INFO_LINES + 3 {
+            assert_eq!(info_start(lines), None);
+        }
+        assert_eq!(info_start(INFO_LINES + 3), Some(3));
+        assert_eq!(info_start(20), Some(20 - INFO_LINES));
+    }
+}
@@ -0,0 +1,10 @@
+//the emulator core without the sdl frontend, for using it from other programs
+pub mod audio;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod chip8;
+pub mod environment;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
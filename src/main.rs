@@ -1,48 +1,173 @@
+//the sdl frontend is not built for webassembly, where the library is used from javascript instead
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
+//modules
+mod cheats;
+mod cli;
+mod config;
+mod crt;
+mod database;
+mod disasm;
+mod filter;
+mod keymap;
+mod launcher;
+mod memview;
+mod osd;
+mod palette;
+mod recording;
+mod screenshot;
+mod server;
+mod settings;
+mod spriteview;
+mod upscale;
+mod viewport;
+mod wav;
+
 //use declarations
-use rand::Rng;
-use sdl2::audio::AudioSpecDesired;
-use sdl2::event::Event;
+use cheats::{Cheats, RamSearch};
+use chip8_emulator::audio::{self, AudioStream, Beeper, BeeperSettings};
+use chip8_emulator::chip8::{
+    self, Chip8, Quirks, DISPLAY_HEIGHT, DISPLAY_WIDTH, FRAMES_PER_SECOND, INSTRUCTIONS_PER_FRAME,
+    PROGRAM_START,
+};
+use cli::{Command, Failure, Options, Parsed};
+use config::{Config, RomConfig};
+use crt::CrtSettings;
+use database::{Database, RomInfo};
+use filter::{DisplayFilter, FilterMode};
+use keymap::{Action, Keymap};
+use memview::MemoryViewer;
+use osd::Osd;
+use palette::Palette;
+use recording::Recorder;
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::controller::{Button, GameController};
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{FullscreenType, Window, WindowContext};
 use sdl2::AudioSubsystem;
 use sdl2::EventPump;
+use sdl2::GameControllerSubsystem;
+use sdl2::VideoSubsystem;
+use server::Server;
+use settings::Settings;
+use spriteview::SpriteViewer;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::time::Duration;
+use std::ops::Range;
+use std::path::Path;
+use std::process;
+use std::time::{Duration, Instant, SystemTime};
+use upscale::Upscaler;
+use wav::WavWriter;
 
-//sound structs and methods
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
-}
+//sample rate of the generated audio
+const AUDIO_SAMPLE_RATE: u32 = 44100;
 
-impl sdl2::audio::AudioCallback for SquareWave {
-    type Channel = f32;
+//most frames of audio allowed to wait in the queue
+const MAX_QUEUED_FRAMES: usize = 6;
 
-    fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
+//default size of an emulated pixel in window pixels
+const DEFAULT_SCALE: u32 = 20;
+
+//how far behind the emulator can fall before it stops trying to catch up
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+//how often the rom file is checked for changes with --watch
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+//frames run by the test command when --frames is not given
+const DEFAULT_TEST_FRAMES: u64 = 600;
+
+//main method, prints any error and exits with a code saying what went wrong
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if let Err(failure) = run(&args) {
+        eprintln!("{}", failure.message);
+        process::exit(failure.code);
     }
 }
 
-//main method
-fn main() -> Result<(), String> {
+//run the command given on the command line
+fn run(args: &[String]) -> Result<(), Failure> {
+    //get command line options and settings
+    let options: Options = match cli::parse_args(args) {
+        Ok(Parsed::Options(options)) => *options,
+        Ok(Parsed::Help) => {
+            println!("{}", cli::HELP);
+            return Ok(());
+        }
+        Ok(Parsed::Version) => {
+            println!("chip8_emulator {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Err(e) => return Err(Failure::usage(e)),
+    };
+    //a config file that can not be read is left alone, so fixing a typo does not lose everything in it
+    let mut config: Config = Config::load().unwrap_or_else(|e| {
+        eprintln!("{}, using default settings without saving them", e);
+        Config {
+            read_only: true,
+            ..Config::default()
+        }
+    });
+    let database: Database = Database::load();
+
+    //commands that work without a window, which all need a rom apart from the server that loads
+    //roms when asked to
+    let headless: bool = options.headless.is_some() || options.screenshot.is_some();
+    if options.command != Command::Run || headless {
+        let rom = || {
+            options.file_name.as_deref().ok_or_else(|| {
+                Failure::usage(if headless {
+                    "--headless and --screenshot need a rom to run".to_string()
+                } else {
+                    format!("{} needs a rom", options.command.name())
+                })
+            })
+        };
+        return match options.command {
+            Command::Run => run_headless(&options, &config, &database, rom()?),
+            Command::Disasm => {
+                let contents: Vec<u8> = read_rom(rom()?)?;
+                let address: usize = options.load_address.unwrap_or(PROGRAM_START);
+                print!("{}", disasm::listing(&contents, address));
+                Ok(())
+            }
+            Command::Info => print_info(&options, &database, rom()?),
+            Command::Test => run_test(&options, &config, &database, rom()?),
+            Command::Serve => run_server(&options, &config, &database),
+        };
+    }
+
+    //load the rom given on the command line before opening the window, so a bad path exits with an error
+    let mut loaded: Option<(String, Chip8, Vec<u8>)> = match &options.file_name {
+        Some(file_name) => {
+            let (chip8, contents): (Chip8, Vec<u8>) = load_rom(file_name, &options)?;
+            Some((file_name.clone(), chip8, contents))
+        }
+        None => None,
+    };
+
     //graphics, sound, and input setup
+    let scale: u32 = options.scale.or(config.scale).unwrap_or(DEFAULT_SCALE);
     let sdl_context: sdl2::Sdl = sdl2::init()?;
     let audio_subsystem: AudioSubsystem = sdl_context.audio()?;
     let video_subsystem: sdl2::VideoSubsystem = sdl_context.video()?;
+    let controller_subsystem: GameControllerSubsystem = sdl_context.game_controller()?;
     let window: sdl2::video::Window = video_subsystem
-        .window("Chip 8 Emulator", 1280, 640)
+        .window(
+            "Chip 8 Emulator",
+            DISPLAY_WIDTH as u32 * scale,
+            DISPLAY_HEIGHT as u32 * scale,
+        )
         .position_centered()
+        .resizable()
         .build()
         .expect("could not initialize video subsystem");
     let mut canvas: sdl2::render::Canvas<sdl2::video::Window> = window
@@ -51,680 +176,1657 @@ fn main() -> Result<(), String> {
         .expect("could not make a canvas");
     canvas.clear();
     canvas.present();
-    let mut event_pump: EventPump = sdl_context.event_pump()?;
+    let texture_creator: TextureCreator<WindowContext> = canvas.texture_creator();
+    let event_pump: EventPump = sdl_context.event_pump()?;
     let desired_spec: AudioSpecDesired = AudioSpecDesired {
-        freq: Some(44100),
+        freq: Some(AUDIO_SAMPLE_RATE as i32),
         channels: Some(1), // mono
         samples: None,     // default sample size
     };
-    let device = audio_subsystem
-        .open_playback(None, &desired_spec, |spec| {
-            // initialize the audio callback
-            SquareWave {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.25,
+    let audio_queue: AudioQueue<f32> = audio_subsystem.open_queue(None, &desired_spec)?;
+    audio_queue.resume();
+
+    //open the game controllers plugged in now for the rom browser
+    let controllers: Vec<GameController> = (0..controller_subsystem.num_joysticks()?)
+        .filter(|index| controller_subsystem.is_game_controller(*index))
+        .filter_map(|index| controller_subsystem.open(index).ok())
+        .collect();
+    let mut frontend: Frontend = Frontend {
+        canvas,
+        texture_creator,
+        event_pump,
+        audio_queue,
+        video: video_subsystem,
+        _controllers: controllers,
+    };
+
+    //run roms until the window is closed, showing the rom browser when there is no rom to run
+    let mut next_rom: Option<String> = None;
+    let mut message: Option<String> = None;
+    loop {
+        let (file_name, chip8, contents): (String, Chip8, Vec<u8>) = match loaded.take() {
+            Some(loaded) => loaded,
+            None => {
+                let file_name: String = match next_rom.take() {
+                    Some(file_name) => file_name,
+                    None => match launcher::run(
+                        &mut frontend.canvas,
+                        &mut frontend.event_pump,
+                        &config,
+                        &database,
+                        message.take(),
+                    )? {
+                        Some(file_name) => file_name,
+                        None => return Ok(()),
+                    },
+                };
+
+                //load the rom, going back to the browser with the error if it can not be loaded
+                match load_rom(&file_name, &options) {
+                    Ok((chip8, contents)) => (file_name, chip8, contents),
+                    Err(failure) => {
+                        eprintln!("{}", failure.message);
+                        message = Some(failure.message);
+                        continue;
+                    }
+                }
             }
-        })
-        .unwrap();
-
-    //emulated memory setup
-    let mut memory: [u8; 0x1000] = [0; 0x1000];
-    let mut display: [u8; 0x800] = [0; 0x800];
-    let mut registers: [u8; 0x10] = [0; 0x10];
-    let mut program_counter: usize = 0x200;
-    let mut index_register: u16 = 0x0;
-    let mut stack: [usize; 0xFF] = [0; 0xFF];
-    let mut stack_index: usize = 0x0;
-    let mut delay_timer: u8 = 0x0;
-    let mut sound_timer: u8 = 0x0;
-    let mut input: [u8; 0x10] = [0; 0x10];
-
-    //font setup
-    memory[..0x50].clone_from_slice(&[
-        0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-        0x20, 0x60, 0x20, 0x20, 0x70, // 1
-        0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-        0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-        0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-        0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-        0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-        0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-        0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-        0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-        0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-        0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-        0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-        0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-        0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-        0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-    ]);
-
-    //get file name and load file
-    let args: Vec<String> = env::args().collect();
-    let file_name: &String = &args[1];
-    let contents: Vec<u8> = fs::read(file_name).expect("");
-    for i in 0..contents.len() {
-        memory[program_counter + i] = contents[i];
+        };
+        config.add_recent(&file_name);
+        if let Err(e) = config.save() {
+            eprintln!("{}", e);
+        }
+
+        match run_rom(
+            &mut frontend,
+            &options,
+            &mut config,
+            &database,
+            chip8,
+            &file_name,
+            &contents,
+        )? {
+            RomExit::Quit => return Ok(()),
+            RomExit::Browse => {}
+            RomExit::Load(file_name) => next_rom = Some(file_name),
+        }
     }
+}
 
-    //opcode loop
-    let mut should_run = true;
-    let mut timer_counter = 0;
-    while should_run {
-        //input handling
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => {
-                    should_run = false;
-                }
+//sdl objects kept for the whole time the window is open
+struct Frontend {
+    //kept to open the debug windows
+    video: VideoSubsystem,
+    canvas: Canvas<Window>,
+    texture_creator: TextureCreator<WindowContext>,
+    event_pump: EventPump,
+    audio_queue: AudioQueue<f32>,
+    //controllers only send events while they are open
+    _controllers: Vec<GameController>,
+}
 
-                //check keypad inputs and store to array
-                Event::KeyDown {
-                    keycode: Some(Keycode::Num1),
-                    ..
-                } => {
-                    input[1] = 1;
-                }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Num1),
-                    ..
-                } => {
-                    input[1] = 0;
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Num2),
-                    ..
-                } => {
-                    input[2] = 1;
-                }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Num2),
-                    ..
-                } => {
-                    input[2] = 0;
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Num3),
-                    ..
-                } => {
-                    input[3] = 1;
-                }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Num3),
-                    ..
-                } => {
-                    input[3] = 0;
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Num4),
-                    ..
-                } => {
-                    input[0xC] = 1;
-                }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Num4),
-                    ..
-                } => {
-                    input[0xC] = 0;
-                }
+//why a rom stopped running
+enum RomExit {
+    Quit,
+    Browse,
+    Load(String),
+}
 
-                Event::KeyDown {
-                    keycode: Some(Keycode::Q),
-                    ..
-                } => {
-                    input[4] = 1;
+//settings picked for a rom from the config file and command line
+struct RomSetup {
+    rom_hash: String,
+    rom_name: String,
+    //title from the rom database, or the rom name if it is not in the database
+    title: String,
+    custom_palette: Option<Palette>,
+    palette: Palette,
+    filter: DisplayFilter,
+    upscaler: Upscaler,
+    crt: CrtSettings,
+    beeper_settings: BeeperSettings,
+    quirks: Quirks,
+    instructions_per_frame: u32,
+    //window scale and speed multiplier
+    scale: u32,
+    speed: f64,
+    //chip 8 keys for the arrow keys and buttons, from the rom database
+    keys: BTreeMap<String, u8>,
+    //keyboard keys for the chip 8 keypad and the hotkeys, from the config file
+    keymap: Keymap,
+}
+
+//pick the settings for a rom
+//settings from the command line come first, then the rom's config section, then the rom database,
+//then the global config
+fn rom_setup(
+    options: &Options,
+    config: &Config,
+    database: &Database,
+    file_name: &str,
+    contents: &[u8],
+) -> RomSetup {
+    //look the rom up in the database
+    let rom_hash: String = config::rom_hash(contents);
+    let rom_name: String = config::rom_name(file_name);
+    let info: Option<RomInfo> = if options.no_database {
+        None
+    } else {
+        database.lookup(&rom_hash)
+    };
+    if let Some(info) = &info {
+        eprintln!(
+            "found {} in the rom database{}",
+            info.title,
+            info.platform
+                .as_ref()
+                .map(|platform| format!(", running as {}", platform))
+                .unwrap_or_default()
+        );
+    }
+
+    //pick the palette, using the database colors unless this rom has its own palette set
+    let rom_has_palette: bool = config
+        .rom
+        .get(&rom_hash)
+        .is_some_and(|rom| rom.palette.is_some() || rom.colors.is_some());
+    let database_colors: Option<Vec<String>> = info
+        .as_ref()
+        .and_then(|info| info.colors.clone())
+        .filter(|_| !rom_has_palette)
+        .map(|colors| colors.into_iter().take(4).collect());
+    let custom_palette: Option<Palette> = match database_colors
+        .clone()
+        .or_else(|| config.colors_for(&rom_hash))
+    {
+        Some(colors) => match Palette::custom(&colors) {
+            Ok(palette) => Some(palette),
+            Err(e) => {
+                eprintln!("{}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    let palette_name: String = if let Some(name) = &options.palette {
+        name.clone()
+    } else if database_colors.is_some() {
+        "custom".to_string()
+    } else {
+        config
+            .palette_for(&rom_hash)
+            .unwrap_or_else(|| "classic".to_string())
+    };
+    let palette: Palette = palette::select(&palette_name, &custom_palette);
+
+    //pick the anti flicker filter
+    let filter_mode: FilterMode = match options.filter {
+        Some(mode) => mode,
+        None => match config.filter_for(&rom_hash) {
+            Some(name) => FilterMode::parse(&name).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                FilterMode::Off
+            }),
+            None => FilterMode::Off,
+        },
+    };
+    let filter: DisplayFilter = DisplayFilter::new(filter_mode, DISPLAY_WIDTH * DISPLAY_HEIGHT);
+
+    //pick the upscaler
+    let upscaler: Upscaler = match options.upscaler {
+        Some(upscaler) => upscaler,
+        None => match config.upscaler_for(&rom_hash) {
+            Some(name) => Upscaler::parse(&name).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                Upscaler::Nearest
+            }),
+            None => Upscaler::Nearest,
+        },
+    };
+
+    //get the crt effect settings
+    let crt: CrtSettings = config.crt.unwrap_or_default();
+
+    //get the beeper settings
+    let mut beeper_settings: BeeperSettings = config.audio_for(&rom_hash);
+    options.audio.apply(&mut beeper_settings);
+
+    //get the quirks and speed the rom needs
+    //the global quirks and tickrate are only used for roms that are not in the database
+    let rom_config: Option<&RomConfig> = config.rom.get(&rom_hash);
+    let mut quirks: Quirks = match options
+        .quirk_preset
+        .as_deref()
+        .and_then(database::platform_defaults)
+    {
+        Some((quirks, _)) => quirks,
+        None => match info.as_ref().and_then(|info| info.quirks) {
+            Some(quirks) => quirks,
+            None => {
+                let mut quirks: Quirks = Quirks::default();
+                if let Some(overrides) = &config.quirks {
+                    overrides.apply(&mut quirks);
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Q),
-                    ..
-                } => {
-                    input[4] = 0;
+                quirks
+            }
+        },
+    };
+    if let Some(overrides) = rom_config.and_then(|rom| rom.quirks.as_ref()) {
+        overrides.apply(&mut quirks);
+    }
+    options.quirks.apply(&mut quirks);
+    let instructions_per_frame: u32 = options
+        .tickrate
+        .or(rom_config.and_then(|rom| rom.tickrate))
+        .or(info.as_ref().and_then(|info| info.tickrate))
+        .or(config.tickrate)
+        .unwrap_or(INSTRUCTIONS_PER_FRAME);
+    let scale: u32 = options
+        .scale
+        .or(config.scale_for(&rom_hash))
+        .unwrap_or(DEFAULT_SCALE);
+    let speed: f64 = options.speed.or(config.speed_for(&rom_hash)).unwrap_or(1.0);
+
+    //get the keyboard keys
+    let keymap: Keymap =
+        Keymap::new(config.keypad_for(&rom_hash), &config.hotkeys).unwrap_or_else(|e| {
+            eprintln!("{}, using the default keys", e);
+            Keymap::default()
+        });
+
+    RomSetup {
+        rom_hash,
+        title: info
+            .as_ref()
+            .map(|info| info.title.clone())
+            .unwrap_or_else(|| rom_name.clone()),
+        rom_name,
+        custom_palette,
+        palette,
+        filter,
+        upscaler,
+        crt,
+        beeper_settings,
+        quirks,
+        instructions_per_frame,
+        scale,
+        speed,
+        keys: info.map(|info| info.keys).unwrap_or_default(),
+        keymap,
+    }
+}
+
+//run a rom without a window or audio device for a number of frames, saving a screenshot and the audio
+fn run_headless(
+    options: &Options,
+    config: &Config,
+    database: &Database,
+    file_name: &str,
+) -> Result<(), Failure> {
+    let frames: u64 = options.headless.or(options.screenshot).unwrap_or(0);
+    let (mut chip8, contents): (Chip8, Vec<u8>) = load_rom(file_name, options)?;
+    let RomSetup {
+        rom_name,
+        scale,
+        palette,
+        mut filter,
+        upscaler,
+        crt,
+        beeper_settings,
+        quirks,
+        instructions_per_frame,
+        ..
+    } = rom_setup(options, config, database, file_name, &contents);
+    chip8.quirks = quirks;
+    chip8.instructions_per_frame = instructions_per_frame;
+
+    let mut audio_stream: AudioStream = AudioStream::new(beeper_settings, AUDIO_SAMPLE_RATE);
+    let mut audio_recording: Option<WavWriter> = match &options.record_audio {
+        Some(path) => Some(WavWriter::create(Path::new(path), AUDIO_SAMPLE_RATE)?),
+        None => None,
+    };
+    let record_size: (usize, usize) = (
+        DISPLAY_WIDTH * scale as usize,
+        DISPLAY_HEIGHT * scale as usize,
+    );
+    let mut recorder: Option<Recorder> = match &options.record {
+        Some(path) => Some(Recorder::start(
+            path,
+            &palette,
+            AUDIO_SAMPLE_RATE,
+            record_size,
+        )?),
+        None => None,
+    };
+    while chip8.frame < frames {
+        let frame_ended: bool = chip8.step();
+        report_bad_opcode(&mut chip8);
+        audio_stream.instruction(chip8.sound_timer != 0);
+        if frame_ended {
+            filter.push_frame(&chip8.display);
+            let samples: Vec<f32> = audio_stream.end_frame();
+            if let Some(wav) = &mut audio_recording {
+                wav.write_samples(&samples)?;
+            }
+            if let Some(active) = &mut recorder {
+                active.add_frame(&chip8.display, &palette, &samples, || {
+                    screenshot_image(
+                        &chip8.display,
+                        &filter,
+                        upscaler,
+                        &palette,
+                        &crt,
+                        false,
+                        record_size,
+                    )
+                    .0
+                })?;
+            }
+        }
+    }
+    if let Some(wav) = audio_recording {
+        wav.finish()?;
+        eprintln!("saved {}", options.record_audio.as_ref().unwrap());
+    }
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    if options.screenshot.is_none() {
+        return Ok(());
+    }
+
+    let (image, width, height): (Vec<u8>, usize, usize) = screenshot_image(
+        &chip8.display,
+        &filter,
+        upscaler,
+        &palette,
+        &crt,
+        options.raw_screenshot,
+        (
+            DISPLAY_WIDTH * scale as usize,
+            DISPLAY_HEIGHT * scale as usize,
+        ),
+    );
+    let path: String = screenshot::file_name(&rom_name, chip8.frame, options.raw_screenshot);
+    screenshot::save_png(Path::new(&path), &image, width, height)?;
+    eprintln!("saved {}", path);
+    Ok(())
+}
+
+//run a rom without a window and print a hash of the display, failing if it is not the --expect hash
+fn run_test(
+    options: &Options,
+    config: &Config,
+    database: &Database,
+    file_name: &str,
+) -> Result<(), Failure> {
+    let (mut chip8, contents): (Chip8, Vec<u8>) = load_rom(file_name, options)?;
+    let RomSetup {
+        quirks,
+        instructions_per_frame,
+        ..
+    } = rom_setup(options, config, database, file_name, &contents);
+    chip8.quirks = quirks;
+    chip8.instructions_per_frame = instructions_per_frame;
+
+    let frames: u64 = options.frames.unwrap_or(DEFAULT_TEST_FRAMES);
+    while chip8.frame < frames {
+        chip8.step();
+        report_bad_opcode(&mut chip8);
+    }
+    let display_hash: String = config::rom_hash(&chip8.display);
+    println!(
+        "{}: display after {} frames {}",
+        file_name, frames, display_hash
+    );
+
+    match &options.expect {
+        Some(expected) if *expected != display_hash => Err(Failure::from(format!(
+            "{}: test failed, expected display {}",
+            file_name, expected
+        ))),
+        Some(_) => {
+            println!("{}: test passed", file_name);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+//run the automation server, which loads roms with the settings they would run with in the window
+fn run_server(options: &Options, config: &Config, database: &Database) -> Result<(), Failure> {
+    let load = |file_name: &str| -> Result<Chip8, String> {
+        let (mut chip8, contents): (Chip8, Vec<u8>) =
+            load_rom(file_name, options).map_err(|failure| failure.message)?;
+        let RomSetup {
+            quirks,
+            instructions_per_frame,
+            ..
+        } = rom_setup(options, config, database, file_name, &contents);
+        chip8.quirks = quirks;
+        chip8.instructions_per_frame = instructions_per_frame;
+        Ok(chip8)
+    };
+
+    //a rom on the command line has to load, as with the other commands
+    if let Some(file_name) = &options.file_name {
+        read_rom(file_name)?;
+    }
+    let mut server: Server = Server::new(&load, options.file_name.as_deref())?;
+    match &options.socket {
+        Some(path) => server.listen_unix(path)?,
+        None => server.listen_tcp(options.port.unwrap_or(server::DEFAULT_PORT))?,
+    }
+    Ok(())
+}
+
+//print what is known about a rom
+fn print_info(options: &Options, database: &Database, file_name: &str) -> Result<(), Failure> {
+    let contents: Vec<u8> = read_rom(file_name)?;
+    let rom_hash: String = config::rom_hash(&contents);
+    println!("file:        {}", file_name);
+    println!("size:        {} bytes", contents.len());
+    println!("sha1:        {}", rom_hash);
+
+    let info: Option<RomInfo> = if options.no_database {
+        None
+    } else {
+        database.lookup(&rom_hash)
+    };
+    let info: RomInfo = match info {
+        Some(info) => info,
+        None => {
+            println!("not in the rom database");
+            return Ok(());
+        }
+    };
+    println!("title:       {}", info.title);
+    if !info.authors.is_empty() {
+        println!("authors:     {}", info.authors.join(", "));
+    }
+    if let Some(release) = &info.release {
+        println!("release:     {}", release);
+    }
+    if let Some(platform) = &info.platform {
+        println!("platform:    {}", platform);
+    }
+    if let Some(tickrate) = info.tickrate {
+        println!("tickrate:    {}", tickrate);
+    }
+    if let Some(quirks) = &info.quirks {
+        println!("quirks:      {}", database::format_quirks(quirks));
+    }
+    if let Some(description) = &info.description {
+        println!(
+            "description: {}",
+            description
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+        );
+    }
+    Ok(())
+}
+
+//read a rom file, failing with the no input exit code
+fn read_rom(file_name: &str) -> Result<Vec<u8>, Failure> {
+    fs::read(file_name).map_err(|e| Failure {
+        message: format!("could not read {}: {}", file_name, e),
+        code: cli::EXIT_NO_INPUT,
+    })
+}
+
+//read a rom file and load it into a new machine, seeded and at the address from the command line
+fn load_rom(file_name: &str, options: &Options) -> Result<(Chip8, Vec<u8>), Failure> {
+    let contents: Vec<u8> = read_rom(file_name)?;
+    let mut chip8: Chip8 = Chip8::new();
+    restart_rom(&mut chip8, &contents, options).map_err(|e| Failure {
+        message: format!("could not load {}: {}", file_name, e),
+        code: cli::EXIT_NO_INPUT,
+    })?;
+    Ok((chip8, contents))
+}
+
+//reset a machine and load a rom into it, seeded and at the address from the command line
+fn restart_rom(chip8: &mut Chip8, contents: &[u8], options: &Options) -> Result<(), String> {
+    chip8.reset();
+    match options.load_address {
+        Some(address) => chip8.load_rom_at(contents, address)?,
+        None => chip8.load_rom(contents)?,
+    }
+    if let Some(seed) = options.seed {
+        chip8.seed(seed);
+    }
+    Ok(())
+}
+
+//print an instruction the machine could not run
+fn report_bad_opcode(chip8: &mut Chip8) {
+    if let Some((address, opcode)) = chip8.bad_opcode.take() {
+        eprintln!("{:#06x} opcode does not exist at {:#05x}", opcode, address);
+    }
+}
+
+//get the addresses a rom is loaded at
+fn rom_range(contents: &[u8], options: &Options) -> Range<usize> {
+    let start: usize = options.load_address.unwrap_or(PROGRAM_START);
+    start..start + contents.len()
+}
+
+//get when a file was last changed, or None if that can not be read
+fn modified_time(file_name: &str) -> Option<SystemTime> {
+    fs::metadata(file_name)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+//run a rom in the window until it is closed or another rom is picked
+fn run_rom(
+    frontend: &mut Frontend,
+    options: &Options,
+    config: &mut Config,
+    database: &Database,
+    mut chip8: Chip8,
+    file_name: &str,
+    contents: &[u8],
+) -> Result<RomExit, String> {
+    let mut integer_scale: bool = options.integer_scale || config.integer_scale.unwrap_or(false);
+    let RomSetup {
+        rom_hash,
+        rom_name,
+        title,
+        custom_palette,
+        mut palette,
+        mut filter,
+        mut upscaler,
+        mut crt,
+        beeper_settings,
+        quirks,
+        instructions_per_frame,
+        mut scale,
+        mut speed,
+        keys,
+        keymap,
+    } = rom_setup(options, config, database, file_name, contents);
+    chip8.quirks = quirks;
+    chip8.instructions_per_frame = instructions_per_frame;
+
+    //sdl objects
+    let canvas: &mut Canvas<Window> = &mut frontend.canvas;
+    let texture_creator: &TextureCreator<WindowContext> = &frontend.texture_creator;
+    let event_pump: &mut EventPump = &mut frontend.event_pump;
+    let audio_queue: &AudioQueue<f32> = &frontend.audio_queue;
+    let video: &VideoSubsystem = &frontend.video;
+    let main_window_id: u32 = canvas.window().id();
+    canvas
+        .window_mut()
+        .set_title(&format!("{} - Chip 8 Emulator", title))
+        .map_err(|e| e.to_string())?;
+    resize_window(canvas, scale)?;
+    let mut crt_texture: Option<Texture> = None;
+    audio_queue.clear();
+    let mut audio_stream: AudioStream =
+        AudioStream::new(beeper_settings, audio_queue.spec().freq as u32);
+
+    //start recording if asked to
+    let record_size: (usize, usize) = (
+        DISPLAY_WIDTH * scale as usize,
+        DISPLAY_HEIGHT * scale as usize,
+    );
+    let mut recorder: Option<Recorder> = match &options.record {
+        Some(path) => Some(Recorder::start(
+            path,
+            &palette,
+            audio_stream.sample_rate(),
+            record_size,
+        )?),
+        None => None,
+    };
+
+    //start recording the audio if asked to
+    let mut audio_recording: Option<WavWriter> = match &options.record_audio {
+        Some(path) => {
+            eprintln!("recording audio to {}", path);
+            Some(WavWriter::create(
+                Path::new(path),
+                audio_stream.sample_rate(),
+            )?)
+        }
+        None => None,
+    };
+
+    //on screen display
+    let mut osd: Osd = Osd::new(config.show_stats.unwrap_or(false));
+    let mut paused: bool = options.paused;
+    osd.paused = paused;
+
+    //speed controls, paused by losing focus, run one frame, fast forward held, and slow motion divisor
+    let pause_on_focus_loss: bool = config.pause_on_focus_loss.unwrap_or(true);
+    let fast_forward_speed: f64 = config.fast_forward_speed.unwrap_or(0.0);
+    let mut focus_paused: bool = false;
+    let mut advance_frame: bool = false;
+    let mut fast_forward: bool = false;
+    let mut slow_motion: u32 = 1;
+    let mut next_instruction: Instant = Instant::now();
+
+    //with --watch the rom is reloaded when its file changes, the contents are kept to reset to
+    let mut contents: Vec<u8> = contents.to_vec();
+    let mut rom_modified: Option<SystemTime> = modified_time(file_name);
+    let mut watch_checked: Instant = Instant::now();
+
+    //debug windows
+    let mut memory_viewer: Option<MemoryViewer> = None;
+    let mut sprite_viewer: Option<SpriteViewer> = None;
+
+    //cheats from the .cht file next to the rom, and the ram search for finding new ones
+    //a file that can not be read leaves cheats off so it is not overwritten
+    let mut cheats: Result<Cheats, String> =
+        Cheats::load(&Path::new(file_name).with_extension("cht"));
+    if let Err(e) = &cheats {
+        eprintln!("{}", e);
+    }
+    let mut ram_search: RamSearch = RamSearch::default();
+
+    //opcode loop, runs until the window is closed or another rom is picked
+    let mut exit: Option<RomExit> = None;
+    while exit.is_none() {
+        //reload the rom if its file has changed, keeping the settings picked for it
+        if options.watch && watch_checked.elapsed() >= WATCH_INTERVAL {
+            watch_checked = Instant::now();
+            let modified: Option<SystemTime> = modified_time(file_name);
+            if modified != rom_modified {
+                rom_modified = modified;
+                match fs::read(file_name) {
+                    Ok(changed) => match restart_rom(&mut chip8, &changed, options) {
+                        Ok(()) => {
+                            contents = changed;
+                            if let Some(viewer) = &mut memory_viewer {
+                                viewer.rom_range = rom_range(&contents, options);
+                            }
+                            audio_queue.clear();
+                            osd.notify(format!("reloaded {}", rom_name));
+                        }
+                        Err(e) => osd.notify(e),
+                    },
+                    Err(e) => osd.notify(format!("could not read {}: {}", file_name, e)),
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::W),
-                    ..
-                } => {
-                    input[5] = 1;
+            }
+        }
+
+        //input handling
+        let mut open_settings: bool = false;
+        let mut open_cheats: bool = false;
+        for event in event_pump.poll_iter() {
+            //events for the debug windows, keys they do not use go on to the hotkeys
+            if let Some(viewer) = &mut memory_viewer {
+                if event.get_window_id() == Some(viewer.window_id())
+                    && viewer.handle_event(&event, &mut chip8, paused)
+                {
+                    continue;
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::W),
-                    ..
-                } => {
-                    input[5] = 0;
+            }
+            if let Some(viewer) = &mut sprite_viewer {
+                if event.get_window_id() == Some(viewer.window_id())
+                    && viewer.handle_event(&event, &chip8)
+                {
+                    continue;
                 }
+            }
+
+            //arrow keys and buttons the rom database maps to chip 8 keys for this rom
+            if let Some((key, pressed)) = mapped_button(&keys, &event) {
+                chip8.input[key] = pressed as u8;
+                continue;
+            }
+
+            //hotkeys, which can be changed in the config file
+            let action: Option<Action> = match &event {
                 Event::KeyDown {
-                    keycode: Some(Keycode::E),
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat: false,
                     ..
-                } => {
-                    input[6] = 1;
-                }
-                Event::KeyUp {
-                    keycode: Some(Keycode::E),
-                    ..
-                } => {
-                    input[6] = 0;
+                } => keymap.action(*keycode, *keymod),
+                _ => None,
+            };
+            match action {
+                Some(Action::Quit) => {
+                    exit = Some(RomExit::Quit);
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::R),
-                    ..
-                } => {
-                    input[0xD] = 1;
+
+                //go back to the rom browser
+                Some(Action::Browse) => {
+                    exit = Some(RomExit::Browse);
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::R),
-                    ..
-                } => {
-                    input[0xD] = 0;
+
+                //open the settings screen once the events have been handled
+                Some(Action::Settings) => {
+                    open_settings = true;
                 }
 
-                Event::KeyDown {
-                    keycode: Some(Keycode::A),
-                    ..
-                } => {
-                    input[7] = 1;
+                //open the cheats screen once the events have been handled
+                Some(Action::Cheats) => {
+                    open_cheats = true;
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::A),
-                    ..
-                } => {
-                    input[7] = 0;
+
+                //toggle fullscreen
+                Some(Action::Fullscreen) => {
+                    let window: &mut sdl2::video::Window = canvas.window_mut();
+                    let fullscreen: FullscreenType = match window.fullscreen_state() {
+                        FullscreenType::Off => FullscreenType::Desktop,
+                        _ => FullscreenType::Off,
+                    };
+                    window.set_fullscreen(fullscreen)?;
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::S),
-                    ..
-                } => {
-                    input[8] = 1;
+
+                //toggle integer scaling and remember the choice
+                Some(Action::IntegerScale) => {
+                    integer_scale = !integer_scale;
+                    osd.notify(format!(
+                        "integer scaling {}",
+                        if integer_scale { "on" } else { "off" }
+                    ));
+                    config.integer_scale = Some(integer_scale);
+                    if let Err(e) = config.save() {
+                        eprintln!("{}", e);
+                    }
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::S),
-                    ..
-                } => {
-                    input[8] = 0;
+
+                //cycle through the anti flicker filters and remember the choice for this rom
+                Some(Action::Filter) => {
+                    filter.set_mode(filter.mode().next());
+                    osd.notify(format!("filter: {}", filter.mode().name()));
+                    config.rom_mut(&rom_hash).filter = Some(filter.mode().name());
+                    if let Err(e) = config.save() {
+                        eprintln!("{}", e);
+                    }
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::D),
-                    ..
-                } => {
-                    input[9] = 1;
+
+                //toggle the crt effects and remember the choice
+                Some(Action::Crt) => {
+                    crt.enabled = !crt.enabled;
+                    osd.notify(format!(
+                        "crt effects {}",
+                        if crt.enabled { "on" } else { "off" }
+                    ));
+                    config.crt = Some(crt);
+                    if let Err(e) = config.save() {
+                        eprintln!("{}", e);
+                    }
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::D),
-                    ..
-                } => {
-                    input[9] = 0;
+
+                //cycle through the upscalers and remember the choice for this rom
+                Some(Action::Upscaler) => {
+                    upscaler = upscaler.next();
+                    osd.notify(format!("upscaler: {}", upscaler.name()));
+                    config.rom_mut(&rom_hash).upscaler = Some(upscaler.name().to_string());
+                    if let Err(e) = config.save() {
+                        eprintln!("{}", e);
+                    }
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::F),
-                    ..
-                } => {
-                    input[0xE] = 1;
+
+                //save a screenshot of the window, or of the raw display
+                Some(Action::Screenshot) | Some(Action::RawScreenshot) => {
+                    let raw: bool = action == Some(Action::RawScreenshot);
+                    let (output_width, output_height): (u32, u32) = canvas.output_size()?;
+                    let area: Rect = viewport::fit(
+                        output_width,
+                        output_height,
+                        DISPLAY_WIDTH as u32,
+                        DISPLAY_HEIGHT as u32,
+                        integer_scale,
+                    );
+                    let (image, width, height): (Vec<u8>, usize, usize) = screenshot_image(
+                        &chip8.display,
+                        &filter,
+                        upscaler,
+                        &palette,
+                        &crt,
+                        raw,
+                        (area.width() as usize, area.height() as usize),
+                    );
+                    let path: String = screenshot::file_name(&rom_name, chip8.frame, raw);
+                    match screenshot::save_png(Path::new(&path), &image, width, height) {
+                        Ok(()) => osd.notify(format!("saved {}", path)),
+                        Err(e) => osd.notify(e),
+                    }
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::F),
-                    ..
-                } => {
-                    input[0xE] = 0;
+
+                //start or stop recording, to the --record path if given or to a gif otherwise
+                Some(Action::Record) => match recorder.take() {
+                    Some(recorder) => match recorder.finish() {
+                        Ok(()) => osd.notify("recording stopped".to_string()),
+                        Err(e) => osd.notify(e),
+                    },
+                    None => {
+                        let path: String = options
+                            .record
+                            .clone()
+                            .unwrap_or_else(|| recording::file_name(&rom_name, chip8.frame));
+                        match Recorder::start(
+                            &path,
+                            &palette,
+                            audio_stream.sample_rate(),
+                            record_size,
+                        ) {
+                            Ok(started) => {
+                                osd.notify(format!("recording to {}", path));
+                                recorder = Some(started);
+                            }
+                            Err(e) => osd.notify(e),
+                        }
+                    }
+                },
+
+                //start or stop recording the audio, to the --record-audio path if given
+                Some(Action::RecordAudio) => match audio_recording.take() {
+                    Some(wav) => {
+                        let seconds: f64 = wav.seconds();
+                        match wav.finish() {
+                            Ok(()) => {
+                                osd.notify(format!("audio recording stopped after {:.1}s", seconds))
+                            }
+                            Err(e) => osd.notify(e),
+                        }
+                    }
+                    None => {
+                        let path: String = options
+                            .record_audio
+                            .clone()
+                            .unwrap_or_else(|| wav::file_name(&rom_name, chip8.frame));
+                        match WavWriter::create(Path::new(&path), audio_stream.sample_rate()) {
+                            Ok(wav) => {
+                                osd.notify(format!("recording audio to {}", path));
+                                audio_recording = Some(wav);
+                            }
+                            Err(e) => osd.notify(e),
+                        }
+                    }
+                },
+
+                //pause or resume the rom
+                Some(Action::Pause) => {
+                    paused = !paused;
+                    focus_paused = false;
+                    osd.paused = paused;
+                    osd.notify(if paused { "paused" } else { "resumed" }.to_string());
                 }
 
-                Event::KeyDown {
-                    keycode: Some(Keycode::Z),
-                    ..
-                } => {
-                    input[0xA] = 1;
+                //start the rom again without reloading the file
+                Some(Action::Reset) => match restart_rom(&mut chip8, &contents, options) {
+                    Ok(()) => {
+                        if let Some(viewer) = &mut memory_viewer {
+                            viewer.rom_range = rom_range(&contents, options);
+                        }
+                        audio_queue.clear();
+                        osd.notify("reset".to_string());
+                    }
+                    Err(e) => osd.notify(e),
+                },
+
+                //run one frame, pausing first if running
+                Some(Action::FrameAdvance) => {
+                    if paused {
+                        advance_frame = true;
+                    } else {
+                        paused = true;
+                        osd.paused = paused;
+                        osd.notify("paused".to_string());
+                    }
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Z),
-                    ..
-                } => {
-                    input[0xA] = 0;
+
+                //run faster until the key is let go
+                Some(Action::FastForward) => {
+                    fast_forward = true;
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::X),
-                    ..
-                } => {
-                    input[0] = 1;
+
+                //cycle through normal, half and quarter speed
+                Some(Action::SlowMotion) => {
+                    slow_motion = match slow_motion {
+                        1 => 2,
+                        2 => 4,
+                        _ => 1,
+                    };
+                    osd.notify(if slow_motion == 1 {
+                        "normal speed".to_string()
+                    } else {
+                        format!("slow motion 1/{}", slow_motion)
+                    });
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::X),
-                    ..
-                } => {
-                    input[0] = 0;
+
+                //show or hide the fps and speed and remember the choice
+                Some(Action::Stats) => {
+                    config.show_stats = Some(osd.toggle_stats());
+                    if let Err(e) = config.save() {
+                        eprintln!("{}", e);
+                    }
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::C),
-                    ..
-                } => {
-                    input[0xB] = 1;
+
+                //open or close the memory viewer
+                Some(Action::MemoryViewer) => {
+                    memory_viewer = match memory_viewer.take() {
+                        Some(_) => None,
+                        None => match MemoryViewer::open(video, rom_range(&contents, options)) {
+                            Ok(viewer) => Some(viewer),
+                            Err(e) => {
+                                osd.notify(e);
+                                None
+                            }
+                        },
+                    };
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::C),
-                    ..
-                } => {
-                    input[0xB] = 0;
+
+                //open or close the sprite viewer
+                Some(Action::SpriteViewer) => {
+                    sprite_viewer = match sprite_viewer.take() {
+                        Some(_) => None,
+                        None => match SpriteViewer::open(video) {
+                            Ok(viewer) => Some(viewer),
+                            Err(e) => {
+                                osd.notify(e);
+                                None
+                            }
+                        },
+                    };
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::V),
-                    ..
-                } => {
-                    input[0xF] = 1;
+
+                //mute or unmute the beeper
+                Some(Action::Mute) => {
+                    let beeper: &mut Beeper = audio_stream.beeper_mut();
+                    let mut settings: BeeperSettings = beeper.settings();
+                    settings.muted = !settings.muted;
+                    beeper.set_settings(settings);
+                    osd.notify(if settings.muted { "muted" } else { "unmuted" }.to_string());
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::V),
-                    ..
-                } => {
-                    input[0xF] = 0;
+
+                //cycle through the palettes and remember the choice for this rom
+                Some(Action::Palette) => {
+                    let names: Vec<&str> = palette::cycle_names(custom_palette.is_some());
+                    let current: usize = names
+                        .iter()
+                        .position(|name| *name == palette.name)
+                        .unwrap_or(0);
+                    let next: &str = names[(current + 1) % names.len()];
+                    palette = palette::select(next, &custom_palette);
+                    osd.notify(format!("palette: {}", palette.name));
+
+                    config.rom_mut(&rom_hash).palette = Some(palette.name.clone());
+                    if let Err(e) = config.save() {
+                        eprintln!("{}", e);
+                    }
                 }
-                _ => {}
-            }
-        }
 
-        //load 2 byte opcode
-        let opcode = ((memory[program_counter] as u16) << 8) | (memory[program_counter + 1] as u16);
+                None => match event {
+                    Event::Quit { .. }
+                    | Event::Window {
+                        win_event: WindowEvent::Close,
+                        ..
+                    } => {
+                        exit = Some(RomExit::Quit);
+                    }
 
-        //check opcode and execute
-        if opcode == 0x00E0 {
-            //clear screen (00E0)
-            //clear
-            for i in 0..0xFF {
-                display[i as usize] = 0x00;
-            }
-        } else if opcode == 0x00EE {
-            //return from subroutine
-            //set program counter to value stored in highest part of stack
-            program_counter = stack[stack_index - 1];
-        } else if (opcode & 0xF000) == 0x1000 {
-            //jump (1NNN)
-            program_counter = ((opcode & 0x0FFF) - 2) as usize;
-        } else if (opcode & 0xF000) == 0x2000 {
-            //call subroutine (2NNN)
-            //set highest part of stack to program counter
-            stack[stack_index] = program_counter;
-            stack_index = stack_index.wrapping_add(1);
-
-            //set program counter equal to passed value
-            program_counter = ((opcode & 0x0FFF) - 2) as usize;
-        } else if (opcode & 0xF000) == 0x3000 {
-            //instruction skip (3XNN) if Vx = NN
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //get value to compare register to
-            let cmp_val = (opcode & 0x00FF) as u8;
-
-            //compare
-            if registers[reg_num] == cmp_val {
-                program_counter = program_counter.wrapping_add(2);
-            }
-        } else if (opcode & 0xF000) == 0x4000 {
-            //instruction skip (4XNN) if Vx != NN
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
+                    //run a rom dropped onto the window
+                    Event::DropFile { filename, .. } => {
+                        exit = Some(RomExit::Load(filename));
+                    }
+
+                    //pause while another window has focus, and resume when it comes back
+                    //unless the rom was paused by hand in between
+                    Event::Window {
+                        win_event: WindowEvent::FocusLost,
+                        ..
+                    } => {
+                        fast_forward = false;
+                        if pause_on_focus_loss
+                            && !paused
+                            && memory_viewer.is_none()
+                            && sprite_viewer.is_none()
+                        {
+                            paused = true;
+                            focus_paused = true;
+                            osd.paused = paused;
+                        }
+                    }
+                    Event::Window {
+                        win_event: WindowEvent::FocusGained,
+                        ..
+                    } if focus_paused => {
+                        paused = false;
+                        focus_paused = false;
+                        osd.paused = paused;
+                    }
 
-            //get value to compare register to
-            let cmp_val = (opcode & 0x00FF) as u8;
+                    //stop fast forwarding when its key is let go
+                    Event::KeyUp {
+                        keycode: Some(keycode),
+                        ..
+                    } if keymap.releases(Action::FastForward, keycode) => {
+                        fast_forward = false;
+                    }
 
-            //compare
-            if registers[reg_num] != cmp_val {
-                program_counter = program_counter.wrapping_add(2);
+                    //check keypad inputs and store to array
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        window_id,
+                        ..
+                    } if window_id == main_window_id => {
+                        if let Some(key) = keymap.key(keycode) {
+                            chip8.input[key] = 1;
+                        }
+                    }
+                    Event::KeyUp {
+                        keycode: Some(keycode),
+                        window_id,
+                        ..
+                    } if window_id == main_window_id => {
+                        if let Some(key) = keymap.key(keycode) {
+                            chip8.input[key] = 0;
+                        }
+                    }
+                    _ => {}
+                },
             }
-        } else if (opcode & 0xF00F) == 0x5000 {
-            //instruction skip (5XY0) if Vx = Vy
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //compare
-            if registers[reg_num_x] == registers[reg_num_y] {
-                program_counter = program_counter.wrapping_add(2);
+        }
+
+        //drop debug windows that were closed
+        if memory_viewer.as_ref().is_some_and(MemoryViewer::closed) {
+            memory_viewer = None;
+        }
+        if sprite_viewer.as_ref().is_some_and(SpriteViewer::closed) {
+            sprite_viewer = None;
+        }
+
+        //show a sprite clicked in the sprite viewer in the memory viewer, opening it if needed
+        if let Some(address) = sprite_viewer.as_mut().and_then(SpriteViewer::take_clicked) {
+            if memory_viewer.is_none() {
+                match MemoryViewer::open(video, rom_range(&contents, options)) {
+                    Ok(viewer) => memory_viewer = Some(viewer),
+                    Err(e) => osd.notify(e),
+                }
             }
-        } else if (opcode & 0xF000) == 0x6000 {
-            //set register vx (6XNN)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //get value to set register to
-            let reg_val = (opcode & 0x00FF) as u8;
-
-            //update register
-            registers[reg_num] = reg_val;
-        } else if (opcode & 0xF000) == 0x7000 {
-            //add value to register vx (7XNN)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //get value to set register to
-            let reg_val = (opcode & 0x00FF) as u8;
-
-            //update register
-            registers[reg_num] = registers[reg_num].wrapping_add(reg_val);
-        } else if (opcode & 0xF00F) == 0x8000 {
-            //set value of Vx to value of Vy (8XY0)
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //update register
-            registers[reg_num_x] = registers[reg_num_y];
-        } else if (opcode & 0xF00F) == 0x8001 {
-            //set value of Vx to value of Vx or Vy (8XY1)
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //update register
-            registers[reg_num_x] |= registers[reg_num_y];
-        } else if (opcode & 0xF00F) == 0x8002 {
-            //set value of Vx to value of Vx and Vy (8XY2)
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //update register
-            registers[reg_num_x] &= registers[reg_num_y];
-        } else if (opcode & 0xF00F) == 0x8003 {
-            //set value of Vx to value of Vx xor Vy (8XY3)
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //update register
-            registers[reg_num_x] ^= registers[reg_num_y];
-        } else if (opcode & 0xF00F) == 0x8004 {
-            //set value of Vx to value of Vx + Vy (8XY4), set VF to whether or not there was an overflow
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //check for overflow
-            if registers[reg_num_x].checked_add(registers[reg_num_y]) == None {
-                registers[0xF] = 1;
-            } else {
-                registers[0xF] = 0;
+            if let Some(viewer) = &mut memory_viewer {
+                viewer.show(address);
             }
+        }
 
-            //update register
-            registers[reg_num_x] = registers[reg_num_x].wrapping_add(registers[reg_num_y]);
-        } else if (opcode & 0xF00F) == 0x8005 {
-            //set value of Vx to value of Vx - Vy (8XY5), set VF to whether or not there was an underflow
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //check for overflow
-            if registers[reg_num_x].checked_sub(registers[reg_num_y]) == None {
-                registers[0xF] = 0;
-            } else {
-                registers[0xF] = 1;
+        //change settings on the settings screen, then run with them and remember them
+        if open_settings {
+            let before: Settings = Settings {
+                palette: palette.name.clone(),
+                scale,
+                integer_scale,
+                speed,
+                tickrate: chip8.instructions_per_frame,
+                filter: filter.mode(),
+                upscaler,
+                crt: crt.enabled,
+                show_stats: osd.show_stats,
+                beeper: audio_stream.beeper_mut().settings(),
+                quirks: chip8.quirks,
+            };
+            let mut changed: Settings = before.clone();
+            let for_rom: bool = match settings::run(
+                canvas,
+                event_pump,
+                &mut changed,
+                &title,
+                custom_palette.is_some(),
+            )? {
+                Some(for_rom) => for_rom,
+                None => {
+                    exit = Some(RomExit::Quit);
+                    true
+                }
+            };
+            changed.save(&before, config, for_rom.then_some(rom_hash.as_str()));
+            if let Err(e) = config.save() {
+                eprintln!("{}", e);
             }
 
-            //update register
-            registers[reg_num_x] = registers[reg_num_x].wrapping_sub(registers[reg_num_y]);
-        } else if (opcode & 0xF00F) == 0x8006 {
-            //set value of Vx to value of Vy shifted 1 bit to the right (8XY6), set Vf to the shifted bit
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //update register
-            registers[reg_num_x] = registers[reg_num_y];
-            registers[0xF] = registers[reg_num_x] % 2;
-            registers[reg_num_x] = registers[reg_num_x] >> 1;
-        } else if (opcode & 0xF00F) == 0x8007 {
-            //set value of Vx to value of Vy - Vx (8XY7), set VF to whether or not there was an underflow
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //check for overflow
-            if registers[reg_num_y].checked_sub(registers[reg_num_x]) == None {
-                registers[0xF] = 0;
-            } else {
-                registers[0xF] = 1;
+            palette = palette::select(&changed.palette, &custom_palette);
+            if changed.scale != scale {
+                scale = changed.scale;
+                resize_window(canvas, scale)?;
             }
-
-            //update register
-            registers[reg_num_x] = registers[reg_num_y] - registers[reg_num_x];
-        } else if (opcode & 0xF00F) == 0x800E {
-            //set value of Vx to value of Vy shifted 7 bits to the right (8XYE), set Vf to the shifted bit
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //update register
-            registers[reg_num_x] = registers[reg_num_y];
-            registers[0xF] = registers[reg_num_x] >> 7;
-            registers[reg_num_x] = registers[reg_num_x] << 1;
-        } else if (opcode & 0xF00F) == 0x9000 {
-            //skip next instruction if Vx != Vy (9XY0)
-            //get register numbers
-            let reg_num_x = ((opcode & 0x0F00) >> 0x8) as usize;
-            let reg_num_y = ((opcode & 0x00F0) >> 0x4) as usize;
-
-            //update register
-            if registers[reg_num_x] != registers[reg_num_y] {
-                program_counter = program_counter.wrapping_add(2);
+            integer_scale = changed.integer_scale;
+            speed = changed.speed;
+            chip8.instructions_per_frame = changed.tickrate;
+            chip8.quirks = changed.quirks;
+            filter.set_mode(changed.filter);
+            upscaler = changed.upscaler;
+            crt.enabled = changed.crt;
+            osd.show_stats = changed.show_stats;
+            audio_stream.beeper_mut().set_settings(changed.beeper);
+            audio_queue.clear();
+            if changed != before {
+                osd.notify(if for_rom {
+                    "settings saved for this rom".to_string()
+                } else {
+                    "settings saved for all roms".to_string()
+                });
             }
-        } else if (opcode & 0xF000) == 0xA000 {
-            //set index register I (ANNN)
-            //get value to set register to and update register
-            index_register = opcode & 0x0FFF;
-        } else if (opcode & 0xF000) == 0xB000 {
-            //jump to NNN + V0 (1NNN)
-            program_counter = ((opcode & 0x0FFF) + (registers[0] as u16) - 2) as usize;
-        } else if (opcode & 0xF000) == 0xC000 {
-            //set value of Vx to random & NN (CXNN)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //get value to bitwise and random value with
-            let val = (opcode & 0x00FF) as u8;
-
-            //update register
-            registers[reg_num] = rand::thread_rng().gen();
-            registers[reg_num] &= val;
-        } else if (opcode & 0xF000) == 0xD000 {
-            //display / draw (DXYN)
-            //get coordinates and height
-            let x = registers[((opcode & 0x0F00) >> 0x8) as usize] % 64;
-            let y = registers[((opcode & 0x00F0) >> 0x4) as usize] % 32;
-            let h = (opcode & 0x000F) as u8;
-
-            //set VF to 0
-            registers[0xF] = 0x0;
-
-            //draw to screen
-            //iterate through each row to draw
-            for n in 0..h {
-                //get row of sprite data from memory at I
-                let row = memory[(index_register + (n as u16)) as usize];
-
-                //loop through each pixel in byte
-                for i in 0..8 {
-                    //check if pixel is to be toggled
-                    if row & (1 << (7 - i)) != 0 {
-                        //get screen memory address
-                        let location =
-                            ((y as usize) + (n as usize)) * 64 + (x as usize) + (i as usize);
-                        //toggle pixel
-                        display[location] = display[location].wrapping_add(1);
-                        if display[location] == 2 {
-                            display[location] = 0;
-                            registers[0xF] = 1;
-                        }
-                    }
+        }
 
-                    //check if horizontal edge reached, if so go to next line
-                    if x + i == 63 {
-                        break;
+        //search memory and change cheats on the cheats screen, saving any changes
+        if open_cheats {
+            match &mut cheats {
+                Ok(cheats) => {
+                    match cheats::run(
+                        canvas,
+                        event_pump,
+                        cheats,
+                        &mut ram_search,
+                        &chip8.memory,
+                        &title,
+                    )? {
+                        Some(true) => match cheats.save() {
+                            Ok(()) => osd.notify("cheats saved".to_string()),
+                            Err(e) => osd.notify(e),
+                        },
+                        Some(false) => {}
+                        None => exit = Some(RomExit::Quit),
                     }
+                    audio_queue.clear();
                 }
+                Err(e) => osd.notify(e.clone()),
+            }
+        }
 
-                //check if vertical edge reached, if so stop drawing
-                if y + n == 31 {
-                    break;
-                }
+        //work out how fast to run, an infinite speed runs as fast as possible
+        let run_speed: f64 = if fast_forward && fast_forward_speed <= 0.0 {
+            f64::INFINITY
+        } else if fast_forward {
+            speed * fast_forward_speed / slow_motion as f64
+        } else {
+            speed / slow_motion as f64
+        };
+        osd.speed = run_speed;
+
+        //run an instruction unless paused, or until the end of the frame when advancing a frame
+        let running: bool = !paused || advance_frame;
+        let frame_ended: bool = running && chip8.step();
+        report_bad_opcode(&mut chip8);
+        if frame_ended {
+            advance_frame = false;
+            if let Ok(cheats) = &cheats {
+                cheats.apply(&mut chip8.memory);
             }
-        } else if (opcode & 0xF0FF) == 0xE09E {
-            //skip next instruction if key in VX is pressed (EX9E)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //check register
-            if registers[reg_num] < 0x10 {
-                //check key
-                if input[registers[reg_num] as usize] == 1 {
-                    program_counter += 2;
-                }
+        }
+        if let Some((start, length)) = chip8.last_write.take() {
+            if let Some(viewer) = &mut memory_viewer {
+                viewer.note_write(start, length);
             }
-        } else if (opcode & 0xF0FF) == 0xE0A1 {
-            //skip next instruction if key in VX is not pressed (EXA1)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //check register
-            if registers[reg_num] < 0x10 {
-                //check key
-                if input[registers[reg_num] as usize] == 0 {
-                    program_counter += 2;
+        }
+        if running {
+            osd.count(frame_ended);
+
+            //sound, note when the sound timer starts or stops and queue the audio at the end of each frame
+            //the queue is not allowed to get too far ahead if the emulator runs faster than real time
+            audio_stream.instruction(chip8.sound_timer != 0);
+        }
+        let samples: Vec<f32> = if frame_ended {
+            audio_stream.end_frame()
+        } else {
+            Vec::new()
+        };
+        if !samples.is_empty()
+            && (audio_queue.size() as usize)
+                < samples.len() * std::mem::size_of::<f32>() * MAX_QUEUED_FRAMES
+        {
+            audio_queue.queue_audio(&samples)?;
+        }
+
+        //record the audio exactly as it was generated, even if the queue was too full to play it
+        if !samples.is_empty() {
+            if let Some(wav) = &mut audio_recording {
+                if let Err(e) = wav.write_samples(&samples) {
+                    eprintln!("{}, audio recording stopped", e);
+                    //finish what was written so far so the file can still be played
+                    if let Some(stopped) = audio_recording.take() {
+                        if let Err(e) = stopped.finish() {
+                            eprintln!("{}", e);
+                        }
+                    }
                 }
             }
-        } else if (opcode & 0xF0FF) == 0xF007 {
-            //set vx to delay timer (FX07)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //update register
-            registers[reg_num] = delay_timer;
-        } else if (opcode & 0xF0FF) == 0xF00A {
-            //await key press and store code in VX (FX0A)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //loop through inputs
-            let mut i = 0;
-            for key in input {
-                //check key press
-                if key == 1 {
-                    registers[reg_num] = i;
-                    program_counter += 2;
-                    break;
-                }
-                i += 1;
+        }
+
+        //redraw screen after every instruction, or only at the end of each frame when filtered
+        //the crt effects are too slow to draw after every instruction so they are drawn every frame
+        //while paused the screen is still redrawn so notifications come and go
+        if frame_ended {
+            filter.push_frame(&chip8.display);
+        }
+        if frame_ended || paused || (filter.redraws_every_instruction() && !crt.enabled) {
+            //filter and upscale the display
+            let frame: Vec<Color> = upscaler.apply(
+                &filter.frame(&chip8.display, &palette),
+                DISPLAY_WIDTH,
+                DISPLAY_HEIGHT,
+            );
+            let frame_width: usize = DISPLAY_WIDTH * upscaler.factor();
+            let frame_height: usize = DISPLAY_HEIGHT * upscaler.factor();
+
+            if crt.enabled {
+                draw_crt_frame(
+                    canvas,
+                    texture_creator,
+                    &mut crt_texture,
+                    (&frame, frame_width, frame_height),
+                    integer_scale,
+                    &crt,
+                )?;
+            } else {
+                draw_frame(
+                    canvas,
+                    (&frame, frame_width, frame_height),
+                    palette.color(0),
+                    integer_scale,
+                )?;
             }
+            osd.draw(canvas)?;
+            canvas.present();
+        }
 
-            //decrement program counter
-            program_counter -= 2;
-        } else if (opcode & 0xF0FF) == 0xF015 {
-            //set delay timer to vx (FX15)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //update timer
-            delay_timer = registers[reg_num];
-        } else if (opcode & 0xF0FF) == 0xF018 {
-            //set sound timer to vx (FX18)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //update timer
-            sound_timer = registers[reg_num];
-        } else if (opcode & 0xF0FF) == 0xF01E {
-            //adds Vx to index register (FX1E)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //update index register
-            index_register += registers[reg_num] as u16;
-        } else if (opcode & 0xF0FF) == 0xF029 {
-            //sets index register to sprite address of char in Vx (FX29)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //update index register
-            index_register = ((registers[reg_num] & 0x0F) as u16) * 5;
-        } else if (opcode & 0xF0FF) == 0xF033 {
-            //store bcd representation of Vx in I, I+1, and I+2 (FX33)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //update index register
-            memory[(index_register) as usize] = registers[reg_num] / 100;
-            memory[(index_register + 1) as usize] =
-                (registers[reg_num] - 100 * (registers[reg_num] / 100)) / 10;
-            memory[(index_register + 2) as usize] =
-                registers[reg_num] - 10 * (registers[reg_num] / 10);
-        } else if (opcode & 0xF0FF) == 0xF055 {
-            //store V0 to Vx in index register to index register + X (FX55)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //update index register
-            for i in 0..(reg_num + 1) {
-                memory[(index_register as usize) + i] = registers[i];
+        //update the debug windows once a frame, or while paused so edits show
+        if frame_ended || paused {
+            if let Some(viewer) = &mut memory_viewer {
+                viewer.draw(&chip8, paused)?;
             }
-        } else if (opcode & 0xF0FF) == 0xF065 {
-            //fill V0 to Vx from index register to index register + X (FX65)
-            //get register number
-            let reg_num = ((opcode & 0x0F00) >> 0x8) as usize;
-
-            //update index register
-            for i in 0..(reg_num + 1) {
-                registers[i] = memory[(index_register as usize) + i];
+            if let Some(viewer) = &mut sprite_viewer {
+                viewer.draw(&chip8)?;
             }
-        } else {
-            println!("{:#06x} opcode does not exist", opcode)
-        }
-
-        //increment program counter
-        program_counter = program_counter.wrapping_add(2);
-
-        //redraw screen
-        //clear canvas as black, and set color to white
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-
-        //loop through each pixel line
-        for y in 0..(32 as usize) {
-            //loop through each pixel in line and draw
-            for x in 0..(64 as usize) {
-                //get pixel value and draw
-                let pixel = display[y * 64 + x];
-                if pixel > 0 {
-                    let _ = canvas.fill_rect(Rect::new((x * 20) as i32, (y * 20) as i32, 20, 20));
+        }
+
+        //record the frame
+        if frame_ended {
+            if let Some(active) = &mut recorder {
+                let result: Result<(), String> =
+                    active.add_frame(&chip8.display, &palette, &samples, || {
+                        screenshot_image(
+                            &chip8.display,
+                            &filter,
+                            upscaler,
+                            &palette,
+                            &crt,
+                            false,
+                            record_size,
+                        )
+                        .0
+                    });
+                if let Err(e) = result {
+                    eprintln!("{}, recording stopped", e);
+                    //finish what was written so far so the file can still be played
+                    if let Some(stopped) = recorder.take() {
+                        if let Err(e) = stopped.finish() {
+                            eprintln!("{}", e);
+                        }
+                    }
                 }
             }
         }
 
-        //timer management
-        if timer_counter == 7 {
-            if delay_timer > 0 {
-                delay_timer = delay_timer.wrapping_sub(1);
+        //keep to the rom's speed by sleeping until the next instruction is due, so time lost to
+        //sleeping too long or drawing is made up on the following instructions
+        //while paused sleep for about a frame
+        if paused && !advance_frame {
+            ::std::thread::sleep(Duration::from_millis(16));
+            next_instruction = Instant::now();
+        } else if run_speed.is_infinite() {
+            next_instruction = Instant::now();
+        } else {
+            next_instruction += Duration::from_secs_f64(
+                1.0 / (FRAMES_PER_SECOND * chip8.instructions_per_frame as f64 * run_speed),
+            );
+            let now: Instant = Instant::now();
+            if next_instruction > now {
+                ::std::thread::sleep(next_instruction - now);
+            } else if now - next_instruction > MAX_LAG {
+                next_instruction = now;
             }
-            if sound_timer > 0 {
-                sound_timer = sound_timer.wrapping_sub(1);
+        }
+    }
+
+    //finish any recording
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    if let Some(wav) = audio_recording {
+        wav.finish()?;
+    }
+
+    Ok(exit.unwrap_or(RomExit::Quit))
+}
+
+//draw a frame of pixel colors to the canvas
+//the frame is given with its width and height as it may be upscaled from the display
+fn draw_frame(
+    canvas: &mut Canvas<Window>,
+    (frame, frame_width, frame_height): (&[Color], usize, usize),
+    background: Color,
+    integer_scale: bool,
+) -> Result<(), String> {
+    //get the area of the window to draw the display in
+    let (output_width, output_height): (u32, u32) = canvas.output_size()?;
+    let area: Rect = viewport::fit(
+        output_width,
+        output_height,
+        DISPLAY_WIDTH as u32,
+        DISPLAY_HEIGHT as u32,
+        integer_scale,
+    );
+
+    //clear canvas as black for the letterbox, and fill the display area with the background color
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.set_draw_color(background);
+    canvas.fill_rect(area)?;
+
+    //loop through each pixel line
+    for y in 0..frame_height {
+        //loop through each pixel in line and draw
+        for x in 0..frame_width {
+            //get pixel color and draw if it is not the background
+            let color: Color = frame[y * frame_width + x];
+            if color != background {
+                canvas.set_draw_color(color);
+                canvas.fill_rect(viewport::pixel_rect(
+                    area,
+                    frame_width as u32,
+                    frame_height as u32,
+                    x,
+                    y,
+                ))?;
             }
-            timer_counter = 0;
-        } else {
-            timer_counter += 1;
         }
+    }
 
-        //sound
-        if sound_timer != 0 {
-            device.resume();
-        } else {
-            device.pause();
+    Ok(())
+}
+
+//draw a frame of pixel colors to the canvas with the crt effects applied
+//the effects are drawn in software to a texture the size of the display area
+fn draw_crt_frame<'a>(
+    canvas: &mut Canvas<Window>,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    texture: &mut Option<Texture<'a>>,
+    (frame, frame_width, frame_height): (&[Color], usize, usize),
+    integer_scale: bool,
+    settings: &CrtSettings,
+) -> Result<(), String> {
+    //get the area of the window to draw the display in
+    let (output_width, output_height): (u32, u32) = canvas.output_size()?;
+    let area: Rect = viewport::fit(
+        output_width,
+        output_height,
+        DISPLAY_WIDTH as u32,
+        DISPLAY_HEIGHT as u32,
+        integer_scale,
+    );
+
+    //make a new texture if the display area changed size
+    let size_changed: bool = match texture {
+        Some(texture) => {
+            let query = texture.query();
+            query.width != area.width() || query.height != area.height()
         }
+        None => true,
+    };
+    if size_changed {
+        *texture = Some(
+            texture_creator
+                .create_texture_streaming(PixelFormatEnum::RGB24, area.width(), area.height())
+                .map_err(|e| e.to_string())?,
+        );
+    }
+
+    //draw the effects and copy them to the texture
+    let image: Vec<u8> = crt::apply(
+        frame,
+        frame_width,
+        frame_height,
+        DISPLAY_WIDTH,
+        DISPLAY_HEIGHT,
+        area.width() as usize,
+        area.height() as usize,
+        settings,
+    );
+    let texture: &mut Texture = texture.as_mut().unwrap();
+    texture
+        .update(None, &image, area.width() as usize * 3)
+        .map_err(|e| e.to_string())?;
 
-        //canvas redisplay and sleep
-        canvas.present();
-        ::std::thread::sleep(Duration::new(0, 2_000_000_u32));
+    //clear canvas as black for the letterbox and draw the texture
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.copy(texture, None, area)
+}
+
+//get the screen as rgb24 bytes for a screenshot, along with its width and height
+//the raw screenshot is the display in the current palette with one pixel per emulated pixel,
+//otherwise it is filtered, upscaled and drawn at the given size like the window
+fn screenshot_image(
+    display: &[u8],
+    filter: &DisplayFilter,
+    upscaler: Upscaler,
+    palette: &Palette,
+    crt: &CrtSettings,
+    raw: bool,
+    (width, height): (usize, usize),
+) -> (Vec<u8>, usize, usize) {
+    if raw {
+        let frame: Vec<Color> = display.iter().map(|pixel| palette.color(*pixel)).collect();
+        return (screenshot::to_rgb(&frame), DISPLAY_WIDTH, DISPLAY_HEIGHT);
     }
 
+    let frame: Vec<Color> = upscaler.apply(
+        &filter.frame(display, palette),
+        DISPLAY_WIDTH,
+        DISPLAY_HEIGHT,
+    );
+    let image: Vec<u8> = screenshot::render(
+        (
+            &frame,
+            DISPLAY_WIDTH * upscaler.factor(),
+            DISPLAY_HEIGHT * upscaler.factor(),
+        ),
+        (DISPLAY_WIDTH, DISPLAY_HEIGHT),
+        width,
+        height,
+        crt,
+    );
+    (image, width, height)
+}
+
+//get the chip 8 key and whether it was pressed for an arrow key or controller button event
+//the arrow keys and d-pad are up, down, left and right, space and the a button are a,
+//and left shift and the b button are b, as named in the rom database
+fn mapped_button(keys: &BTreeMap<String, u8>, event: &Event) -> Option<(usize, bool)> {
+    let (name, pressed): (&str, bool) = match event {
+        Event::KeyDown {
+            keycode: Some(keycode),
+            ..
+        }
+        | Event::KeyUp {
+            keycode: Some(keycode),
+            ..
+        } => {
+            let name: &str = match *keycode {
+                Keycode::Up => "up",
+                Keycode::Down => "down",
+                Keycode::Left => "left",
+                Keycode::Right => "right",
+                Keycode::Space => "a",
+                Keycode::LShift => "b",
+                _ => return None,
+            };
+            (name, matches!(event, Event::KeyDown { .. }))
+        }
+        Event::ControllerButtonDown { button, .. } | Event::ControllerButtonUp { button, .. } => {
+            let name: &str = match button {
+                Button::DPadUp => "up",
+                Button::DPadDown => "down",
+                Button::DPadLeft => "left",
+                Button::DPadRight => "right",
+                Button::A => "a",
+                Button::B => "b",
+                _ => return None,
+            };
+            (name, matches!(event, Event::ControllerButtonDown { .. }))
+        }
+        _ => return None,
+    };
+    let key: u8 = *keys.get(name)?;
+    Some(((key & 0xF) as usize, pressed))
+}
+
+//size the window to show each emulated pixel at a scale, unless it is fullscreen
+fn resize_window(canvas: &mut Canvas<Window>, scale: u32) -> Result<(), String> {
+    let window: &mut Window = canvas.window_mut();
+    if window.fullscreen_state() == FullscreenType::Off {
+        window
+            .set_size(DISPLAY_WIDTH as u32 * scale, DISPLAY_HEIGHT as u32 * scale)
+            .map_err(|e| e.to_string())?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use database::QuirkOverrides;
+
+    //a rom listed in the test database as a superchip rom with its own colors
+    const ROM: [u8; 4] = [0x00, 0xE0, 0x12, 0x00];
+
+    fn database() -> Database {
+        Database::parse(&format!(
+            r##"[{{
+                "title": "Test Program",
+                "roms": {{
+                    "{}": {{
+                        "platforms": ["superchip"],
+                        "colors": {{ "pixels": ["#000000", "#ff0000"] }}
+                    }}
+                }}
+            }}]"##,
+            config::rom_hash(&ROM)
+        ))
+        .unwrap()
+    }
+
+    //global settings, and overrides for the rom when asked for
+    fn config(rom_section: bool) -> Config {
+        let mut config: Config = Config {
+            palette: Some("green".to_string()),
+            scale: Some(6),
+            tickrate: Some(20),
+            quirks: Some(QuirkOverrides {
+                vblank: Some(true),
+                ..QuirkOverrides::default()
+            }),
+            ..Config::default()
+        };
+        if rom_section {
+            *config.rom_mut(&config::rom_hash(&ROM)) = RomConfig {
+                palette: Some("amber".to_string()),
+                scale: Some(7),
+                tickrate: Some(25),
+                quirks: Some(QuirkOverrides {
+                    jump: Some(false),
+                    ..QuirkOverrides::default()
+                }),
+                ..RomConfig::default()
+            };
+        }
+        config
+    }
+
+    //pick the settings for the rom with options from a command line
+    fn setup(line: &str, config: &Config, database: &Database) -> RomSetup {
+        let args: Vec<String> = ["chip8_emulator", "game.ch8"]
+            .into_iter()
+            .chain(line.split_whitespace())
+            .map(str::to_string)
+            .collect();
+        let options: Options = match cli::parse_args(&args) {
+            Ok(Parsed::Options(options)) => *options,
+            _ => panic!("\"{}\" did not give options", line),
+        };
+        rom_setup(&options, config, database, "game.ch8", &ROM)
+    }
+
+    #[test]
+    fn global_settings_are_used_for_unknown_roms() {
+        let picked: RomSetup = setup("", &config(false), &Database::default());
+        assert_eq!(picked.title, "game");
+        assert_eq!(picked.palette.name, "green");
+        assert_eq!(picked.scale, 6);
+        assert_eq!(picked.instructions_per_frame, 20);
+        assert_eq!(
+            picked.quirks,
+            Quirks {
+                vblank: true,
+                ..Quirks::default()
+            }
+        );
+
+        let picked: RomSetup = setup("", &Config::default(), &Database::default());
+        assert_eq!(picked.palette.name, "classic");
+        assert_eq!(picked.scale, DEFAULT_SCALE);
+        assert_eq!(picked.instructions_per_frame, INSTRUCTIONS_PER_FRAME);
+    }
+
+    #[test]
+    fn the_database_comes_before_global_settings() {
+        let picked: RomSetup = setup("", &config(false), &database());
+        let (quirks, tickrate): (Quirks, u32) = database::platform_defaults("superchip").unwrap();
+        assert_eq!(picked.title, "Test Program");
+        assert_eq!(picked.palette.name, "custom");
+        assert_eq!(picked.palette.colors[1], Color::RGB(0xFF, 0, 0));
+        assert_eq!(picked.instructions_per_frame, tickrate);
+        assert_eq!(picked.quirks, quirks);
+        //the database has no window scale
+        assert_eq!(picked.scale, 6);
+
+        //unless it is turned off
+        let picked: RomSetup = setup("--no-database", &config(false), &database());
+        assert_eq!(picked.palette.name, "green");
+        assert_eq!(picked.instructions_per_frame, 20);
+    }
+
+    #[test]
+    fn the_rom_section_comes_before_the_database() {
+        let picked: RomSetup = setup("", &config(true), &database());
+        let (quirks, _): (Quirks, u32) = database::platform_defaults("superchip").unwrap();
+        assert_eq!(picked.palette.name, "amber");
+        assert_eq!(picked.scale, 7);
+        assert_eq!(picked.instructions_per_frame, 25);
+        assert_eq!(
+            picked.quirks,
+            Quirks {
+                jump: false,
+                ..quirks
+            }
+        );
+    }
+
+    #[test]
+    fn the_command_line_comes_first() {
+        let picked: RomSetup = setup(
+            "--palette lcd --scale 9 --tickrate 40 --quirk jump=on --quirk-preset originalChip8",
+            &config(true),
+            &database(),
+        );
+        let (quirks, _): (Quirks, u32) = database::platform_defaults("originalChip8").unwrap();
+        assert_eq!(picked.palette.name, "lcd");
+        assert_eq!(picked.scale, 9);
+        assert_eq!(picked.instructions_per_frame, 40);
+        assert_eq!(
+            picked.quirks,
+            Quirks {
+                jump: true,
+                ..quirks
+            }
+        );
+    }
+}
@@ -0,0 +1,351 @@
+//use declarations
+use crate::chip8::Chip8;
+use crate::osd::{self, GLYPH_HEIGHT, GLYPH_WIDTH, SPACING};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+use sdl2::VideoSubsystem;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+//bytes shown on each row, and rows shown in the window
+const ROW_BYTES: usize = 16;
+const ROWS: usize = 40;
+
+//characters on each row, the address, the bytes in hex and the bytes as text
+const ADDRESS_CHARACTERS: usize = 6;
+const ROW_CHARACTERS: usize = ADDRESS_CHARACTERS + ROW_BYTES * 3 + 1 + ROW_BYTES;
+
+//lines above the rows for the registers and the keys
+const HEADER_LINES: usize = 2;
+
+//size of a font pixel in window pixels
+const FONT_SIZE: i32 = 2;
+
+//end of the font data at the start of memory
+const FONT_END: usize = 0x50;
+
+//how long writes by FX33 and FX55 stay highlighted
+const FLASH_TIME: Duration = Duration::from_millis(600);
+
+//highlight colors
+const FONT_COLOR: Color = Color::RGB(0, 0, 96);
+const ROM_COLOR: Color = Color::RGB(0, 56, 0);
+const PC_COLOR: Color = Color::RGB(0, 150, 0);
+const INDEX_COLOR: Color = Color::RGB(150, 0, 150);
+
+//memory viewer struct and methods
+//shows memory as hex and text in its own window, and lets bytes be typed in while paused
+pub struct MemoryViewer {
+    canvas: Canvas<Window>,
+    //addresses the rom was loaded at
+    pub rom_range: Range<usize>,
+    //first row shown, and whether the rows scroll to keep the program counter in sight
+    top_row: usize,
+    follow_pc: bool,
+    //selected byte, and the high digit typed for it so far
+    cursor: usize,
+    typed: Option<u8>,
+    //memory written by FX33 and FX55, with when it was written
+    flashes: Vec<(Range<usize>, Instant)>,
+    closed: bool,
+}
+
+impl MemoryViewer {
+    //open the viewer window
+    pub fn open(video: &VideoSubsystem, rom_range: Range<usize>) -> Result<MemoryViewer, String> {
+        let width: u32 = ((ROW_CHARACTERS as i32 + 2) * advance()) as u32;
+        let height: u32 = ((ROWS + HEADER_LINES + 1) as i32 * line_height()) as u32;
+        let window: Window = video
+            .window("Memory - Chip 8 Emulator", width, height)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let canvas: Canvas<Window> = window.into_canvas().build().map_err(|e| e.to_string())?;
+        Ok(MemoryViewer {
+            canvas,
+            cursor: rom_range.start,
+            rom_range,
+            top_row: 0,
+            follow_pc: true,
+            typed: None,
+            flashes: Vec::new(),
+            closed: false,
+        })
+    }
+
+    //id of the viewer window, to tell which events are for it
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    //check if the window was closed
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+
+    //select an address and scroll to it, no longer following the program counter
+    pub fn show(&mut self, address: usize) {
+        self.cursor = address & 0xFFF;
+        self.typed = None;
+        self.follow_pc = false;
+        self.scroll_to(self.cursor);
+    }
+
+    //highlight memory that was just written
+    pub fn note_write(&mut self, start: usize, length: usize) {
+        self.flashes.push((start..start + length, Instant::now()));
+    }
+
+    //handle an event for the viewer window, returns false for keys it does not use
+    //so the hotkeys still work from the viewer
+    pub fn handle_event(&mut self, event: &Event, chip8: &mut Chip8, paused: bool) -> bool {
+        match event {
+            Event::Window {
+                win_event: WindowEvent::Close,
+                ..
+            } => self.closed = true,
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match *keycode {
+                Keycode::Escape => self.closed = true,
+                Keycode::Up => self.move_cursor(-(ROW_BYTES as isize)),
+                Keycode::Down => self.move_cursor(ROW_BYTES as isize),
+                Keycode::Left => self.move_cursor(-1),
+                Keycode::Right => self.move_cursor(1),
+                Keycode::PageUp => self.move_cursor(-((ROWS * ROW_BYTES) as isize)),
+                Keycode::PageDown => self.move_cursor((ROWS * ROW_BYTES) as isize),
+                Keycode::P => self.follow_pc = !self.follow_pc,
+                Keycode::I => self.show(chip8.index_register as usize),
+                _ => match hex_digit(*keycode) {
+                    //bytes can only be changed while paused
+                    Some(digit) if paused => self.type_digit(chip8, digit),
+                    Some(_) => {}
+                    None => return false,
+                },
+            },
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                if let Some(address) = self.address_at(*x, *y) {
+                    self.cursor = address;
+                    self.typed = None;
+                }
+            }
+            Event::MouseWheel { y, .. } => {
+                let rows: isize = self.top_row as isize - *y as isize * 3;
+                self.top_row = rows.clamp(0, (0x1000 / ROW_BYTES - ROWS) as isize) as usize;
+                self.follow_pc = false;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    //draw the viewer
+    pub fn draw(&mut self, chip8: &Chip8, paused: bool) -> Result<(), String> {
+        if self.follow_pc {
+            self.scroll_to(chip8.program_counter);
+        }
+        self.flashes
+            .retain(|(_, written)| written.elapsed() < FLASH_TIME);
+
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        let white: Color = Color::RGB(255, 255, 255);
+
+        //registers and keys at the top
+        let header: [String; HEADER_LINES] = [
+            format!(
+                "PC {:03X}  I {:03X}  SP {:02X}  DT {:02X}  ST {:02X}  AT {:03X}: {:02X}",
+                chip8.program_counter,
+                chip8.index_register,
+                chip8.stack_index,
+                chip8.delay_timer,
+                chip8.sound_timer,
+                self.cursor,
+                chip8.memory[self.cursor]
+            ),
+            format!(
+                "P FOLLOW PC {}  I GO TO I  {}",
+                if self.follow_pc { "ON" } else { "OFF" },
+                if paused {
+                    "TYPE HEX TO EDIT"
+                } else {
+                    "PAUSE TO EDIT"
+                }
+            ),
+        ];
+        for (line, text) in header.iter().enumerate() {
+            osd::draw_glyphs(
+                &mut self.canvas,
+                text,
+                FONT_SIZE,
+                advance(),
+                line as i32 * line_height(),
+                white,
+            )?;
+        }
+
+        for row in 0..ROWS {
+            let row_address: usize = (self.top_row + row) * ROW_BYTES;
+            if row_address >= chip8.memory.len() {
+                break;
+            }
+            let y: i32 = (row + HEADER_LINES) as i32 * line_height();
+
+            //highlights behind the bytes, in the hex and text columns
+            for byte in 0..ROW_BYTES {
+                let address: usize = row_address + byte;
+                let hex_cell: Rect = self.cell(ADDRESS_CHARACTERS + byte * 3, 2, y);
+                let text_cell: Rect = self.cell(ROW_CHARACTERS - ROW_BYTES + byte, 1, y);
+                if let Some(color) = self.background(address, chip8) {
+                    self.canvas.set_draw_color(color);
+                    self.canvas.fill_rects(&[hex_cell, text_cell])?;
+                }
+
+                //writes fade out
+                let flash: Option<Duration> = self
+                    .flashes
+                    .iter()
+                    .filter(|(range, _)| range.contains(&address))
+                    .map(|(_, written)| written.elapsed())
+                    .min();
+                if let Some(elapsed) = flash {
+                    let fade: f64 = 1.0 - elapsed.as_secs_f64() / FLASH_TIME.as_secs_f64();
+                    self.canvas.set_blend_mode(BlendMode::Blend);
+                    self.canvas
+                        .set_draw_color(Color::RGBA(255, 200, 0, (fade * 255.0) as u8));
+                    self.canvas.fill_rects(&[hex_cell, text_cell])?;
+                    self.canvas.set_blend_mode(BlendMode::None);
+                }
+
+                if address == self.cursor {
+                    self.canvas.set_draw_color(white);
+                    self.canvas.draw_rect(hex_cell)?;
+                }
+            }
+
+            //address, hex, and printable bytes as text
+            let mut text: String = format!("{:04X}  ", row_address);
+            for address in row_address..row_address + ROW_BYTES {
+                match self.typed {
+                    Some(high) if address == self.cursor => text.push_str(&format!("{:X}_ ", high)),
+                    _ => text.push_str(&format!("{:02X} ", chip8.memory[address])),
+                }
+            }
+            text.push(' ');
+            for address in row_address..row_address + ROW_BYTES {
+                let byte: u8 = chip8.memory[address];
+                text.push(if (0x20..0x7F).contains(&byte) {
+                    byte as char
+                } else {
+                    '.'
+                });
+            }
+            osd::draw_glyphs(&mut self.canvas, &text, FONT_SIZE, advance(), y, white)?;
+        }
+
+        self.canvas.present();
+        Ok(())
+    }
+
+    //move the selected byte and scroll to it
+    fn move_cursor(&mut self, amount: isize) {
+        let address: isize = (self.cursor as isize + amount).clamp(0, 0xFFF);
+        self.show(address as usize);
+    }
+
+    //scroll so an address is in sight
+    fn scroll_to(&mut self, address: usize) {
+        let row: usize = (address & 0xFFF) / ROW_BYTES;
+        if row < self.top_row {
+            self.top_row = row;
+        } else if row >= self.top_row + ROWS {
+            self.top_row = row + 1 - ROWS;
+        }
+    }
+
+    //type a hex digit into the selected byte, the byte is written once both digits are typed
+    fn type_digit(&mut self, chip8: &mut Chip8, digit: u8) {
+        match self.typed.take() {
+            None => self.typed = Some(digit),
+            Some(high) => {
+                chip8.memory[self.cursor] = (high << 4) | digit;
+                self.note_write(self.cursor, 1);
+                self.move_cursor(1);
+            }
+        }
+    }
+
+    //get the highlight color for an address
+    fn background(&self, address: usize, chip8: &Chip8) -> Option<Color> {
+        if address == chip8.program_counter || address == chip8.program_counter + 1 {
+            Some(PC_COLOR)
+        } else if address == chip8.index_register as usize {
+            Some(INDEX_COLOR)
+        } else if self.rom_range.contains(&address) {
+            Some(ROM_COLOR)
+        } else if address < FONT_END {
+            Some(FONT_COLOR)
+        } else {
+            None
+        }
+    }
+
+    //get the rectangle around characters on a row
+    fn cell(&self, column: usize, characters: usize, y: i32) -> Rect {
+        Rect::new(
+            (column as i32 + 1) * advance(),
+            y,
+            (characters as i32 * advance() + SPACING * FONT_SIZE) as u32,
+            line_height() as u32,
+        )
+    }
+
+    //get the address of the byte under a point in the window, in the hex or text columns
+    fn address_at(&self, x: i32, y: i32) -> Option<usize> {
+        let row: i32 = y / line_height() - HEADER_LINES as i32;
+        let column: i32 = x / advance() - 1;
+        if !(0..ROWS as i32).contains(&row) {
+            return None;
+        }
+        let hex_start: i32 = ADDRESS_CHARACTERS as i32;
+        let text_start: i32 = (ROW_CHARACTERS - ROW_BYTES) as i32;
+        let byte: i32 = if (hex_start..hex_start + ROW_BYTES as i32 * 3).contains(&column) {
+            (column - hex_start) / 3
+        } else if (text_start..text_start + ROW_BYTES as i32).contains(&column) {
+            column - text_start
+        } else {
+            return None;
+        };
+        let address: usize = (self.top_row + row as usize) * ROW_BYTES + byte as usize;
+        (address < 0x1000).then_some(address)
+    }
+}
+
+//width of a character in window pixels
+fn advance() -> i32 {
+    (GLYPH_WIDTH + SPACING) * FONT_SIZE
+}
+
+//height of a line in window pixels
+fn line_height() -> i32 {
+    (GLYPH_HEIGHT + SPACING * 2) * FONT_SIZE
+}
+
+//get the hex digit for a key, 0 to 9 and A to F
+fn hex_digit(keycode: Keycode) -> Option<u8> {
+    let name: String = keycode.name();
+    if name.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(&name, 16).ok()
+}
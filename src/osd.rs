@@ -0,0 +1,227 @@
+//use declarations
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+use std::time::{Duration, Instant};
+
+//how long a notification stays on screen
+const MESSAGE_TIME: Duration = Duration::from_secs(2);
+
+//how often the measured rates are updated
+const MEASURE_TIME: Duration = Duration::from_millis(500);
+
+//font glyph size in font pixels, and the gap between characters and lines
+pub const GLYPH_WIDTH: i32 = 3;
+pub const GLYPH_HEIGHT: i32 = 5;
+pub const SPACING: i32 = 1;
+
+//on screen display struct and methods
+//shows the measured speed in the top left corner and notifications in the bottom left
+pub struct Osd {
+    pub show_stats: bool,
+    message: Option<(String, Instant)>,
+    //instructions and frames counted since the rates were last measured
+    measure_start: Instant,
+    instructions: u32,
+    frames: u32,
+    fps: f64,
+    ips: f64,
+    //speed multiplier and paused state, shown with the stats
+    //an infinite speed means the emulator is running as fast as it can
+    pub speed: f64,
+    pub paused: bool,
+}
+
+impl Osd {
+    pub fn new(show_stats: bool) -> Osd {
+        Osd {
+            show_stats,
+            message: None,
+            measure_start: Instant::now(),
+            instructions: 0,
+            frames: 0,
+            fps: 0.0,
+            ips: 0.0,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    //show or hide the stats, returning whether they are shown
+    pub fn toggle_stats(&mut self) -> bool {
+        self.show_stats = !self.show_stats;
+        self.show_stats
+    }
+
+    //show a message for a couple of seconds, and print it too
+    pub fn notify(&mut self, text: String) {
+        eprintln!("{}", text);
+        self.message = Some((text, Instant::now()));
+    }
+
+    //count an instruction run, and whether it ended a frame
+    pub fn count(&mut self, frame_ended: bool) {
+        self.instructions += 1;
+        if frame_ended {
+            self.frames += 1;
+        }
+        let elapsed: Duration = self.measure_start.elapsed();
+        if elapsed >= MEASURE_TIME {
+            self.ips = self.instructions as f64 / elapsed.as_secs_f64();
+            self.fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.instructions = 0;
+            self.frames = 0;
+            self.measure_start = Instant::now();
+        }
+    }
+
+    //draw the display over the game
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        //size the font to the window
+        let (_, output_height): (u32, u32) = canvas.output_size()?;
+        let size: i32 = (output_height as i32 / 160).max(1);
+        let line_height: i32 = (GLYPH_HEIGHT + SPACING * 2) * size;
+
+        //stats in the top left
+        if self.show_stats {
+            let mut lines: Vec<String> = vec![
+                format!("FPS {:.1}", self.fps),
+                format!("IPS {:.0}", self.ips),
+                if self.speed.is_finite() {
+                    format!("SPEED {:.2}X", self.speed)
+                } else {
+                    "SPEED MAX".to_string()
+                },
+            ];
+            if self.paused {
+                lines.push("PAUSED".to_string());
+            }
+            for (i, line) in lines.iter().enumerate() {
+                draw_text(canvas, line, size, size, size + i as i32 * line_height)?;
+            }
+        }
+
+        //the latest notification in the bottom left until it times out
+        if let Some((text, shown)) = &self.message {
+            if shown.elapsed() < MESSAGE_TIME {
+                draw_text(canvas, text, size, size, output_height as i32 - line_height)?;
+            } else {
+                self.message = None;
+            }
+        }
+        Ok(())
+    }
+}
+
+//draw a line of text on a dark box, each font pixel is size window pixels
+pub fn draw_text(
+    canvas: &mut Canvas<Window>,
+    text: &str,
+    size: i32,
+    x: i32,
+    y: i32,
+) -> Result<(), String> {
+    //box behind the text so it can be read over any picture
+    let advance: i32 = (GLYPH_WIDTH + SPACING) * size;
+    let width: u32 = (text.chars().count() as i32 * advance + SPACING * size) as u32;
+    let height: u32 = ((GLYPH_HEIGHT + SPACING * 2) * size) as u32;
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+    canvas.fill_rect(Rect::new(x, y, width, height))?;
+    canvas.set_blend_mode(BlendMode::None);
+    draw_glyphs(canvas, text, size, x, y, Color::RGB(255, 255, 255))
+}
+
+//draw a line of text without a box behind it, in the same place draw_text puts it
+pub fn draw_glyphs(
+    canvas: &mut Canvas<Window>,
+    text: &str,
+    size: i32,
+    x: i32,
+    y: i32,
+    color: Color,
+) -> Result<(), String> {
+    //collect the lit font pixels and draw them together
+    let advance: i32 = (GLYPH_WIDTH + SPACING) * size;
+    let mut rects: Vec<Rect> = Vec::new();
+    for (i, character) in text.chars().enumerate() {
+        let left: i32 = x + SPACING * size + i as i32 * advance;
+        let top: i32 = y + SPACING * size;
+        for (row, bits) in glyph(character).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> column) != 0 {
+                    rects.push(Rect::new(
+                        left + column * size,
+                        top + row as i32 * size,
+                        size as u32,
+                        size as u32,
+                    ));
+                }
+            }
+        }
+    }
+    canvas.set_draw_color(color);
+    canvas.fill_rects(&rects)
+}
+
+//get the rows of a 3x5 font glyph, lowercase letters are drawn as uppercase
+fn glyph(character: char) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '"' => [0b101, 0b101, 0b000, 0b000, 0b000],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
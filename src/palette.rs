@@ -0,0 +1,220 @@
+//use declarations
+use sdl2::pixels::Color;
+
+//names of the built in palettes, in the order the palette hotkey cycles through them
+pub const PRESET_NAMES: [&str; 4] = ["classic", "green", "amber", "lcd"];
+
+//palette struct and methods
+//colors are indexed by pixel value: 0 is the background, 1 is the first plane,
+//2 is the second plane, and 3 is where both planes are lit
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [Color; 4],
+}
+
+impl Palette {
+    //get a built in palette by name
+    pub fn preset(name: &str) -> Option<Palette> {
+        let colors: [Color; 4] = match name {
+            //black and white like the original program
+            "classic" => [
+                Color::RGB(0x00, 0x00, 0x00),
+                Color::RGB(0xFF, 0xFF, 0xFF),
+                Color::RGB(0xAA, 0xAA, 0xAA),
+                Color::RGB(0x55, 0x55, 0x55),
+            ],
+            //green phosphor monitor
+            "green" => [
+                Color::RGB(0x00, 0x14, 0x00),
+                Color::RGB(0x33, 0xFF, 0x33),
+                Color::RGB(0x1A, 0x99, 0x1A),
+                Color::RGB(0xB3, 0xFF, 0xB3),
+            ],
+            //amber phosphor monitor
+            "amber" => [
+                Color::RGB(0x1A, 0x0E, 0x00),
+                Color::RGB(0xFF, 0xB0, 0x00),
+                Color::RGB(0x99, 0x66, 0x00),
+                Color::RGB(0xFF, 0xDD, 0x88),
+            ],
+            //green tinted lcd screen
+            "lcd" => [
+                Color::RGB(0x9B, 0xBC, 0x0F),
+                Color::RGB(0x0F, 0x38, 0x0F),
+                Color::RGB(0x30, 0x62, 0x30),
+                Color::RGB(0x8B, 0xAC, 0x0F),
+            ],
+            _ => return None,
+        };
+
+        Some(Palette {
+            name: name.to_string(),
+            colors,
+        })
+    }
+
+    //build a user defined palette from 2 to 4 hex colors
+    //missing plane colors are filled in from the first plane color
+    pub fn custom(hex_colors: &[String]) -> Result<Palette, String> {
+        if hex_colors.len() < 2 || hex_colors.len() > 4 {
+            return Err(format!(
+                "a custom palette needs 2 to 4 colors, got {}",
+                hex_colors.len()
+            ));
+        }
+
+        //parse each color
+        let mut parsed: Vec<Color> = Vec::new();
+        for hex in hex_colors {
+            parsed.push(parse_hex_color(hex)?);
+        }
+
+        //fill in unused plane colors
+        let foreground: Color = parsed[1];
+        let colors: [Color; 4] = [
+            parsed[0],
+            parsed[1],
+            *parsed.get(2).unwrap_or(&foreground),
+            *parsed.get(3).unwrap_or(&foreground),
+        ];
+
+        Ok(Palette {
+            name: "custom".to_string(),
+            colors,
+        })
+    }
+
+    //get the color used to draw a pixel value
+    pub fn color(&self, pixel: u8) -> Color {
+        self.colors[(pixel & 0x3) as usize]
+    }
+}
+
+//list the palettes the hotkey can cycle through, the custom palette is only included if set
+pub fn cycle_names(has_custom: bool) -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = PRESET_NAMES.to_vec();
+    if has_custom {
+        names.push("custom");
+    }
+    names
+}
+
+//get a palette by name, falling back to the classic palette if it does not exist
+pub fn select(name: &str, custom_palette: &Option<Palette>) -> Palette {
+    if name == "custom" {
+        if let Some(palette) = custom_palette {
+            return palette.clone();
+        }
+    }
+    Palette::preset(name).unwrap_or_else(|| {
+        eprintln!("unknown palette \"{}\", using classic", name);
+        Palette::preset("classic").unwrap()
+    })
+}
+
+//parse a color written as RRGGBB or #RRGGBB
+pub fn parse_hex_color(hex: &str) -> Result<Color, String> {
+    let digits: &str = hex.trim().trim_start_matches('#');
+    //from_str_radix would take a sign, so check the digits first
+    if digits.len() != 6 || !digits.chars().all(|digit| digit.is_ascii_hexdigit()) {
+        return Err(format!("invalid color \"{}\", expected #RRGGBB", hex));
+    }
+
+    //parse the whole value and split into channels
+    let value: u32 = u32::from_str_radix(digits, 16)
+        .map_err(|_| format!("invalid color \"{}\", expected #RRGGBB", hex))?;
+    Ok(Color::RGB(
+        (value >> 16) as u8,
+        (value >> 8) as u8,
+        value as u8,
+    ))
+}
+
+//mix two colors, an amount of 0 gives the first color and 1 gives the second
+pub fn blend_color(from: Color, to: Color, amount: f32) -> Color {
+    let mix = |a: u8, b: u8| -> u8 { (a as f32 + (b as f32 - a as f32) * amount).round() as u8 };
+    Color::RGB(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(colors: &[&str]) -> Vec<String> {
+        colors.iter().map(|color| color.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_hex_colors_with_or_without_a_hash() {
+        assert_eq!(parse_hex_color("#FF8000"), Ok(Color::RGB(0xFF, 0x80, 0x00)));
+        assert_eq!(parse_hex_color("0a1B2c"), Ok(Color::RGB(0x0A, 0x1B, 0x2C)));
+        assert_eq!(
+            parse_hex_color(" #123456 "),
+            Ok(Color::RGB(0x12, 0x34, 0x56))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_hex_colors() {
+        for hex in [
+            "", "#", "#FFF", "FFFFF", "#FFFFFFF", "#GG0000", "+FFFFF", "#-FFFFF", "FF 000",
+        ] {
+            assert!(parse_hex_color(hex).is_err(), "{} was accepted", hex);
+        }
+    }
+
+    #[test]
+    fn fills_in_missing_plane_colors() {
+        let palette: Palette = Palette::custom(&strings(&["#000000", "#FFFFFF"])).unwrap();
+        assert_eq!(palette.name, "custom");
+        assert_eq!(
+            palette.colors,
+            [
+                Color::RGB(0, 0, 0),
+                Color::RGB(0xFF, 0xFF, 0xFF),
+                Color::RGB(0xFF, 0xFF, 0xFF),
+                Color::RGB(0xFF, 0xFF, 0xFF),
+            ]
+        );
+
+        let palette: Palette = Palette::custom(&strings(&["000000", "111111", "222222"])).unwrap();
+        assert_eq!(palette.color(2), Color::RGB(0x22, 0x22, 0x22));
+        assert_eq!(palette.color(3), Color::RGB(0x11, 0x11, 0x11));
+    }
+
+    #[test]
+    fn rejects_custom_palettes_of_the_wrong_size_or_with_bad_colors() {
+        assert!(Palette::custom(&strings(&["#000000"])).is_err());
+        assert!(Palette::custom(&strings(&["#000000"; 5])).is_err());
+        assert!(Palette::custom(&strings(&["#000000", "#FFF"])).is_err());
+    }
+
+    #[test]
+    fn looks_up_presets() {
+        for name in PRESET_NAMES {
+            assert_eq!(Palette::preset(name).unwrap().name, name);
+        }
+        assert_eq!(Palette::preset("custom"), None);
+        assert_eq!(Palette::preset("CLASSIC"), None);
+        assert_eq!(cycle_names(false), PRESET_NAMES);
+        assert_eq!(cycle_names(true).last(), Some(&"custom"));
+    }
+
+    #[test]
+    fn selects_a_palette_falling_back_to_classic() {
+        let classic: Palette = Palette::preset("classic").unwrap();
+        let custom: Palette = Palette::custom(&strings(&["#102030", "#405060"])).unwrap();
+        assert_eq!(select("amber", &None).name, "amber");
+        assert_eq!(select("nothing", &None), classic);
+        assert_eq!(select("custom", &None), classic);
+        assert_eq!(select("custom", &Some(custom.clone())), custom);
+        assert_eq!(select("green", &Some(custom)).name, "green");
+    }
+
+    #[test]
+    fn masks_pixel_values_to_four_colors() {
+        let palette: Palette = Palette::preset("classic").unwrap();
+        assert_eq!(palette.color(5), palette.color(1));
+    }
+}
@@ -0,0 +1,214 @@
+//use declarations
+use crate::chip8::{
+    self, Chip8, DISPLAY_HEIGHT, DISPLAY_WIDTH, INSTRUCTIONS_PER_FRAME, PROGRAM_START, QUIRK_NAMES,
+};
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+//python class wrapping the machine
+//memory, registers and the display are returned as bytes, which numpy reads without copying
+//with numpy.frombuffer
+#[pyclass(name = "Chip8", module = "chip8_emulator")]
+struct PyChip8 {
+    chip8: Chip8,
+    //the rom and where it was loaded, kept for reset
+    rom: Vec<u8>,
+    load_address: usize,
+    seed: Option<u64>,
+}
+
+#[pymethods]
+impl PyChip8 {
+    #[new]
+    #[pyo3(signature = (rom=None, load_address=PROGRAM_START, seed=None, tickrate=INSTRUCTIONS_PER_FRAME))]
+    fn new(
+        rom: Option<&[u8]>,
+        load_address: usize,
+        seed: Option<u64>,
+        tickrate: u32,
+    ) -> PyResult<PyChip8> {
+        let mut machine: PyChip8 = PyChip8 {
+            chip8: Chip8::new(),
+            rom: Vec::new(),
+            load_address,
+            seed,
+        };
+        machine.chip8.instructions_per_frame = tickrate.max(1);
+        if let Some(rom) = rom {
+            machine.load_rom(rom, load_address)?;
+        }
+        Ok(machine)
+    }
+
+    //load a rom and start running it, clearing memory first
+    #[pyo3(signature = (rom, load_address=PROGRAM_START))]
+    fn load_rom(&mut self, rom: &[u8], load_address: usize) -> PyResult<()> {
+        self.rom = rom.to_vec();
+        self.load_address = load_address;
+        self.reset()
+    }
+
+    //start the rom again, reseeded if a seed was given
+    fn reset(&mut self) -> PyResult<()> {
+        self.chip8.reset();
+        self.chip8
+            .load_rom_at(&self.rom, self.load_address)
+            .map_err(PyValueError::new_err)?;
+        if let Some(seed) = self.seed {
+            self.chip8.seed(seed);
+        }
+        Ok(())
+    }
+
+    //seed the random number generator now and on every reset
+    fn seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.chip8.seed(seed);
+    }
+
+    //run instructions, returns whether the last one ended a frame
+    #[pyo3(signature = (instructions=1))]
+    fn step(&mut self, py: Python<'_>, instructions: u64) -> bool {
+        let chip8: &mut Chip8 = &mut self.chip8;
+        py.detach(|| {
+            let mut frame_ended: bool = false;
+            for _ in 0..instructions {
+                frame_ended = chip8.step();
+            }
+            frame_ended
+        })
+    }
+
+    //run whole frames
+    #[pyo3(signature = (frames=1))]
+    fn run_frames(&mut self, py: Python<'_>, frames: u64) {
+        let chip8: &mut Chip8 = &mut self.chip8;
+        py.detach(|| {
+            let end: u64 = chip8.frame.saturating_add(frames);
+            while chip8.frame < end {
+                chip8.step();
+            }
+        })
+    }
+
+    //press or release a key from 0 to 15
+    fn set_key(&mut self, key: usize, pressed: bool) -> PyResult<()> {
+        *self
+            .chip8
+            .input
+            .get_mut(key)
+            .ok_or_else(|| PyIndexError::new_err("keys are 0 to 15"))? = pressed as u8;
+        Ok(())
+    }
+
+    //set all 16 keys at once, bit n is key n
+    fn set_keys(&mut self, mask: u16) {
+        for (key, pressed) in self.chip8.input.iter_mut().enumerate() {
+            *pressed = (mask >> key) as u8 & 1;
+        }
+    }
+
+    //turn a quirk on or off by its community database name, such as shift or vblank
+    fn set_quirk(&mut self, name: &str, on: bool) -> PyResult<()> {
+        let index: usize = QUIRK_NAMES
+            .iter()
+            .position(|quirk| *quirk == name)
+            .ok_or_else(|| {
+                PyValueError::new_err(format!(
+                    "unknown quirk \"{}\", expected {}",
+                    name,
+                    QUIRK_NAMES.join(", ")
+                ))
+            })?;
+        *chip8::quirk_mut(&mut self.chip8.quirks, index) = on;
+        Ok(())
+    }
+
+    //all 4 KB of memory
+    #[getter]
+    fn memory<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.chip8.memory)
+    }
+
+    //write bytes to memory starting at an address
+    fn write_memory(&mut self, address: usize, data: &[u8]) -> PyResult<()> {
+        self.chip8
+            .memory
+            .get_mut(address..address.saturating_add(data.len()))
+            .ok_or_else(|| PyIndexError::new_err("address range is outside memory"))?
+            .copy_from_slice(data);
+        Ok(())
+    }
+
+    //registers V0 to VF
+    #[getter]
+    fn registers<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.chip8.registers)
+    }
+
+    fn set_register(&mut self, register: usize, value: u8) -> PyResult<()> {
+        *self
+            .chip8
+            .registers
+            .get_mut(register)
+            .ok_or_else(|| PyIndexError::new_err("registers are 0 to 15"))? = value;
+        Ok(())
+    }
+
+    #[getter]
+    fn pc(&self) -> usize {
+        self.chip8.program_counter
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        self.chip8.index_register
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        self.chip8.delay_timer
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        self.chip8.sound_timer
+    }
+
+    //whether the beeper is sounding
+    #[getter]
+    fn sound_active(&self) -> bool {
+        self.chip8.sound_timer != 0
+    }
+
+    #[getter]
+    fn frame(&self) -> u64 {
+        self.chip8.frame
+    }
+
+    //the 64x32 display, one byte per pixel in rows, 1 for lit
+    #[getter]
+    fn framebuffer<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.chip8.display)
+    }
+
+    //save the machine state, for load_state
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.chip8.save_state())
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.chip8.load_state(state).map_err(PyValueError::new_err)
+    }
+}
+
+//python module, built with maturin and the python feature
+#[pymodule]
+fn chip8_emulator(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyChip8>()?;
+    module.add("DISPLAY_WIDTH", DISPLAY_WIDTH)?;
+    module.add("DISPLAY_HEIGHT", DISPLAY_HEIGHT)?;
+    module.add("QUIRKS", QUIRK_NAMES.to_vec())?;
+    Ok(())
+}
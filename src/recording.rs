@@ -0,0 +1,206 @@
+//use declarations
+use crate::chip8::{DISPLAY_HEIGHT, DISPLAY_WIDTH, FRAMES_PER_SECOND};
+use crate::palette::Palette;
+use crate::wav::WavWriter;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+//size of a gif pixel in emulated pixels
+const GIF_SCALE: usize = 4;
+
+//frame rates of the recordings, gif frame delays are in hundredths of a second so 50 fps is exact
+//every frame lasts 2 hundredths, and the emulated frames in between are dropped or merged
+const GIF_DELAY: u16 = 2;
+const GIF_FPS: f64 = 100.0 / GIF_DELAY as f64;
+const RAW_FPS: f64 = 60.0;
+
+//where the recording is written
+enum Output {
+    //palette indexed animated gif
+    Gif(gif::Encoder<BufWriter<File>>),
+    //raw rgb24 frames and a wav file of the audio
+    Raw {
+        video: Box<dyn Write>,
+        audio: WavWriter,
+    },
+}
+
+//recorder struct and methods
+//frames are added at the end of every emulated frame and resampled to the recording frame rate,
+//so recordings play at the right speed no matter how fast the emulator ran
+pub struct Recorder {
+    output: Output,
+    fps: f64,
+    //colors of the gif global palette
+    gif_palette: Vec<u8>,
+    //emulated frames added and recording frames written so far
+    frames: u64,
+    written: u64,
+}
+
+impl Recorder {
+    //start recording to a path, a .gif path records a gif and anything else records raw frames
+    //raw frames are written to stdout if the path is -, and the audio goes in a .wav file next to them
+    pub fn start(
+        path: &str,
+        palette: &Palette,
+        sample_rate: u32,
+        raw_size: (usize, usize),
+    ) -> Result<Recorder, String> {
+        let gif_palette: Vec<u8> = palette_bytes(palette);
+
+        let (output, fps): (Output, f64) = if path.to_lowercase().ends_with(".gif") {
+            //open the gif and set it to loop forever
+            let file: File =
+                File::create(path).map_err(|e| format!("could not create {}: {}", path, e))?;
+            let mut encoder = gif::Encoder::new(
+                BufWriter::new(file),
+                (DISPLAY_WIDTH * GIF_SCALE) as u16,
+                (DISPLAY_HEIGHT * GIF_SCALE) as u16,
+                &gif_palette,
+            )
+            .map_err(|e| format!("could not write {}: {}", path, e))?;
+            encoder
+                .set_repeat(gif::Repeat::Infinite)
+                .map_err(|e| format!("could not write {}: {}", path, e))?;
+            eprintln!("recording gif to {}", path);
+            (Output::Gif(encoder), GIF_FPS)
+        } else {
+            //open the video stream and the audio file
+            let (video, audio_path): (Box<dyn Write>, String) = if path == "-" {
+                (Box::new(io::stdout()), "recording.wav".to_string())
+            } else {
+                let file: File =
+                    File::create(path).map_err(|e| format!("could not create {}: {}", path, e))?;
+                let audio_path: String = Path::new(path)
+                    .with_extension("wav")
+                    .to_string_lossy()
+                    .to_string();
+                (Box::new(BufWriter::new(file)), audio_path)
+            };
+            let audio: WavWriter = WavWriter::create(Path::new(&audio_path), sample_rate)?;
+            eprintln!(
+                "recording rgb24 {}x{} at {} fps to {}, audio to {}",
+                raw_size.0, raw_size.1, RAW_FPS, path, audio_path
+            );
+            (Output::Raw { video, audio }, RAW_FPS)
+        };
+
+        Ok(Recorder {
+            output,
+            fps,
+            gif_palette,
+            frames: 0,
+            written: 0,
+        })
+    }
+
+    //add an emulated frame along with the audio samples played during it
+    //render is only called when the raw recording needs a new frame
+    pub fn add_frame<F: FnOnce() -> Vec<u8>>(
+        &mut self,
+        display: &[u8],
+        palette: &Palette,
+        samples: &[f32],
+        render: F,
+    ) -> Result<(), String> {
+        self.frames += 1;
+        let time: f64 = self.frames as f64 / FRAMES_PER_SECOND;
+
+        //count how many recording frames have passed, this can be 0 when slowing down the frame rate
+        let mut due: u64 = 0;
+        while (self.written + due) as f64 / self.fps < time {
+            due += 1;
+        }
+        self.written += due;
+
+        match &mut self.output {
+            Output::Gif(encoder) => {
+                if due == 0 {
+                    return Ok(());
+                }
+
+                //scale up the display as palette indexes
+                let width: usize = DISPLAY_WIDTH * GIF_SCALE;
+                let mut indexes: Vec<u8> = vec![0; width * DISPLAY_HEIGHT * GIF_SCALE];
+                for (i, index) in indexes.iter_mut().enumerate() {
+                    let x: usize = (i % width) / GIF_SCALE;
+                    let y: usize = (i / width) / GIF_SCALE;
+                    *index = display[y * DISPLAY_WIDTH + x] & 0x3;
+                }
+
+                //write one frame lasting for every recording frame that passed
+                //the palette is only stored in the frame if it changed since recording started
+                let colors: Vec<u8> = palette_bytes(palette);
+                let frame: gif::Frame = gif::Frame {
+                    width: width as u16,
+                    height: (DISPLAY_HEIGHT * GIF_SCALE) as u16,
+                    delay: due as u16 * GIF_DELAY,
+                    palette: if colors != self.gif_palette {
+                        Some(colors)
+                    } else {
+                        None
+                    },
+                    buffer: Cow::Owned(indexes),
+                    ..gif::Frame::default()
+                };
+                encoder
+                    .write_frame(&frame)
+                    .map_err(|e| format!("could not write gif: {}", e))?;
+            }
+            Output::Raw { video, audio } => {
+                //write the frame once for every recording frame that passed
+                if due > 0 {
+                    let image: Vec<u8> = render();
+                    for _ in 0..due {
+                        video
+                            .write_all(&image)
+                            .map_err(|e| format!("could not write video: {}", e))?;
+                    }
+                }
+
+                //write the audio for this emulated frame
+                audio.write_samples(samples)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    //finish writing the recording
+    pub fn finish(self) -> Result<(), String> {
+        match self.output {
+            Output::Gif(encoder) => {
+                let mut file: BufWriter<File> = encoder
+                    .into_inner()
+                    .map_err(|e| format!("could not write gif: {}", e))?;
+                file.flush()
+                    .map_err(|e| format!("could not write gif: {}", e))?;
+            }
+            Output::Raw { mut video, audio } => {
+                video
+                    .flush()
+                    .map_err(|e| format!("could not write video: {}", e))?;
+                audio.finish()?;
+            }
+        }
+        eprintln!("recording stopped after {} frames", self.frames);
+        Ok(())
+    }
+}
+
+//get the file name for a gif recording started from the hotkey
+pub fn file_name(rom_name: &str, frame: u64) -> String {
+    format!("{}_frame{}.gif", rom_name, frame)
+}
+
+//get the 4 palette colors as rgb bytes
+fn palette_bytes(palette: &Palette) -> Vec<u8> {
+    palette
+        .colors
+        .iter()
+        .flat_map(|color| [color.r, color.g, color.b])
+        .collect()
+}
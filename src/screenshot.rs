@@ -0,0 +1,77 @@
+//use declarations
+use crate::crt::{self, CrtSettings};
+use sdl2::pixels::Color;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+//get the file name for a screenshot, made from the rom name and frame number
+pub fn file_name(rom_name: &str, frame: u64, raw: bool) -> String {
+    if raw {
+        format!("{}_frame{}_raw.png", rom_name, frame)
+    } else {
+        format!("{}_frame{}.png", rom_name, frame)
+    }
+}
+
+//convert a frame of pixel colors to rgb24 bytes
+pub fn to_rgb(frame: &[Color]) -> Vec<u8> {
+    frame
+        .iter()
+        .flat_map(|color| [color.r, color.g, color.b])
+        .collect()
+}
+
+//draw a frame at the given size the same way the window does, as rgb24 bytes
+//the frame is stretched with square pixels, or drawn with the crt effects if they are enabled
+pub fn render(
+    (frame, frame_width, frame_height): (&[Color], usize, usize),
+    (display_width, display_height): (usize, usize),
+    width: usize,
+    height: usize,
+    crt_settings: &CrtSettings,
+) -> Vec<u8> {
+    if crt_settings.enabled {
+        return crt::apply(
+            frame,
+            frame_width,
+            frame_height,
+            display_width,
+            display_height,
+            width,
+            height,
+            crt_settings,
+        );
+    }
+
+    //pick the nearest frame pixel for each output pixel
+    let mut image: Vec<u8> = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        let source_y: usize = y * frame_height / height;
+        for x in 0..width {
+            let source_x: usize = x * frame_width / width;
+            let color: Color = frame[source_y * frame_width + source_x];
+            image.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+    image
+}
+
+//write rgb24 bytes to a png file
+pub fn save_png(path: &Path, image: &[u8], width: usize, height: usize) -> Result<(), String> {
+    let file: File =
+        File::create(path).map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder
+        .write_header()
+        .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+    writer
+        .write_image_data(image)
+        .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+    writer
+        .finish()
+        .map_err(|e| format!("could not write {}: {}", path.display(), e))
+}
@@ -0,0 +1,478 @@
+//use declarations
+use crate::chip8::{Chip8, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::config;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+
+//port the server listens on when none is given
+pub const DEFAULT_PORT: u16 = 8064;
+
+//most instructions one step request can run, so a bad request can not hang the server
+const MAX_STEP_INSTRUCTIONS: u64 = 100_000_000;
+
+//requests, one json object per line with the command in "command"
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+enum Request {
+    LoadRom {
+        path: String,
+    },
+    Reset,
+    //run a number of frames, or instructions if frames is not given
+    Step {
+        frames: Option<u64>,
+        instructions: Option<u64>,
+    },
+    Press {
+        key: usize,
+    },
+    Release {
+        key: usize,
+    },
+    //set all 16 keys at once, bit n is key n
+    SetKeys {
+        mask: u16,
+    },
+    ReadMemory {
+        address: usize,
+        length: usize,
+    },
+    WriteMemory {
+        address: usize,
+        data: Vec<u8>,
+    },
+    Registers,
+    SetRegisters {
+        v: Option<Vec<u8>>,
+        i: Option<u16>,
+        pc: Option<usize>,
+        delay_timer: Option<u8>,
+        sound_timer: Option<u8>,
+    },
+    Framebuffer,
+    //save to a named slot kept by the server, or to a file
+    SaveState {
+        slot: Option<String>,
+        path: Option<String>,
+    },
+    LoadState {
+        slot: Option<String>,
+        path: Option<String>,
+    },
+    //close the connection, or stop the server
+    Close,
+    Shutdown,
+}
+
+//what to do with the connection after a request
+enum After {
+    Continue,
+    Close,
+    Shutdown,
+}
+
+//server struct and methods
+//runs a rom for a client over a local socket, controlled by json requests
+pub struct Server<'a> {
+    chip8: Chip8,
+    file_name: Option<String>,
+    //loads a rom with the settings it would run with in the window
+    load: &'a dyn Fn(&str) -> Result<Chip8, String>,
+    states: HashMap<String, Vec<u8>>,
+}
+
+impl<'a> Server<'a> {
+    //make a server, with a rom already loaded if one was given
+    pub fn new(
+        load: &'a dyn Fn(&str) -> Result<Chip8, String>,
+        file_name: Option<&str>,
+    ) -> Result<Server<'a>, String> {
+        let chip8: Chip8 = match file_name {
+            Some(file_name) => load(file_name)?,
+            None => Chip8::new(),
+        };
+        Ok(Server {
+            chip8,
+            file_name: file_name.map(str::to_string),
+            load,
+            states: HashMap::new(),
+        })
+    }
+
+    //listen on a localhost tcp port, serving one client at a time until told to shut down
+    pub fn listen_tcp(&mut self, port: u16) -> Result<(), String> {
+        let listener: TcpListener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("could not listen on port {}: {}", port, e))?;
+        eprintln!("listening on 127.0.0.1:{}", port);
+        for stream in listener.incoming() {
+            let stream = stream.map_err(|e| e.to_string())?;
+            if self.serve(BufReader::new(&stream), &stream)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    //listen on a unix socket, serving one client at a time until told to shut down
+    #[cfg(unix)]
+    pub fn listen_unix(&mut self, path: &str) -> Result<(), String> {
+        use std::os::unix::net::UnixListener;
+
+        //a socket file left behind by an earlier server stops a new one binding
+        let _ = fs::remove_file(path);
+        let listener: UnixListener =
+            UnixListener::bind(path).map_err(|e| format!("could not listen on {}: {}", path, e))?;
+        eprintln!("listening on {}", path);
+        let mut result: Result<(), String> = Ok(());
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => match self.serve(BufReader::new(&stream), &stream) {
+                    Ok(true) => break,
+                    Ok(false) => {}
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                },
+                Err(e) => {
+                    result = Err(e.to_string());
+                    break;
+                }
+            }
+        }
+        let _ = fs::remove_file(path);
+        result
+    }
+
+    #[cfg(not(unix))]
+    pub fn listen_unix(&mut self, _path: &str) -> Result<(), String> {
+        Err("unix sockets are not supported on this system".to_string())
+    }
+
+    //answer a client's requests until it disconnects, returns true if it asked to shut down
+    fn serve(&mut self, reader: impl BufRead, mut writer: impl Write) -> Result<bool, String> {
+        for line in reader.lines() {
+            let line: String = match line {
+                Ok(line) => line,
+                //a client going away is not an error for the server
+                Err(_) => return Ok(false),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (response, after): (Value, After) =
+                match serde_json::from_str::<Request>(&line).map_err(|e| e.to_string()) {
+                    Ok(request) => match self.handle(request) {
+                        Ok((mut response, after)) => {
+                            response["ok"] = json!(true);
+                            (response, after)
+                        }
+                        Err(e) => (json!({ "ok": false, "error": e }), After::Continue),
+                    },
+                    Err(e) => (json!({ "ok": false, "error": e }), After::Continue),
+                };
+            if writeln!(writer, "{}", response).is_err() {
+                return Ok(false);
+            }
+            match after {
+                After::Continue => {}
+                After::Close => return Ok(false),
+                After::Shutdown => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+
+    //carry out a request, returning the fields of the response
+    fn handle(&mut self, request: Request) -> Result<(Value, After), String> {
+        let chip8: &mut Chip8 = &mut self.chip8;
+        let response: Value = match request {
+            Request::LoadRom { path } => {
+                self.chip8 = (self.load)(&path)?;
+                self.file_name = Some(path);
+                json!({})
+            }
+            Request::Reset => {
+                let file_name: &str = self.file_name.as_deref().ok_or("no rom is loaded")?;
+                self.chip8 = (self.load)(file_name)?;
+                json!({})
+            }
+            Request::Step {
+                frames,
+                instructions,
+            } => {
+                let mut count: u64 = 0;
+                match frames {
+                    Some(frames) => {
+                        let end: u64 = chip8.frame.saturating_add(frames);
+                        while chip8.frame < end && count < MAX_STEP_INSTRUCTIONS {
+                            chip8.step();
+                            count += 1;
+                        }
+                    }
+                    None => {
+                        for _ in 0..instructions.unwrap_or(1).min(MAX_STEP_INSTRUCTIONS) {
+                            chip8.step();
+                            count += 1;
+                        }
+                    }
+                }
+                json!({ "instructions": count, "frame": chip8.frame, "pc": chip8.program_counter })
+            }
+            Request::Press { key } => {
+                *chip8.input.get_mut(key).ok_or("keys are 0 to 15")? = 1;
+                json!({})
+            }
+            Request::Release { key } => {
+                *chip8.input.get_mut(key).ok_or("keys are 0 to 15")? = 0;
+                json!({})
+            }
+            Request::SetKeys { mask } => {
+                for (key, pressed) in chip8.input.iter_mut().enumerate() {
+                    *pressed = (mask >> key) as u8 & 1;
+                }
+                json!({})
+            }
+            Request::ReadMemory { address, length } => {
+                let bytes: &[u8] = chip8
+                    .memory
+                    .get(address..address.saturating_add(length))
+                    .ok_or("address range is outside memory")?;
+                json!({ "data": bytes })
+            }
+            Request::WriteMemory { address, data } => {
+                chip8
+                    .memory
+                    .get_mut(address..address.saturating_add(data.len()))
+                    .ok_or("address range is outside memory")?
+                    .copy_from_slice(&data);
+                json!({})
+            }
+            Request::Registers => json!({
+                "v": chip8.registers,
+                "i": chip8.index_register,
+                "pc": chip8.program_counter,
+                "stack": &chip8.stack[..chip8.stack_index.min(chip8.stack.len())],
+                "delay_timer": chip8.delay_timer,
+                "sound_timer": chip8.sound_timer,
+                "frame": chip8.frame,
+            }),
+            Request::SetRegisters {
+                v,
+                i,
+                pc,
+                delay_timer,
+                sound_timer,
+            } => {
+                if let Some(v) = v {
+                    if v.len() != chip8.registers.len() {
+                        return Err("v needs 16 registers".to_string());
+                    }
+                    chip8.registers.copy_from_slice(&v);
+                }
+                if let Some(pc) = pc {
+                    if pc >= chip8.memory.len() {
+                        return Err("pc is outside memory".to_string());
+                    }
+                    chip8.program_counter = pc;
+                }
+                chip8.index_register = i.unwrap_or(chip8.index_register);
+                chip8.delay_timer = delay_timer.unwrap_or(chip8.delay_timer);
+                chip8.sound_timer = sound_timer.unwrap_or(chip8.sound_timer);
+                json!({})
+            }
+            //rows of 0 and 1 characters, with the hash the test command prints
+            Request::Framebuffer => {
+                let rows: Vec<String> = chip8
+                    .display
+                    .chunks(DISPLAY_WIDTH)
+                    .map(|row| row.iter().map(|pixel| (b'0' + *pixel) as char).collect())
+                    .collect();
+                json!({
+                    "width": DISPLAY_WIDTH,
+                    "height": DISPLAY_HEIGHT,
+                    "rows": rows,
+                    "hash": config::rom_hash(&chip8.display),
+                })
+            }
+            Request::SaveState { slot, path } => {
+                let state: Vec<u8> = chip8.save_state();
+                match path {
+                    Some(path) => fs::write(&path, state)
+                        .map_err(|e| format!("could not write {}: {}", path, e))?,
+                    None => {
+                        self.states.insert(slot.unwrap_or_default(), state);
+                    }
+                }
+                json!({})
+            }
+            Request::LoadState { slot, path } => {
+                let state: Vec<u8> = match path {
+                    Some(path) => {
+                        fs::read(&path).map_err(|e| format!("could not read {}: {}", path, e))?
+                    }
+                    None => {
+                        let slot: String = slot.unwrap_or_default();
+                        self.states
+                            .get(&slot)
+                            .ok_or_else(|| format!("nothing saved in slot \"{}\"", slot))?
+                            .clone()
+                    }
+                };
+                chip8.load_state(&state)?;
+                json!({})
+            }
+            Request::Close => return Ok((json!({}), After::Close)),
+            Request::Shutdown => return Ok((json!({}), After::Shutdown)),
+        };
+        Ok((response, After::Continue))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //load the rom for game.ch8, which counts up in V0 forever, and fail for any other path
+    fn load(path: &str) -> Result<Chip8, String> {
+        if path != "game.ch8" {
+            return Err(format!("could not read {}", path));
+        }
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.load_rom(&[0x70, 0x01, 0x12, 0x00])?;
+        Ok(chip8)
+    }
+
+    //send requests to a server, returning the responses and whether it was told to shut down
+    fn send(server: &mut Server, requests: &[&str]) -> (Vec<Value>, bool) {
+        let input: String = requests.join("\n");
+        let mut output: Vec<u8> = Vec::new();
+        let shutdown: bool = server.serve(input.as_bytes(), &mut output).unwrap();
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        (responses, shutdown)
+    }
+
+    #[test]
+    fn runs_commands_on_the_loaded_rom() {
+        let mut server: Server = Server::new(&load, Some("game.ch8")).unwrap();
+        let (responses, shutdown): (Vec<Value>, bool) = send(
+            &mut server,
+            &[
+                r#"{"command": "step", "instructions": 5}"#,
+                r#"{"command": "registers"}"#,
+                r#"{"command": "write_memory", "address": 768, "data": [1, 2, 3]}"#,
+                r#"{"command": "read_memory", "address": 767, "length": 5}"#,
+                r#"{"command": "set_keys", "mask": 5}"#,
+                r#"{"command": "framebuffer"}"#,
+            ],
+        );
+        assert!(!shutdown);
+        assert!(responses.iter().all(|response| response["ok"] == true));
+        assert_eq!(responses[0]["instructions"], 5);
+        assert_eq!(responses[0]["pc"], 0x202);
+        assert_eq!(responses[1]["v"][0], 3);
+        assert_eq!(responses[3]["data"], json!([0, 1, 2, 3, 0]));
+        assert_eq!(server.chip8.input[..3], [1, 0, 1]);
+        assert_eq!(responses[5]["width"], DISPLAY_WIDTH);
+        assert_eq!(
+            responses[5]["rows"].as_array().map(Vec::len),
+            Some(DISPLAY_HEIGHT)
+        );
+    }
+
+    #[test]
+    fn saves_and_loads_states_in_slots() {
+        let mut server: Server = Server::new(&load, Some("game.ch8")).unwrap();
+        let (responses, _): (Vec<Value>, bool) = send(
+            &mut server,
+            &[
+                r#"{"command": "step", "instructions": 2}"#,
+                r#"{"command": "save_state", "slot": "start"}"#,
+                r#"{"command": "step", "instructions": 10}"#,
+                r#"{"command": "load_state", "slot": "start"}"#,
+                r#"{"command": "load_state", "slot": "other"}"#,
+            ],
+        );
+        assert!(responses[..4].iter().all(|response| response["ok"] == true));
+        assert_eq!(server.chip8.registers[0], 1);
+        assert_eq!(
+            responses[4],
+            json!({ "ok": false, "error": "nothing saved in slot \"other\"" })
+        );
+    }
+
+    #[test]
+    fn reports_bad_requests_and_keeps_going() {
+        let mut server: Server = Server::new(&load, None).unwrap();
+        let (responses, _): (Vec<Value>, bool) = send(
+            &mut server,
+            &[
+                "not json",
+                r#"{"command": "fly"}"#,
+                r#"{"command": "step", "frames": 1, "speed": 2}"#,
+                r#"{"command": "press"}"#,
+                "",
+                r#"{"command": "press", "key": 16}"#,
+                r#"{"command": "read_memory", "address": 4095, "length": 2}"#,
+                r#"{"command": "set_registers", "v": [1, 2]}"#,
+                r#"{"command": "reset"}"#,
+                r#"{"command": "load_rom", "path": "missing.ch8"}"#,
+                r#"{"command": "press", "key": 15}"#,
+                r#"{"command": "set_registers", "pc": 4096}"#,
+                r#"{"command": "set_registers", "pc": 4095}"#,
+            ],
+        );
+        //the blank line gets no response
+        assert_eq!(responses.len(), 12);
+        assert!(responses[..9]
+            .iter()
+            .all(|response| response["ok"] == false));
+        assert!(responses[1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("unknown variant `fly`"));
+        assert!(responses[2]["error"]
+            .as_str()
+            .unwrap()
+            .contains("unknown field `speed`"));
+        assert!(responses[3]["error"]
+            .as_str()
+            .unwrap()
+            .contains("missing field `key`"));
+        assert_eq!(responses[4]["error"], "keys are 0 to 15");
+        assert_eq!(responses[5]["error"], "address range is outside memory");
+        assert_eq!(responses[6]["error"], "v needs 16 registers");
+        assert_eq!(responses[7]["error"], "no rom is loaded");
+        assert_eq!(responses[8]["error"], "could not read missing.ch8");
+        assert_eq!(responses[9], json!({ "ok": true }));
+        assert_eq!(responses[10]["error"], "pc is outside memory");
+        assert_eq!(responses[11], json!({ "ok": true }));
+        assert_eq!(server.chip8.program_counter, 0xFFF);
+    }
+
+    #[test]
+    fn stops_after_close_and_shutdown() {
+        let mut server: Server = Server::new(&load, Some("game.ch8")).unwrap();
+        let (responses, shutdown): (Vec<Value>, bool) = send(
+            &mut server,
+            &[r#"{"command": "close"}"#, r#"{"command": "registers"}"#],
+        );
+        assert_eq!(responses, [json!({ "ok": true })]);
+        assert!(!shutdown);
+
+        let (responses, shutdown): (Vec<Value>, bool) = send(
+            &mut server,
+            &[r#"{"command": "shutdown"}"#, r#"{"command": "registers"}"#],
+        );
+        assert_eq!(responses, [json!({ "ok": true })]);
+        assert!(shutdown);
+    }
+}
@@ -0,0 +1,386 @@
+//use declarations
+use crate::audio::{AudioConfig, BeeperSettings, Waveform};
+use crate::chip8::{self, Quirks, QUIRK_NAMES};
+use crate::config::{Config, RomConfig, MAX_SCALE};
+use crate::crt::CrtSettings;
+use crate::database::QuirkOverrides;
+use crate::filter::FilterMode;
+use crate::osd;
+use crate::palette;
+use crate::upscale::Upscaler;
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::EventPump;
+use std::time::Duration;
+
+//speed multipliers the speed setting steps through
+const SPEEDS: [f64; 9] = [0.25, 0.5, 0.75, 1.0, 1.5, 2.0, 3.0, 4.0, 8.0];
+
+//limits and steps of the number settings
+const MAX_TICKRATE: u32 = 1000;
+const TONE_STEP: f32 = 10.0;
+const MIN_TONE: f32 = 20.0;
+const MAX_TONE: f32 = 4000.0;
+const VOLUME_STEP: f32 = 0.05;
+
+//settings that can be changed on the settings screen, with the values the rom is running with
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub palette: String,
+    pub scale: u32,
+    pub integer_scale: bool,
+    pub speed: f64,
+    pub tickrate: u32,
+    pub filter: FilterMode,
+    pub upscaler: Upscaler,
+    pub crt: bool,
+    pub show_stats: bool,
+    pub beeper: BeeperSettings,
+    pub quirks: Quirks,
+}
+
+//lines on the settings screen
+#[derive(Clone, Copy, PartialEq)]
+enum Item {
+    //whether changes are saved for this rom or for all roms
+    SaveFor,
+    Palette,
+    Scale,
+    IntegerScale,
+    Speed,
+    Tickrate,
+    Filter,
+    Upscaler,
+    Crt,
+    ShowStats,
+    Volume,
+    Tone,
+    Waveform,
+    Muted,
+    //a quirk by its index in QUIRK_NAMES
+    Quirk(usize),
+}
+
+impl Settings {
+    //get the label and value shown for an item
+    fn line(&self, item: Item, for_rom: bool) -> (String, String) {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        match item {
+            Item::SaveFor => (
+                "save for".to_string(),
+                if for_rom { "this rom" } else { "all roms" }.to_string(),
+            ),
+            Item::Palette => ("palette".to_string(), self.palette.clone()),
+            Item::Scale => ("window scale".to_string(), self.scale.to_string()),
+            Item::IntegerScale => ("integer scale".to_string(), on_off(self.integer_scale)),
+            Item::Speed => ("speed".to_string(), format!("{:.2}x", self.speed)),
+            Item::Tickrate => (
+                "instructions per frame".to_string(),
+                self.tickrate.to_string(),
+            ),
+            Item::Filter => ("filter".to_string(), self.filter.name()),
+            Item::Upscaler => ("upscaler".to_string(), self.upscaler.name().to_string()),
+            Item::Crt => ("crt effects".to_string(), on_off(self.crt)),
+            Item::ShowStats => ("show stats".to_string(), on_off(self.show_stats)),
+            Item::Volume => (
+                "volume".to_string(),
+                format!("{:.0}%", self.beeper.volume * 100.0),
+            ),
+            Item::Tone => (
+                "tone".to_string(),
+                format!("{:.0} hz", self.beeper.frequency),
+            ),
+            Item::Waveform => (
+                "waveform".to_string(),
+                self.beeper.waveform.name().to_string(),
+            ),
+            Item::Muted => ("muted".to_string(), on_off(self.beeper.muted)),
+            Item::Quirk(index) => {
+                let mut quirks: Quirks = self.quirks;
+                (
+                    format!("quirk {}", QUIRK_NAMES[index]),
+                    on_off(*chip8::quirk_mut(&mut quirks, index)),
+                )
+            }
+        }
+    }
+
+    //change an item one step up or down, on and off settings are toggled either way
+    fn change(&mut self, item: Item, up: bool, for_rom: &mut bool, has_custom_palette: bool) {
+        match item {
+            Item::SaveFor => *for_rom = !*for_rom,
+            Item::Palette => {
+                let names: Vec<&str> = palette::cycle_names(has_custom_palette);
+                let current: usize = names
+                    .iter()
+                    .position(|name| *name == self.palette)
+                    .unwrap_or(0);
+                let next: usize = if up {
+                    (current + 1) % names.len()
+                } else {
+                    (current + names.len() - 1) % names.len()
+                };
+                self.palette = names[next].to_string();
+            }
+            Item::Scale => {
+                self.scale = if up {
+                    (self.scale + 1).min(MAX_SCALE)
+                } else {
+                    self.scale.saturating_sub(1).max(1)
+                }
+            }
+            Item::IntegerScale => self.integer_scale = !self.integer_scale,
+            Item::Speed => {
+                let next: Option<f64> = if up {
+                    SPEEDS.iter().copied().find(|speed| *speed > self.speed)
+                } else {
+                    SPEEDS
+                        .iter()
+                        .rev()
+                        .copied()
+                        .find(|speed| *speed < self.speed)
+                };
+                self.speed = next.unwrap_or(self.speed);
+            }
+            Item::Tickrate => {
+                self.tickrate = if up {
+                    (self.tickrate + 1).min(MAX_TICKRATE)
+                } else {
+                    self.tickrate.saturating_sub(1).max(1)
+                }
+            }
+            Item::Filter => self.filter = self.filter.next(),
+            Item::Upscaler => self.upscaler = self.upscaler.next(),
+            Item::Crt => self.crt = !self.crt,
+            Item::ShowStats => self.show_stats = !self.show_stats,
+            Item::Volume => {
+                let step: f32 = if up { VOLUME_STEP } else { -VOLUME_STEP };
+                self.beeper.volume = (self.beeper.volume + step).clamp(0.0, 1.0);
+            }
+            Item::Tone => {
+                let step: f32 = if up { TONE_STEP } else { -TONE_STEP };
+                self.beeper.frequency = (self.beeper.frequency + step).clamp(MIN_TONE, MAX_TONE);
+            }
+            Item::Waveform => {
+                let count: usize = Waveform::ALL.len();
+                let current: usize = Waveform::ALL
+                    .iter()
+                    .position(|waveform| *waveform == self.beeper.waveform)
+                    .unwrap_or(0);
+                let next: usize = if up {
+                    (current + 1) % count
+                } else {
+                    (current + count - 1) % count
+                };
+                self.beeper.waveform = Waveform::ALL[next];
+            }
+            Item::Muted => self.beeper.muted = !self.beeper.muted,
+            Item::Quirk(index) => {
+                let quirk: &mut bool = chip8::quirk_mut(&mut self.quirks, index);
+                *quirk = !*quirk;
+            }
+        }
+    }
+
+    //get the settings changed since before that can be stored per rom, as config overrides
+    fn overrides(&self, before: &Settings) -> RomConfig {
+        let mut changes: RomConfig = RomConfig::default();
+        if self.palette != before.palette {
+            changes.palette = Some(self.palette.clone());
+        }
+        if self.scale != before.scale {
+            changes.scale = Some(self.scale);
+        }
+        if self.speed != before.speed {
+            changes.speed = Some(self.speed);
+        }
+        if self.tickrate != before.tickrate {
+            changes.tickrate = Some(self.tickrate);
+        }
+        if self.filter != before.filter {
+            changes.filter = Some(self.filter.name());
+        }
+        if self.upscaler != before.upscaler {
+            changes.upscaler = Some(self.upscaler.name().to_string());
+        }
+        if self.beeper != before.beeper {
+            let changed = |from: f32, to: f32| if from != to { Some(to) } else { None };
+            changes.audio = Some(AudioConfig {
+                frequency: changed(before.beeper.frequency, self.beeper.frequency),
+                volume: changed(before.beeper.volume, self.beeper.volume),
+                waveform: Some(self.beeper.waveform)
+                    .filter(|_| self.beeper.waveform != before.beeper.waveform),
+                muted: Some(self.beeper.muted).filter(|_| self.beeper.muted != before.beeper.muted),
+                ..AudioConfig::default()
+            });
+        }
+        if self.quirks != before.quirks {
+            changes.quirks = Some(QuirkOverrides::changes(&before.quirks, &self.quirks));
+        }
+        changes
+    }
+
+    //write the settings changed since before to the config, in a rom's section if one is given
+    //integer scaling, the crt effects and the stats are always stored globally
+    pub fn save(&self, before: &Settings, config: &mut Config, rom_hash: Option<&str>) {
+        if self.integer_scale != before.integer_scale {
+            config.integer_scale = Some(self.integer_scale);
+        }
+        if self.crt != before.crt {
+            config.crt.get_or_insert_with(CrtSettings::default).enabled = self.crt;
+        }
+        if self.show_stats != before.show_stats {
+            config.show_stats = Some(self.show_stats);
+        }
+        config.set_overrides(&self.overrides(before), rom_hash);
+    }
+}
+
+//show the settings screen until it is closed, changing the settings as they are picked
+//returns whether the changes should be saved for this rom only, or None if the window was closed
+pub fn run(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut EventPump,
+    settings: &mut Settings,
+    title: &str,
+    has_custom_palette: bool,
+) -> Result<Option<bool>, String> {
+    let mut items: Vec<Item> = vec![
+        Item::SaveFor,
+        Item::Palette,
+        Item::Scale,
+        Item::IntegerScale,
+        Item::Speed,
+        Item::Tickrate,
+        Item::Filter,
+        Item::Upscaler,
+        Item::Crt,
+        Item::ShowStats,
+        Item::Volume,
+        Item::Tone,
+        Item::Waveform,
+        Item::Muted,
+    ];
+    items.extend((0..QUIRK_NAMES.len()).map(Item::Quirk));
+    let mut selected: usize = 0;
+    let mut for_rom: bool = true;
+
+    loop {
+        //input handling, with the keyboard or a game controller
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => return Ok(None),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::B, ..
+                } => return Ok(Some(for_rom)),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Up),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::DPadUp,
+                    ..
+                } => selected = selected.saturating_sub(1),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Down),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::DPadDown,
+                    ..
+                } => selected = (selected + 1).min(items.len() - 1),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Left),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::DPadLeft,
+                    ..
+                } => settings.change(items[selected], false, &mut for_rom, has_custom_palette),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Right),
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::DPadRight,
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::A, ..
+                } => settings.change(items[selected], true, &mut for_rom, has_custom_palette),
+                _ => {}
+            }
+        }
+
+        draw(canvas, settings, &items, selected, for_rom, title)?;
+        ::std::thread::sleep(Duration::from_millis(16));
+    }
+}
+
+//draw the settings screen
+fn draw(
+    canvas: &mut Canvas<Window>,
+    settings: &Settings,
+    items: &[Item],
+    selected: usize,
+    for_rom: bool,
+    title: &str,
+) -> Result<(), String> {
+    //size the font to the window, keeping lines for the header and the help at the bottom
+    let (_, output_height): (u32, u32) = canvas.output_size()?;
+    let size: i32 = (output_height as i32 / 160).max(1);
+    let line_height: i32 = 7 * size;
+    let lines: usize = (output_height as i32 / line_height) as usize;
+    let visible: usize = lines.saturating_sub(3).max(1);
+
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    osd::draw_text(canvas, &format!("settings - {}", title), size, size, 0)?;
+
+    //the part of the list around the selected item, with an arrow at the selected one
+    let first: usize = selected.saturating_sub(visible - 1);
+    for (row, (index, item)) in items
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(visible)
+        .enumerate()
+    {
+        let (label, value): (String, String) = settings.line(*item, for_rom);
+        let marker: &str = if index == selected { "> " } else { "  " };
+        let y: i32 = (row as i32 + 2) * line_height;
+        osd::draw_text(
+            canvas,
+            &format!("{}{:<30}{}", marker, label, value),
+            size,
+            size,
+            y,
+        )?;
+    }
+
+    osd::draw_text(
+        canvas,
+        "up/down pick, left/right change, esc done",
+        size,
+        size,
+        (lines as i32 - 1) * line_height,
+    )?;
+    canvas.present();
+    Ok(())
+}
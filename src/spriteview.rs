@@ -0,0 +1,263 @@
+//use declarations
+use crate::chip8::Chip8;
+use crate::disasm;
+use crate::osd::{self, GLYPH_HEIGHT, SPACING};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::VideoSubsystem;
+
+//sprites shown across and down the window
+const COLUMNS: usize = 8;
+const ROWS: usize = 4;
+
+//size of a sprite pixel and a font pixel in window pixels
+const PIXEL_SIZE: i32 = 4;
+const FONT_SIZE: i32 = 2;
+
+//size of each sprite's cell, with room for the biggest sprite and its address below it
+const CELL_WIDTH: i32 = 16 * PIXEL_SIZE + 8;
+const CELL_HEIGHT: i32 = 16 * PIXEL_SIZE + (GLYPH_HEIGHT + SPACING * 2) * FONT_SIZE + 8;
+
+//lines above the sprites for the address, the next instruction and the keys
+const HEADER_LINES: usize = 3;
+
+//tallest sprite DXYN can draw
+const MAX_HEIGHT: usize = 15;
+
+//sprite viewer struct and methods
+//draws memory as sprites in its own window, starting at the index register or any address
+pub struct SpriteViewer {
+    canvas: Canvas<Window>,
+    //address of the first sprite, and whether it follows the index register
+    address: usize,
+    follow_index: bool,
+    //rows in each sprite, and whether they are 16x16 sprites drawn by DXY0 in hi-res
+    height: usize,
+    large: bool,
+    //address of a sprite that was clicked, for the memory viewer to jump to
+    clicked: Option<usize>,
+    closed: bool,
+}
+
+impl SpriteViewer {
+    //open the viewer window
+    pub fn open(video: &VideoSubsystem) -> Result<SpriteViewer, String> {
+        let width: u32 = (COLUMNS as i32 * CELL_WIDTH + 8) as u32;
+        let height: u32 = (header_height() + ROWS as i32 * CELL_HEIGHT) as u32;
+        let window: Window = video
+            .window("Sprites - Chip 8 Emulator", width, height)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let canvas: Canvas<Window> = window.into_canvas().build().map_err(|e| e.to_string())?;
+        Ok(SpriteViewer {
+            canvas,
+            address: 0,
+            follow_index: true,
+            height: MAX_HEIGHT,
+            large: false,
+            clicked: None,
+            closed: false,
+        })
+    }
+
+    //id of the viewer window, to tell which events are for it
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    //check if the window was closed
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+
+    //get the address of a sprite clicked since the last call
+    pub fn take_clicked(&mut self) -> Option<usize> {
+        self.clicked.take()
+    }
+
+    //handle an event for the viewer window, returns false for keys it does not use
+    //so the hotkeys still work from the viewer
+    pub fn handle_event(&mut self, event: &Event, chip8: &Chip8) -> bool {
+        match event {
+            Event::Window {
+                win_event: WindowEvent::Close,
+                ..
+            } => self.closed = true,
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match *keycode {
+                Keycode::Escape => self.closed = true,
+                Keycode::Up => self.height = (self.height + 1).min(MAX_HEIGHT),
+                Keycode::Down => self.height = self.height.saturating_sub(1).max(1),
+                Keycode::H => self.large = !self.large,
+                Keycode::I => {
+                    self.follow_index = !self.follow_index;
+                    self.address = chip8.index_register as usize;
+                }
+                Keycode::Left => self.move_by(chip8, -1),
+                Keycode::Right => self.move_by(chip8, 1),
+                Keycode::PageUp => self.move_by(chip8, -((self.sprite_bytes() * COLUMNS) as isize)),
+                Keycode::PageDown => self.move_by(chip8, (self.sprite_bytes() * COLUMNS) as isize),
+                _ => return false,
+            },
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                let column: i32 = (x - 4) / CELL_WIDTH;
+                let row: i32 = (y - header_height()) / CELL_HEIGHT;
+                if *y >= header_height()
+                    && (0..COLUMNS as i32).contains(&column)
+                    && (0..ROWS as i32).contains(&row)
+                {
+                    self.clicked =
+                        Some(self.sprite_address((row * COLUMNS as i32 + column) as usize));
+                }
+            }
+            Event::MouseWheel { y, .. } => self.move_by(
+                chip8,
+                -(*y as isize) * (self.sprite_bytes() * COLUMNS) as isize,
+            ),
+            _ => return false,
+        }
+        true
+    }
+
+    //draw the viewer
+    pub fn draw(&mut self, chip8: &Chip8) -> Result<(), String> {
+        if self.follow_index {
+            self.address = chip8.index_register as usize & 0xFFF;
+        }
+        let white: Color = Color::RGB(255, 255, 255);
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+
+        //what is shown, the next instruction if it draws, and the keys
+        let opcode: u16 = ((chip8.memory[chip8.program_counter & 0xFFF] as u16) << 8)
+            | chip8.memory[(chip8.program_counter + 1) & 0xFFF] as u16;
+        let next_draw: bool = opcode & 0xF000 == 0xD000;
+        let header: [String; HEADER_LINES] = [
+            format!(
+                "AT {:03X}{}  {}  I {:03X}",
+                self.address,
+                if self.follow_index { " (I)" } else { "" },
+                if self.large {
+                    "16X16".to_string()
+                } else {
+                    format!("8X{}", self.height)
+                },
+                chip8.index_register
+            ),
+            if next_draw {
+                format!("NEXT {:04X}: {}", opcode, disasm::instruction(opcode))
+            } else {
+                "NEXT INSTRUCTION DOES NOT DRAW".to_string()
+            },
+            "UP/DOWN HEIGHT  H 16X16  LEFT/RIGHT MOVE  I FOLLOW I  CLICK FOR MEMORY".to_string(),
+        ];
+        let line_height: i32 = (GLYPH_HEIGHT + SPACING * 2) * FONT_SIZE;
+        for (line, text) in header.iter().enumerate() {
+            osd::draw_glyphs(
+                &mut self.canvas,
+                text,
+                FONT_SIZE,
+                4,
+                line as i32 * line_height,
+                white,
+            )?;
+        }
+
+        for index in 0..COLUMNS * ROWS {
+            let address: usize = self.sprite_address(index);
+            let left: i32 = 4 + (index % COLUMNS) as i32 * CELL_WIDTH;
+            let top: i32 = header_height() + (index / COLUMNS) as i32 * CELL_HEIGHT;
+            let (width, rows): (usize, usize) = if self.large {
+                (16, 16)
+            } else {
+                (8, self.height)
+            };
+
+            //dark box the size of the sprite so blank sprites can be seen
+            let area: Rect = Rect::new(
+                left,
+                top,
+                (width as i32 * PIXEL_SIZE) as u32,
+                (rows as i32 * PIXEL_SIZE) as u32,
+            );
+            self.canvas.set_draw_color(Color::RGB(40, 40, 40));
+            self.canvas.fill_rect(area)?;
+
+            //lit pixels, 16x16 sprites have 2 bytes to a row
+            let mut pixels: Vec<Rect> = Vec::new();
+            for row in 0..rows {
+                for column in 0..width {
+                    let byte: u8 = chip8.memory[(address + row * (width / 8) + column / 8) & 0xFFF];
+                    if byte & (0x80 >> (column % 8)) != 0 {
+                        pixels.push(Rect::new(
+                            left + column as i32 * PIXEL_SIZE,
+                            top + row as i32 * PIXEL_SIZE,
+                            PIXEL_SIZE as u32,
+                            PIXEL_SIZE as u32,
+                        ));
+                    }
+                }
+            }
+            self.canvas.set_draw_color(white);
+            self.canvas.fill_rects(&pixels)?;
+
+            //outline the sprite the next instruction will draw
+            if index == 0 && next_draw && address == chip8.index_register as usize {
+                self.canvas.set_draw_color(Color::RGB(255, 200, 0));
+                self.canvas.draw_rect(area)?;
+            }
+
+            osd::draw_glyphs(
+                &mut self.canvas,
+                &format!("{:03X}", address),
+                FONT_SIZE,
+                left,
+                top + 16 * PIXEL_SIZE + 2,
+                white,
+            )?;
+        }
+
+        self.canvas.present();
+        Ok(())
+    }
+
+    //bytes in each sprite
+    fn sprite_bytes(&self) -> usize {
+        if self.large {
+            32
+        } else {
+            self.height
+        }
+    }
+
+    //get the address of a sprite shown
+    fn sprite_address(&self, index: usize) -> usize {
+        (self.address + index * self.sprite_bytes()) & 0xFFF
+    }
+
+    //move the first sprite, no longer following the index register
+    fn move_by(&mut self, chip8: &Chip8, amount: isize) {
+        if self.follow_index {
+            self.address = chip8.index_register as usize & 0xFFF;
+            self.follow_index = false;
+        }
+        self.address = (self.address as isize + amount).rem_euclid(0x1000) as usize;
+    }
+}
+
+//height of the lines above the sprites in window pixels
+fn header_height() -> i32 {
+    HEADER_LINES as i32 * (GLYPH_HEIGHT + SPACING * 2) * FONT_SIZE + 4
+}
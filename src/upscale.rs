@@ -0,0 +1,478 @@
+//use declarations
+use crate::palette::blend_color;
+use sdl2::pixels::Color;
+
+//pixel art upscaling filters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Upscaler {
+    //plain square pixels
+    Nearest,
+    //smooth diagonal edges at 2x
+    Scale2x,
+    //smooth diagonal edges at 3x
+    Scale3x,
+    //fill corners matching 3 neighbours at 2x
+    Eagle,
+    //blend corners along detected edges at 2x
+    Xbr,
+}
+
+impl Upscaler {
+    //parse an upscaler name
+    pub fn parse(text: &str) -> Result<Upscaler, String> {
+        match text {
+            "nearest" => Ok(Upscaler::Nearest),
+            "scale2x" => Ok(Upscaler::Scale2x),
+            "scale3x" => Ok(Upscaler::Scale3x),
+            "eagle" => Ok(Upscaler::Eagle),
+            "xbr" => Ok(Upscaler::Xbr),
+            _ => Err(format!(
+                "unknown upscaler \"{}\", expected nearest, scale2x, scale3x, eagle or xbr",
+                text
+            )),
+        }
+    }
+
+    //get the next upscaler for the upscaler hotkey
+    pub fn next(self) -> Upscaler {
+        match self {
+            Upscaler::Nearest => Upscaler::Scale2x,
+            Upscaler::Scale2x => Upscaler::Scale3x,
+            Upscaler::Scale3x => Upscaler::Eagle,
+            Upscaler::Eagle => Upscaler::Xbr,
+            Upscaler::Xbr => Upscaler::Nearest,
+        }
+    }
+
+    //get the name of the upscaler in the same form parse reads
+    pub fn name(self) -> &'static str {
+        match self {
+            Upscaler::Nearest => "nearest",
+            Upscaler::Scale2x => "scale2x",
+            Upscaler::Scale3x => "scale3x",
+            Upscaler::Eagle => "eagle",
+            Upscaler::Xbr => "xbr",
+        }
+    }
+
+    //get how many times larger the upscaled frame is on each side
+    pub fn factor(self) -> usize {
+        match self {
+            Upscaler::Nearest => 1,
+            Upscaler::Scale2x | Upscaler::Eagle | Upscaler::Xbr => 2,
+            Upscaler::Scale3x => 3,
+        }
+    }
+
+    //upscale a frame, the new frame is factor times wider and taller
+    pub fn apply(self, frame: &[Color], width: usize, height: usize) -> Vec<Color> {
+        match self {
+            Upscaler::Nearest => frame.to_vec(),
+            Upscaler::Scale2x => scale2x(frame, width, height),
+            Upscaler::Scale3x => scale3x(frame, width, height),
+            Upscaler::Eagle => eagle(frame, width, height),
+            Upscaler::Xbr => xbr(frame, width, height),
+        }
+    }
+}
+
+//get the pixel at an offset from x and y, repeating the edge pixels outside the frame
+fn neighbour(
+    frame: &[Color],
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    dx: i32,
+    dy: i32,
+) -> Color {
+    let x: usize = (x as i32 + dx).clamp(0, width as i32 - 1) as usize;
+    let y: usize = (y as i32 + dy).clamp(0, height as i32 - 1) as usize;
+    frame[y * width + x]
+}
+
+//scale2x, also known as epx or advmame2x
+fn scale2x(frame: &[Color], width: usize, height: usize) -> Vec<Color> {
+    let mut output: Vec<Color> = vec![Color::RGB(0, 0, 0); width * height * 4];
+    let get = |x: usize, y: usize, dx: i32, dy: i32| neighbour(frame, width, height, x, y, dx, dy);
+
+    //loop through each pixel
+    for y in 0..height {
+        for x in 0..width {
+            //get the pixel and the pixels above, left, right and below it
+            let p: Color = get(x, y, 0, 0);
+            let a: Color = get(x, y, 0, -1);
+            let c: Color = get(x, y, -1, 0);
+            let b: Color = get(x, y, 1, 0);
+            let d: Color = get(x, y, 0, 1);
+
+            //fill each corner from a neighbour if it continues an edge
+            let top_left: Color = if c == a && c != d && a != b { a } else { p };
+            let top_right: Color = if a == b && a != c && b != d { b } else { p };
+            let bottom_left: Color = if d == c && d != b && c != a { c } else { p };
+            let bottom_right: Color = if b == d && b != a && d != c { d } else { p };
+
+            //store the 2x2 block
+            let row: usize = width * 2;
+            output[(y * 2) * row + x * 2] = top_left;
+            output[(y * 2) * row + x * 2 + 1] = top_right;
+            output[(y * 2 + 1) * row + x * 2] = bottom_left;
+            output[(y * 2 + 1) * row + x * 2 + 1] = bottom_right;
+        }
+    }
+
+    output
+}
+
+//scale3x, also known as advmame3x
+fn scale3x(frame: &[Color], width: usize, height: usize) -> Vec<Color> {
+    let mut output: Vec<Color> = vec![Color::RGB(0, 0, 0); width * height * 9];
+    let get = |x: usize, y: usize, dx: i32, dy: i32| neighbour(frame, width, height, x, y, dx, dy);
+
+    //loop through each pixel
+    for y in 0..height {
+        for x in 0..width {
+            //get the 3x3 block of pixels around the pixel
+            //a b c
+            //d e f
+            //g h i
+            let a: Color = get(x, y, -1, -1);
+            let b: Color = get(x, y, 0, -1);
+            let c: Color = get(x, y, 1, -1);
+            let d: Color = get(x, y, -1, 0);
+            let e: Color = get(x, y, 0, 0);
+            let f: Color = get(x, y, 1, 0);
+            let g: Color = get(x, y, -1, 1);
+            let h: Color = get(x, y, 0, 1);
+            let i: Color = get(x, y, 1, 1);
+
+            //work out each of the 9 output pixels
+            let block: [Color; 9] = [
+                if d == b && d != h && b != f { d } else { e },
+                if (d == b && d != h && b != f && e != c) || (b == f && b != d && f != h && e != a)
+                {
+                    b
+                } else {
+                    e
+                },
+                if b == f && b != d && f != h { f } else { e },
+                if (d == b && d != h && b != f && e != g) || (d == h && d != b && h != f && e != a)
+                {
+                    d
+                } else {
+                    e
+                },
+                e,
+                if (b == f && b != d && f != h && e != i) || (h == f && h != d && f != b && e != c)
+                {
+                    f
+                } else {
+                    e
+                },
+                if d == h && d != b && h != f { d } else { e },
+                if (h == f && h != d && f != b && e != g) || (d == h && d != b && h != f && e != i)
+                {
+                    h
+                } else {
+                    e
+                },
+                if h == f && h != d && f != b { f } else { e },
+            ];
+
+            //store the 3x3 block
+            let row: usize = width * 3;
+            for (n, color) in block.iter().enumerate() {
+                output[(y * 3 + n / 3) * row + x * 3 + n % 3] = *color;
+            }
+        }
+    }
+
+    output
+}
+
+//eagle, each corner takes the color of its 3 outside neighbours if they all match
+fn eagle(frame: &[Color], width: usize, height: usize) -> Vec<Color> {
+    let mut output: Vec<Color> = vec![Color::RGB(0, 0, 0); width * height * 4];
+    let get = |x: usize, y: usize, dx: i32, dy: i32| neighbour(frame, width, height, x, y, dx, dy);
+
+    //loop through each pixel
+    for y in 0..height {
+        for x in 0..width {
+            let center: Color = get(x, y, 0, 0);
+
+            //work out each corner from the 3 pixels touching it
+            let row: usize = width * 2;
+            for corner_y in 0..2 {
+                for corner_x in 0..2 {
+                    let dx: i32 = corner_x as i32 * 2 - 1;
+                    let dy: i32 = corner_y as i32 * 2 - 1;
+                    let side: Color = get(x, y, dx, 0);
+                    let diagonal: Color = get(x, y, dx, dy);
+                    let vertical: Color = get(x, y, 0, dy);
+
+                    output[(y * 2 + corner_y) * row + x * 2 + corner_x] =
+                        if side == diagonal && diagonal == vertical {
+                            diagonal
+                        } else {
+                            center
+                        };
+                }
+            }
+        }
+    }
+
+    output
+}
+
+//xbr style edge smoothing at 2x
+//each corner looks for an edge running across it by comparing color differences along both
+//diagonals, and blends toward the closest neighbour along the edge when one is found
+fn xbr(frame: &[Color], width: usize, height: usize) -> Vec<Color> {
+    let mut output: Vec<Color> = vec![Color::RGB(0, 0, 0); width * height * 4];
+    let get = |x: usize, y: usize, dx: i32, dy: i32| neighbour(frame, width, height, x, y, dx, dy);
+
+    //loop through each pixel
+    for y in 0..height {
+        for x in 0..width {
+            let row: usize = width * 2;
+            for corner_y in 0..2 {
+                for corner_x in 0..2 {
+                    //mirror the offsets so every corner is handled like the bottom right one
+                    let sx: i32 = corner_x as i32 * 2 - 1;
+                    let sy: i32 = corner_y as i32 * 2 - 1;
+                    let at = |dx: i32, dy: i32| get(x, y, dx * sx, dy * sy);
+
+                    //pixels around the bottom right corner, named as in the xbr description
+                    //      b  c
+                    //   d  e  f  f4
+                    //   g  h  i  i4
+                    //      h5 i5
+                    let e: Color = at(0, 0);
+                    let b: Color = at(0, -1);
+                    let c: Color = at(1, -1);
+                    let d: Color = at(-1, 0);
+                    let f: Color = at(1, 0);
+                    let g: Color = at(-1, 1);
+                    let h: Color = at(0, 1);
+                    let i: Color = at(1, 1);
+                    let f4: Color = at(2, 0);
+                    let i4: Color = at(2, 1);
+                    let h5: Color = at(0, 2);
+                    let i5: Color = at(1, 2);
+
+                    //weight of an edge along h to f against an edge along e to i
+                    let across: u32 = distance(e, c)
+                        + distance(e, g)
+                        + distance(i, f4)
+                        + distance(i, h5)
+                        + 4 * distance(h, f);
+                    let along: u32 = distance(h, d)
+                        + distance(h, i5)
+                        + distance(f, i4)
+                        + distance(f, b)
+                        + 4 * distance(e, i);
+
+                    //blend toward the closer of f and h when the edge crosses this corner
+                    let color: Color = if across < along && e != f && e != h {
+                        let edge: Color = if distance(e, f) <= distance(e, h) {
+                            f
+                        } else {
+                            h
+                        };
+                        blend_color(e, edge, 0.5)
+                    } else {
+                        e
+                    };
+                    output[(y * 2 + corner_y) * row + x * 2 + corner_x] = color;
+                }
+            }
+        }
+    }
+
+    output
+}
+
+//get how different two colors look, comparing brightness more than hue
+fn distance(a: Color, b: Color) -> u32 {
+    let (ay, au, av): (i32, i32, i32) = yuv(a);
+    let (by, bu, bv): (i32, i32, i32) = yuv(b);
+    (48 * (ay - by).abs() + 7 * (au - bu).abs() + 6 * (av - bv).abs()) as u32
+}
+
+//convert a color to brightness and two color difference channels
+fn yuv(color: Color) -> (i32, i32, i32) {
+    let (r, g, b): (i32, i32, i32) = (color.r as i32, color.g as i32, color.b as i32);
+    let y: i32 = (299 * r + 587 * g + 114 * b) / 1000;
+    (y, (b - y) * 493 / 1000, (r - y) * 877 / 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Color = Color::RGB(0xFF, 0xFF, 0xFF);
+    const BLACK: Color = Color::RGB(0, 0, 0);
+
+    //a single lit pixel
+    const DOT: [&str; 3] = ["...", ".#.", "..."];
+    //a staircase with a diagonal edge
+    const STAIRS: [&str; 4] = ["#...", "##..", "###.", "####"];
+
+    //build a frame drawn as rows of # for white and . for black
+    fn frame(rows: &[&str]) -> Vec<Color> {
+        rows.iter()
+            .flat_map(|row| row.chars())
+            .map(|pixel| if pixel == '#' { WHITE } else { BLACK })
+            .collect()
+    }
+
+    //upscale a frame drawn as rows, returning the upscaled rows with + for blended colors
+    fn upscale(upscaler: Upscaler, rows: &[&str]) -> Vec<String> {
+        let width: usize = rows[0].len();
+        let frame: Vec<Color> = frame(rows);
+        let output: Vec<Color> = upscaler.apply(&frame, width, rows.len());
+        assert_eq!(
+            output.len(),
+            frame.len() * upscaler.factor() * upscaler.factor()
+        );
+        output
+            .chunks(width * upscaler.factor())
+            .map(|row| {
+                row.iter()
+                    .map(|pixel| match *pixel {
+                        WHITE => '#',
+                        BLACK => '.',
+                        _ => '+',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_parse_back_to_the_same_upscaler() {
+        let mut upscaler: Upscaler = Upscaler::Nearest;
+        for _ in 0..5 {
+            assert_eq!(Upscaler::parse(upscaler.name()), Ok(upscaler));
+            upscaler = upscaler.next();
+        }
+        assert_eq!(upscaler, Upscaler::Nearest);
+        assert!(Upscaler::parse("hq2x").is_err());
+    }
+
+    #[test]
+    fn nearest_leaves_the_frame_alone() {
+        assert_eq!(upscale(Upscaler::Nearest, &STAIRS), STAIRS);
+    }
+
+    #[test]
+    fn scale2x_keeps_a_single_pixel_square() {
+        assert_eq!(
+            upscale(Upscaler::Scale2x, &DOT),
+            ["......", "......", "..##..", "..##..", "......", "......"]
+        );
+    }
+
+    #[test]
+    fn scale2x_smooths_a_diagonal_edge() {
+        assert_eq!(
+            upscale(Upscaler::Scale2x, &STAIRS),
+            [
+                "##......", "###.....", "###.....", "#####...", "#####...", "#######.", "########",
+                "########"
+            ]
+        );
+    }
+
+    #[test]
+    fn scale3x_keeps_a_single_pixel_square() {
+        assert_eq!(
+            upscale(Upscaler::Scale3x, &DOT),
+            [
+                ".........",
+                ".........",
+                ".........",
+                "...###...",
+                "...###...",
+                "...###...",
+                ".........",
+                ".........",
+                "........."
+            ]
+        );
+    }
+
+    #[test]
+    fn scale3x_smooths_a_diagonal_edge() {
+        assert_eq!(
+            upscale(Upscaler::Scale3x, &STAIRS),
+            [
+                "###.........",
+                "####........",
+                "####........",
+                "#####.......",
+                "######......",
+                "#######.....",
+                "########....",
+                "#########...",
+                "###########.",
+                "############",
+                "############",
+                "############"
+            ]
+        );
+    }
+
+    #[test]
+    fn eagle_fills_corners_from_matching_neighbours() {
+        //every corner of a lone pixel is surrounded by the background, so it is lost
+        assert_eq!(
+            upscale(Upscaler::Eagle, &DOT),
+            ["......", "......", "......", "......", "......", "......"]
+        );
+        assert_eq!(
+            upscale(Upscaler::Eagle, &STAIRS),
+            [
+                "##......", "###.....", "###.....", "#####...", "#####...", "#######.", "########",
+                "########"
+            ]
+        );
+    }
+
+    #[test]
+    fn xbr_blends_along_a_diagonal_edge() {
+        assert_eq!(
+            upscale(Upscaler::Xbr, &STAIRS),
+            [
+                "##......", "##+.....", "###+....", "####+...", "#####+..", "######+.", "########",
+                "########"
+            ]
+        );
+        //the blended pixels are halfway between the two colors
+        let output: Vec<Color> = Upscaler::Xbr.apply(&frame(&STAIRS), 4, 4);
+        assert_eq!(output[8 + 2], Color::RGB(0x80, 0x80, 0x80));
+    }
+
+    #[test]
+    fn xbr_rounds_off_a_single_pixel() {
+        assert_eq!(
+            upscale(Upscaler::Xbr, &DOT),
+            ["......", "......", "..++..", "..++..", "......", "......"]
+        );
+    }
+
+    #[test]
+    fn flat_frames_stay_flat() {
+        for upscaler in [
+            Upscaler::Scale2x,
+            Upscaler::Scale3x,
+            Upscaler::Eagle,
+            Upscaler::Xbr,
+        ] {
+            let output: Vec<String> = upscale(upscaler, &["##", "##"]);
+            assert!(output
+                .iter()
+                .all(|row| row.chars().all(|pixel| pixel == '#')));
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//use declarations
+use sdl2::rect::Rect;
+
+//get the area of the window the display is drawn in
+//the display keeps its aspect ratio and is centered, leaving letterbox bars on the sides
+//with integer scaling each emulated pixel is a whole number of window pixels
+pub fn fit(
+    output_width: u32,
+    output_height: u32,
+    display_width: u32,
+    display_height: u32,
+    integer_scale: bool,
+) -> Rect {
+    let (width, height): (u32, u32) = if integer_scale {
+        //largest whole scale that fits, never smaller than 1
+        let scale: u32 = (output_width / display_width)
+            .min(output_height / display_height)
+            .max(1);
+        (display_width * scale, display_height * scale)
+    } else if output_width * display_height > output_height * display_width {
+        //window is wider than the display, fit to height
+        (
+            output_height * display_width / display_height,
+            output_height,
+        )
+    } else {
+        //window is taller than the display, fit to width
+        (output_width, output_width * display_height / display_width)
+    };
+
+    //center the area in the window
+    Rect::new(
+        (output_width as i32 - width as i32) / 2,
+        (output_height as i32 - height as i32) / 2,
+        width.max(1),
+        height.max(1),
+    )
+}
+
+//get the window rect covered by one emulated pixel
+//edges are rounded per pixel so fractional scales leave no gaps between pixels
+pub fn pixel_rect(area: Rect, display_width: u32, display_height: u32, x: usize, y: usize) -> Rect {
+    let left: i32 = area.x() + (x as u32 * area.width() / display_width) as i32;
+    let right: i32 = area.x() + ((x as u32 + 1) * area.width() / display_width) as i32;
+    let top: i32 = area.y() + (y as u32 * area.height() / display_height) as i32;
+    let bottom: i32 = area.y() + ((y as u32 + 1) * area.height() / display_height) as i32;
+
+    Rect::new(
+        left,
+        top,
+        (right - left).max(1) as u32,
+        (bottom - top).max(1) as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_a_wide_window_to_its_height() {
+        assert_eq!(fit(2000, 500, 64, 32, false), Rect::new(500, 0, 1000, 500));
+    }
+
+    #[test]
+    fn fits_a_tall_window_to_its_width() {
+        assert_eq!(fit(640, 1000, 64, 32, false), Rect::new(0, 340, 640, 320));
+    }
+
+    #[test]
+    fn fills_a_window_with_the_same_aspect_ratio() {
+        assert_eq!(fit(1280, 640, 64, 32, false), Rect::new(0, 0, 1280, 640));
+    }
+
+    #[test]
+    fn integer_scale_uses_the_largest_whole_scale() {
+        assert_eq!(fit(1000, 500, 64, 32, true), Rect::new(20, 10, 960, 480));
+        assert_eq!(fit(2000, 500, 64, 32, true), Rect::new(520, 10, 960, 480));
+        assert_eq!(fit(640, 1000, 64, 32, true), Rect::new(0, 340, 640, 320));
+    }
+
+    #[test]
+    fn windows_smaller_than_the_display() {
+        //without integer scaling the display shrinks to fit
+        assert_eq!(fit(40, 30, 64, 32, false), Rect::new(0, 5, 40, 20));
+        //with it the display stays at scale 1 and hangs over the edges
+        assert_eq!(fit(40, 30, 64, 32, true), Rect::new(-12, -1, 64, 32));
+        //a window too small for a pixel still gets an area
+        assert_eq!(fit(1, 1, 64, 32, false), Rect::new(0, 0, 1, 1));
+    }
+
+    #[test]
+    fn pixels_cover_the_area_without_gaps() {
+        let area: Rect = Rect::new(5, 7, 100, 50);
+        let mut right: i32 = area.x();
+        for x in 0..64 {
+            let pixel: Rect = pixel_rect(area, 64, 32, x, 0);
+            assert_eq!(pixel.x(), right);
+            right = pixel.x() + pixel.width() as i32;
+        }
+        assert_eq!(right, area.x() + area.width() as i32);
+        assert_eq!(pixel_rect(area, 64, 32, 63, 31).bottom(), area.bottom());
+    }
+}
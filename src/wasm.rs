@@ -0,0 +1,131 @@
+//webassembly bindings for embedding the interpreter in a web page, built for
+//wasm32-unknown-unknown with wasm-pack
+//the page draws the framebuffer and plays the audio samples itself, so nothing here needs sdl
+
+//use declarations
+use crate::audio::{AudioStream, BeeperSettings};
+use crate::chip8::{Chip8, DISPLAY_HEIGHT, DISPLAY_WIDTH, PROGRAM_START};
+use wasm_bindgen::prelude::*;
+
+//math.random from javascript, to seed the machine as there is no clock to seed from
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Math)]
+    fn random() -> f64;
+}
+
+//emulator struct and methods
+//a machine with its rom and the audio rendered for it, made in javascript with new Emulator()
+#[wasm_bindgen]
+pub struct Emulator {
+    chip8: Chip8,
+    //the rom, kept for reset
+    rom: Vec<u8>,
+    //seed set from javascript, used again on every reset
+    seed: Option<u32>,
+    audio_stream: AudioStream,
+    //samples from frames run since they were last taken
+    samples: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    //make a machine with no rom, rendering audio at the sample rate of the page's audio context
+    #[wasm_bindgen(constructor)]
+    pub fn new(sample_rate: u32) -> Emulator {
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.seed((random() * u32::MAX as f64) as u64);
+        Emulator {
+            chip8,
+            rom: Vec::new(),
+            seed: None,
+            audio_stream: AudioStream::new(BeeperSettings::default(), sample_rate.max(1)),
+            samples: Vec::new(),
+        }
+    }
+
+    //load a rom at 0x200 and start running it, clearing memory first
+    #[wasm_bindgen(js_name = loadRom)]
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        self.rom = rom.to_vec();
+        self.reset()
+    }
+
+    //start the rom again, with the same random numbers if a seed was set
+    pub fn reset(&mut self) -> Result<(), JsError> {
+        self.chip8.reset();
+        self.samples.clear();
+        self.chip8
+            .load_rom_at(&self.rom, PROGRAM_START)
+            .map_err(|e| JsError::new(&e))?;
+        if let Some(seed) = self.seed {
+            self.chip8.seed(seed as u64);
+        }
+        Ok(())
+    }
+
+    //seed the random number generator now and on every reset, so runs repeat exactly
+    pub fn seed(&mut self, seed: u32) {
+        self.seed = Some(seed);
+        self.chip8.seed(seed as u64);
+    }
+
+    //run instructions until the end of the frame, call 60 times a second for normal speed
+    #[wasm_bindgen(js_name = runFrame)]
+    pub fn run_frame(&mut self) {
+        while !self.chip8.step() {
+            self.audio_stream.instruction(self.chip8.sound_timer != 0);
+        }
+        self.audio_stream.instruction(self.chip8.sound_timer != 0);
+        let samples: Vec<f32> = self.audio_stream.end_frame();
+        self.samples.extend(samples);
+    }
+
+    //press or release a key from 0 to 15, other keys are ignored
+    #[wasm_bindgen(js_name = setKey)]
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        if let Some(input) = self.chip8.input.get_mut(key) {
+            *input = pressed as u8;
+        }
+    }
+
+    //set all 16 keys at once, bit n is key n
+    #[wasm_bindgen(js_name = setKeys)]
+    pub fn set_keys(&mut self, mask: u16) {
+        for (key, pressed) in self.chip8.input.iter_mut().enumerate() {
+            *pressed = (mask >> key) as u8 & 1;
+        }
+    }
+
+    //the display as a Uint8Array, one byte per pixel in rows, 1 for lit
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.chip8.display.to_vec()
+    }
+
+    //the samples rendered since this was last called, as a Float32Array from -1 to 1
+    #[wasm_bindgen(js_name = takeAudio)]
+    pub fn take_audio(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    //whether the beeper is sounding
+    #[wasm_bindgen(getter, js_name = soundActive)]
+    pub fn sound_active(&self) -> bool {
+        self.chip8.sound_timer != 0
+    }
+
+    #[wasm_bindgen(getter, js_name = sampleRate)]
+    pub fn sample_rate(&self) -> u32 {
+        self.audio_stream.sample_rate()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width() -> usize {
+        DISPLAY_WIDTH
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height() -> usize {
+        DISPLAY_HEIGHT
+    }
+}
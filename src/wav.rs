@@ -0,0 +1,173 @@
+//use declarations
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+//get the file name for an audio recording started from the hotkey
+pub fn file_name(rom_name: &str, frame: u64) -> String {
+    format!("{}_frame{}.wav", rom_name, frame)
+}
+
+//wav writer struct and methods
+//writes mono 16 bit pcm, the sizes in the header are filled in when the file is finished
+//a writer dropped without being finished, such as by an error, fills them in itself so the audio
+//written so far can still be played
+pub struct WavWriter {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    samples: u32,
+    finished: bool,
+}
+
+impl WavWriter {
+    //create a wav file and write a header with empty sizes
+    pub fn create(path: &Path, sample_rate: u32) -> Result<WavWriter, String> {
+        let file: File = File::create(path)
+            .map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+        let mut writer: WavWriter = WavWriter {
+            file: BufWriter::new(file),
+            sample_rate,
+            samples: 0,
+            finished: false,
+        };
+        writer
+            .write_header()
+            .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        Ok(writer)
+    }
+
+    //add samples from -1 to 1 to the end of the file
+    pub fn write_samples(&mut self, samples: &[f32]) -> Result<(), String> {
+        for sample in samples {
+            let value: i16 = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file
+                .write_all(&value.to_le_bytes())
+                .map_err(|e| format!("could not write audio: {}", e))?;
+        }
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    //length of the audio written so far in seconds
+    pub fn seconds(&self) -> f64 {
+        self.samples as f64 / self.sample_rate as f64
+    }
+
+    //fill in the sizes in the header and close the file
+    pub fn finish(mut self) -> Result<(), String> {
+        self.finished = true;
+        self.write_sizes()
+            .map_err(|e| format!("could not write audio: {}", e))
+    }
+
+    //rewrite the header with the sizes of the samples written so far
+    fn write_sizes(&mut self) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.file.flush()
+    }
+
+    //write the riff header, format chunk and data chunk header
+    fn write_header(&mut self) -> std::io::Result<()> {
+        let data_size: u32 = self.samples * 2;
+        self.file.write_all(b"RIFF")?;
+        self.file.write_all(&(36 + data_size).to_le_bytes())?;
+        self.file.write_all(b"WAVE")?;
+
+        //format: pcm, 1 channel, 16 bits per sample
+        self.file.write_all(b"fmt ")?;
+        self.file.write_all(&16u32.to_le_bytes())?;
+        self.file.write_all(&1u16.to_le_bytes())?;
+        self.file.write_all(&1u16.to_le_bytes())?;
+        self.file.write_all(&self.sample_rate.to_le_bytes())?;
+        self.file.write_all(&(self.sample_rate * 2).to_le_bytes())?;
+        self.file.write_all(&2u16.to_le_bytes())?;
+        self.file.write_all(&16u16.to_le_bytes())?;
+
+        self.file.write_all(b"data")?;
+        self.file.write_all(&data_size.to_le_bytes())
+    }
+}
+
+impl Drop for WavWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.write_sizes();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    //get a path in the temporary directory that no other test uses
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chip8_wav_{}_{}.wav", std::process::id(), name))
+    }
+
+    //read back a wav file and remove it
+    fn read(path: &Path) -> Vec<u8> {
+        let bytes: Vec<u8> = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        bytes
+    }
+
+    //get the little endian u32 at an offset
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn finishing_fills_in_the_sizes() {
+        let path: PathBuf = temp_path("finish");
+        let mut writer: WavWriter = WavWriter::create(&path, 44100).unwrap();
+        writer.write_samples(&[0.0, 1.0, -1.0]).unwrap();
+        writer.write_samples(&[2.0, 0.5]).unwrap();
+        assert_eq!(writer.seconds(), 5.0 / 44100.0);
+        writer.finish().unwrap();
+
+        let bytes: Vec<u8> = read(&path);
+        assert_eq!(bytes.len(), 44 + 10);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(&bytes, 4), 36 + 10);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&bytes, 24), 44100);
+        assert_eq!(u32_at(&bytes, 28), 88200);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(&bytes, 40), 10);
+
+        //samples past 1 are clipped
+        let samples: Vec<i16> = bytes[44..]
+            .chunks(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(samples, [0, i16::MAX, -i16::MAX, i16::MAX, i16::MAX / 2]);
+    }
+
+    #[test]
+    fn dropping_fills_in_the_sizes() {
+        let path: PathBuf = temp_path("drop");
+        let mut writer: WavWriter = WavWriter::create(&path, 8000).unwrap();
+        writer.write_samples(&[0.25; 100]).unwrap();
+        drop(writer);
+
+        let bytes: Vec<u8> = read(&path);
+        assert_eq!(bytes.len(), 44 + 200);
+        assert_eq!(u32_at(&bytes, 4), 36 + 200);
+        assert_eq!(u32_at(&bytes, 24), 8000);
+        assert_eq!(u32_at(&bytes, 40), 200);
+    }
+
+    #[test]
+    fn empty_recordings_are_valid() {
+        let path: PathBuf = temp_path("empty");
+        WavWriter::create(&path, 44100).unwrap().finish().unwrap();
+
+        let bytes: Vec<u8> = read(&path);
+        assert_eq!(bytes.len(), 44);
+        assert_eq!(u32_at(&bytes, 4), 36);
+        assert_eq!(u32_at(&bytes, 40), 0);
+    }
+}
@@ -0,0 +1,85 @@
+//tests for the webassembly bindings, run with wasm-pack test --node
+#![cfg(target_arch = "wasm32")]
+
+//use declarations
+use chip8_emulator::wasm::Emulator;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const SAMPLE_RATE: u32 = 44100;
+
+//draw the 0 glyph at the top left, then loop
+const DRAW: [u8; 6] = [0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04];
+//set the sound timer to 5, then loop
+const BEEP: [u8; 6] = [0x60, 0x05, 0xF0, 0x18, 0x12, 0x04];
+//draw the glyph of a random number from 0 to 15 at the top left, then loop
+const RANDOM: [u8; 10] = [0xC0, 0x0F, 0xF0, 0x29, 0x61, 0x00, 0xD1, 0x15, 0x12, 0x08];
+//wait for a key, put it in V1 and draw its glyph at the top left, then loop
+const KEY: [u8; 10] = [0xF1, 0x0A, 0xF1, 0x29, 0x60, 0x00, 0xD0, 0x05, 0x12, 0x08];
+
+fn emulator(rom: &[u8]) -> Emulator {
+    let mut emulator: Emulator = Emulator::new(SAMPLE_RATE);
+    emulator.load_rom(rom).unwrap();
+    emulator
+}
+
+#[wasm_bindgen_test]
+fn draws_to_the_framebuffer() {
+    let mut emulator: Emulator = emulator(&DRAW);
+    emulator.run_frame();
+    let framebuffer: Vec<u8> = emulator.framebuffer();
+    assert_eq!(framebuffer.len(), Emulator::width() * Emulator::height());
+    assert_eq!(framebuffer[..8], [1, 1, 1, 1, 0, 0, 0, 0]);
+    assert_eq!(
+        framebuffer[Emulator::width()..Emulator::width() + 4],
+        [1, 0, 0, 1]
+    );
+}
+
+#[wasm_bindgen_test]
+fn renders_audio_while_the_sound_timer_runs() {
+    let mut emulator: Emulator = emulator(&BEEP);
+    emulator.run_frame();
+    assert!(emulator.sound_active());
+    let samples: Vec<f32> = emulator.take_audio();
+    assert!(samples.len().abs_diff(SAMPLE_RATE as usize / 60) <= 1);
+    assert!(samples.iter().any(|sample| *sample != 0.0));
+    assert!(emulator.take_audio().is_empty());
+
+    //the timer runs out after 5 frames and the beeper goes quiet
+    for _ in 0..10 {
+        emulator.run_frame();
+    }
+    assert!(!emulator.sound_active());
+}
+
+#[wasm_bindgen_test]
+fn waits_for_a_key() {
+    let mut emulator: Emulator = emulator(&KEY);
+    emulator.run_frame();
+    assert!(emulator.framebuffer().iter().all(|pixel| *pixel == 0));
+
+    //the key is taken as soon as it is pressed
+    emulator.set_key(0x8, true);
+    emulator.run_frame();
+    emulator.set_key(0x8, false);
+    emulator.run_frame();
+    emulator.run_frame();
+    assert_eq!(emulator.framebuffer()[..4], [1, 1, 1, 1]);
+}
+
+#[wasm_bindgen_test]
+fn reset_repeats_the_random_numbers_after_seeding() {
+    let mut emulator: Emulator = emulator(&RANDOM);
+    emulator.seed(1);
+    emulator.run_frame();
+    let framebuffer: Vec<u8> = emulator.framebuffer();
+    emulator.reset().unwrap();
+    emulator.run_frame();
+    assert_eq!(emulator.framebuffer(), framebuffer);
+}
+
+#[wasm_bindgen_test]
+fn rejects_a_rom_too_big_for_memory() {
+    let mut emulator: Emulator = Emulator::new(SAMPLE_RATE);
+    assert!(emulator.load_rom(&[0; 0x1000]).is_err());
+}